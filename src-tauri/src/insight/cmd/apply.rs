@@ -11,6 +11,7 @@ use regex::Regex;
 use smallvec::SmallVec;
 
 use crate::{
+  expr::{self, Expression},
   io::csv::{config::CsvConfigBuilder, options::CsvOptions},
   utils,
 };
@@ -236,7 +237,7 @@ async fn apply_perform<P: AsRef<Path> + Send + Sync>(
   skiprows: usize,
   flexible: bool,
   threads: Option<usize>,
-) -> Result<()> {
+) -> Result<Option<String>> {
   let columns: Vec<&str> = columns.split('|').collect();
   if columns.is_empty() {
     return Err(anyhow!("At least one column must be specified."));
@@ -307,6 +308,14 @@ async fn apply_perform<P: AsRef<Path> + Send + Sync>(
 
   let mut headers = rdr.headers()?.clone();
 
+  // calcconv templates are compiled once, `<UNIT>` and `<CPC>` templates keep the cpc calculator
+  let expression = if mode == "calcconv" && !expr::is_cpc_template(&formatstr) {
+    let columns: Vec<&str> = headers.iter().collect();
+    Some(Expression::compile(&formatstr, &columns)?)
+  } else {
+    None
+  };
+
   if let Some(ref new_column) = new_column {
     for col in new_column.split(sep_char) {
       headers.push_field(col);
@@ -396,6 +405,8 @@ async fn apply_perform<P: AsRef<Path> + Send + Sync>(
           ApplyCmd::CalcConv => {
             let result = if record[column_index_next].is_empty() {
               String::new()
+            } else if let Some(expression) = &expression {
+              expression.eval_cell(&record)
             } else {
               let mut cell = record[column_index_next].to_owned();
               let record_vec: Vec<String> = record.iter().map(|f| f.to_string()).collect();
@@ -404,12 +415,10 @@ async fn apply_perform<P: AsRef<Path> + Send + Sync>(
                 cell = formatted.to_string();
               }
 
-              let mut append_unit = false;
-              let cell_for_eval = if cell.ends_with("<UNIT>") {
-                append_unit = true;
-                cell.trim_end_matches("<UNIT>")
-              } else {
-                &cell
+              let cell = cell.trim_end();
+              let (cell_for_eval, append_unit) = match cell.strip_suffix("<UNIT>") {
+                Some(cell) => (cell, true),
+                None => (cell.strip_suffix("<CPC>").unwrap_or(cell), false),
               };
               match eval(cell_for_eval, true, Unit::Celsius, false) {
                 Ok(answer) => {
@@ -447,7 +456,10 @@ async fn apply_perform<P: AsRef<Path> + Send + Sync>(
     batch.clear();
   }
 
-  Ok(wtr.flush()?)
+  wtr.flush()?;
+
+  // the rows the expression failed on, shown by the UI
+  Ok(expression.and_then(|e| e.report()))
 }
 
 #[tauri::command]
@@ -464,7 +476,7 @@ pub async fn apply(
   skiprows: usize,
   flexible: bool,
   threads: usize,
-) -> Result<String, String> {
+) -> Result<(String, String), String> {
   let start_time = Instant::now();
 
   match apply_perform(
@@ -483,10 +495,10 @@ pub async fn apply(
   )
   .await
   {
-    Ok(_) => {
+    Ok(report) => {
       let end_time = Instant::now();
       let elapsed_time = end_time.duration_since(start_time).as_secs_f64();
-      Ok(format!("{elapsed_time:.2}"))
+      Ok((report.unwrap_or_default(), format!("{elapsed_time:.2}")))
     }
     Err(err) => Err(format!("{err}")),
   }
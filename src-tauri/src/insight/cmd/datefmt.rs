@@ -83,7 +83,7 @@ const DATE_FORMATS: &[&str] = &[
 ];
 
 /// 尝试将字符串解析为 NaiveDateTime (支持日期,日期时间)
-fn parse_to_naive_datetime(s: &str) -> Option<NaiveDateTime> {
+pub(crate) fn parse_to_naive_datetime(s: &str) -> Option<NaiveDateTime> {
   // 1.parse datetime
   for fmt in DATE_FORMATS {
     if let Ok(dt) = NaiveDateTime::parse_from_str(s, fmt) {
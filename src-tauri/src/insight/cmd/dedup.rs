@@ -0,0 +1,307 @@
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{BufRead, BufReader, BufWriter, Write},
+  path::{Path, PathBuf},
+  time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+use csv::{ByteRecord, ReaderBuilder, WriterBuilder};
+use serde::Serialize;
+use tempfile::{NamedTempFile, TempDir};
+
+use crate::cmd::extsort::{RW_BUFFER_CAPACITY, new_sorter};
+use crate::cmd::join::{SortedRows, key_columns, row_key};
+use crate::io::csv::{options::CsvOptions, selection::Selection};
+use crate::normalize::TextNorm;
+
+type ByteString = Vec<u8>;
+
+/// Options of `run_dedup`
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct DedupOptions {
+  /// key columns, `|` separated, the whole row by default
+  pub keys: Option<String>,
+  /// first (default), last or none of the rows of a key
+  pub keep: Option<String>,
+  /// normalisation of the key values, the output keeps the original values
+  pub norm: TextNorm,
+  /// write the removed rows to `<input>_dedup_removed.csv`
+  pub removed: bool,
+  /// sort the keys on disk instead of holding them in memory
+  pub external: bool,
+}
+
+/// Counts of a dedup, returned by `run_dedup` as JSON for the UI
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DedupStats {
+  pub rows: usize,
+  pub kept: usize,
+  pub removed: usize,
+  /// keys that occur in more than one row
+  pub duplicate_keys: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Keep {
+  First,
+  Last,
+  /// only the keys of one row
+  None,
+}
+
+impl Keep {
+  fn parse(keep: Option<&str>) -> Result<Self> {
+    match keep.unwrap_or("first") {
+      "" | "first" => Ok(Keep::First),
+      "last" => Ok(Keep::Last),
+      "none" => Ok(Keep::None),
+      other => Err(anyhow!("Unsupported keep policy: {other}")),
+    }
+  }
+}
+
+/// The rows of one key: byte offsets of the first and the last row, and the count
+struct Group {
+  first: u64,
+  last: u64,
+  count: usize,
+}
+
+impl Group {
+  fn new(pos: u64) -> Self {
+    Group {
+      first: pos,
+      last: pos,
+      count: 1,
+    }
+  }
+
+  fn push(&mut self, pos: u64) {
+    self.last = pos;
+    self.count += 1;
+  }
+
+  /// The byte offset of the kept row
+  fn kept(&self, keep: Keep) -> Option<u64> {
+    match keep {
+      Keep::First => Some(self.first),
+      Keep::Last => Some(self.last),
+      Keep::None => (self.count == 1).then_some(self.first),
+    }
+  }
+}
+
+fn row_pos(row: &ByteRecord) -> Result<u64> {
+  Ok(row.position().ok_or(anyhow!("position is null"))?.byte())
+}
+
+/// The byte offsets of the kept rows in file order, the keys are held in memory
+fn kept_in_memory(
+  mut rdr: csv::Reader<File>,
+  sel: &Selection,
+  norm: &TextNorm,
+  keep: Keep,
+  stats: &mut DedupStats,
+) -> Result<Vec<u64>> {
+  let mut groups: HashMap<Vec<ByteString>, Group> = HashMap::new();
+  let mut row = ByteRecord::new();
+  while rdr.read_byte_record(&mut row)? {
+    let pos = row_pos(&row)?;
+    groups
+      .entry(row_key(sel, &row, norm))
+      .and_modify(|group| group.push(pos))
+      .or_insert_with(|| Group::new(pos));
+  }
+
+  stats.duplicate_keys = groups.values().filter(|group| group.count > 1).count();
+  let mut kept: Vec<u64> = groups
+    .values()
+    .filter_map(|group| group.kept(keep))
+    .collect();
+  kept.sort_unstable();
+  Ok(kept)
+}
+
+/// Count the duplicate key of a group and write the offset of its kept row
+fn close_group<W: Write>(
+  group: Group,
+  keep: Keep,
+  wtr: &mut W,
+  stats: &mut DedupStats,
+) -> Result<()> {
+  if group.count > 1 {
+    stats.duplicate_keys += 1;
+  }
+  if let Some(pos) = group.kept(keep) {
+    writeln!(wtr, "{pos:020}")?;
+  }
+  Ok(())
+}
+
+/// The byte offsets of the kept rows in file order, the keys are sorted on disk
+/// (in `tmp_dir`) and so are the offsets, the file returned holds one offset per line
+fn kept_external(
+  rdr: csv::Reader<File>,
+  sel: Selection,
+  norm: TextNorm,
+  keep: Keep,
+  tmp_dir: &Path,
+  stats: &mut DedupStats,
+) -> Result<NamedTempFile> {
+  let sorter = new_sorter(tmp_dir)?;
+  let mut rows = SortedRows::new(rdr, sel, norm, tmp_dir, &sorter)?;
+
+  let kept_tfile = NamedTempFile::new_in(tmp_dir)?;
+  let mut kept_wtr = BufWriter::with_capacity(RW_BUFFER_CAPACITY, kept_tfile.as_file());
+  let mut current: Option<(Vec<ByteString>, Group)> = None;
+  while let Some((key, row)) = rows.next_row()? {
+    let pos = row_pos(&row)?;
+    match current.as_mut() {
+      Some((current_key, group)) if *current_key == key => group.push(pos),
+      _ => {
+        if let Some((_, group)) = current.replace((key, Group::new(pos))) {
+          close_group(group, keep, &mut kept_wtr, stats)?;
+        }
+      }
+    }
+  }
+  if let Some((_, group)) = current {
+    close_group(group, keep, &mut kept_wtr, stats)?;
+  }
+  kept_wtr.flush()?;
+  drop(kept_wtr);
+
+  let line_rdr = BufReader::with_capacity(RW_BUFFER_CAPACITY, File::open(kept_tfile.path())?);
+  let sorted = sorter
+    .sort_by(line_rdr.lines(), |a: &String, b: &String| a.cmp(b))
+    .map_err(|e| anyhow!("cannot do external sort: {e:?}"))?;
+  let sorted_tfile = NamedTempFile::new_in(tmp_dir)?;
+  let mut sorted_wtr = BufWriter::with_capacity(RW_BUFFER_CAPACITY, sorted_tfile.as_file());
+  for line in sorted {
+    let line = line.map_err(|e| anyhow!("cannot do external sort: {e:?}"))?;
+    writeln!(sorted_wtr, "{line}")?;
+  }
+  sorted_wtr.flush()?;
+  drop(sorted_wtr);
+  kept_tfile.close()?;
+
+  Ok(sorted_tfile)
+}
+
+/// Remove the duplicate rows of `path` by key columns (the whole row by default),
+/// the kept rows are written in file order to `<input>_dedup.csv`
+pub async fn run_dedup<P: AsRef<Path> + Send + Sync>(
+  path: P,
+  quoting: bool,
+  options: DedupOptions,
+) -> Result<DedupStats> {
+  let opts = CsvOptions::new(&path);
+  let sep = opts.detect_separator()?;
+  let output_path = opts.output_path(Some("dedup"), None)?;
+  let removed_path = match options.removed {
+    true => Some(opts.output_path(Some("dedup_removed"), None)?),
+    false => None,
+  };
+  let tmp_parent = match opts.parent_path()? {
+    "" => PathBuf::from("."),
+    parent => PathBuf::from(parent),
+  };
+  let keep = Keep::parse(options.keep.as_deref())?;
+  let reader = || -> Result<csv::Reader<File>> {
+    Ok(
+      ReaderBuilder::new()
+        .delimiter(sep)
+        .quoting(quoting)
+        .from_reader(File::open(path.as_ref())?),
+    )
+  };
+
+  let mut rdr = reader()?;
+  let headers = rdr.byte_headers()?.clone();
+  let sel = match options.keys.as_deref().map(key_columns) {
+    Some(keys) if !keys.is_empty() => Selection::from_headers(&headers, &keys)?,
+    _ => Selection::all(headers.len()),
+  };
+
+  // first pass, the byte offsets of the kept rows
+  let mut stats = DedupStats::default();
+  let (kept, _tmp_dir): (Box<dyn Iterator<Item = Result<u64>>>, _) = match options.external {
+    true => {
+      let tmp_dir = TempDir::new_in(tmp_parent)?;
+      let kept_file = kept_external(rdr, sel, options.norm, keep, tmp_dir.path(), &mut stats)?;
+      let lines = BufReader::with_capacity(RW_BUFFER_CAPACITY, File::open(kept_file.path())?)
+        .lines()
+        .map(|line| -> Result<u64> { Ok(line?.parse::<u64>()?) });
+      (Box::new(lines), Some((tmp_dir, kept_file)))
+    }
+    false => {
+      let kept = kept_in_memory(rdr, &sel, &options.norm, keep, &mut stats)?;
+      (Box::new(kept.into_iter().map(Ok::<_, anyhow::Error>)), None)
+    }
+  };
+
+  // second pass, the rows in file order
+  let mut wtr = WriterBuilder::new().delimiter(sep).from_path(output_path)?;
+  wtr.write_record(&headers)?;
+  let mut removed_wtr = match removed_path {
+    Some(path) => {
+      let mut wtr = WriterBuilder::new().delimiter(sep).from_path(path)?;
+      wtr.write_record(&headers)?;
+      Some(wtr)
+    }
+    None => None,
+  };
+  let mut kept = kept.peekable();
+  let mut rdr = reader()?;
+  let mut row = ByteRecord::new();
+  while rdr.read_byte_record(&mut row)? {
+    stats.rows += 1;
+    let pos = row_pos(&row)?;
+    let is_kept = match kept.peek() {
+      Some(Ok(next)) => *next == pos,
+      Some(Err(e)) => return Err(anyhow!("{e}")),
+      None => false,
+    };
+    match is_kept {
+      true => {
+        kept.next();
+        stats.kept += 1;
+        wtr.write_byte_record(&row)?;
+      }
+      false => {
+        stats.removed += 1;
+        if let Some(wtr) = removed_wtr.as_mut() {
+          wtr.write_byte_record(&row)?;
+        }
+      }
+    }
+  }
+  wtr.flush()?;
+  if let Some(mut wtr) = removed_wtr {
+    wtr.flush()?;
+  }
+
+  Ok(stats)
+}
+
+#[tauri::command]
+pub async fn dedup(
+  path: String,
+  quoting: bool,
+  options: Option<DedupOptions>,
+) -> Result<(String, String), String> {
+  let start_time = Instant::now();
+
+  match run_dedup(path, quoting, options.unwrap_or_default()).await {
+    Ok(stats) => {
+      let end_time = Instant::now();
+      let elapsed_time = end_time.duration_since(start_time).as_secs_f64();
+      let stats = serde_json::to_string(&stats).map_err(|e| format!("{e}"))?;
+      Ok((stats, format!("{elapsed_time:.2}")))
+    }
+    Err(err) => Err(format!("{err}")),
+  }
+}
@@ -0,0 +1,409 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs::File,
+  path::{Path, PathBuf},
+  time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+use csv::{ByteRecord, ReaderBuilder, WriterBuilder};
+use serde::Serialize;
+use tempfile::{NamedTempFile, TempDir};
+
+use crate::cmd::extsort::new_sorter;
+use crate::cmd::join::{SortedRows, join_source, key_columns, row_key};
+use crate::io::csv::{options::CsvOptions, selection::Selection};
+use crate::normalize::TextNorm;
+
+type ByteString = Vec<u8>;
+
+/// Options of `run_diff`
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct DiffOptions {
+  /// normalisation of the key values, the output keeps the original values
+  pub norm: TextNorm,
+  /// sort both files on disk and merge them instead of holding the keys of the
+  /// old file in memory, the output is in key order
+  pub sort_merge: bool,
+  /// columns that are not compared, `|` separated
+  pub ignore_columns: Option<String>,
+}
+
+/// Counts of a diff, returned by `run_diff` as JSON for the UI
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DiffStats {
+  pub old_rows: usize,
+  pub new_rows: usize,
+  pub added: usize,
+  pub removed: usize,
+  pub changed: usize,
+  pub unchanged: usize,
+  /// the changed values of the changed rows
+  pub changed_cells: usize,
+  /// columns only in the new file
+  pub added_columns: Vec<String>,
+  /// columns only in the old file
+  pub removed_columns: Vec<String>,
+}
+
+/// Writes the added, removed and changed rows to `<old>_diff.csv` and the
+/// changed values to `<old>_diff_cells.csv`, the files are written next to
+/// them and only moved in place by `finish`, a failed diff leaves no output
+struct DiffWriter {
+  wtr: csv::Writer<File>,
+  cells: csv::Writer<File>,
+  outputs: Vec<(NamedTempFile, PathBuf)>,
+  headers: ByteRecord,
+  /// the old key columns
+  keys: Vec<usize>,
+  /// the index in the new row of every old column
+  columns: Vec<Option<usize>>,
+  /// the new columns that are not in the old file
+  new_only: Vec<usize>,
+  /// the old columns that are compared
+  compared: Vec<bool>,
+  stats: DiffStats,
+}
+
+impl DiffWriter {
+  fn new(
+    old: &ByteRecord,
+    new: &ByteRecord,
+    keys: &Selection,
+    ignore: &[&str],
+    output_paths: [PathBuf; 2],
+    delimiter: u8,
+  ) -> Result<Self> {
+    let name = |field: &[u8]| String::from_utf8_lossy(field).into_owned();
+    for column in ignore {
+      if !old.iter().chain(new).any(|field| name(field) == *column) {
+        return Err(anyhow!("Column '{column}' not found in headers."));
+      }
+    }
+    let columns: Vec<Option<usize>> = old
+      .iter()
+      .map(|field| new.iter().position(|f| f == field))
+      .collect();
+    let new_only: Vec<usize> = (0..new.len())
+      .filter(|&j| !old.iter().any(|field| field == &new[j]))
+      .collect();
+    let compared = old
+      .iter()
+      .map(|field| !ignore.contains(&name(field).as_str()))
+      .collect();
+
+    let stats = DiffStats {
+      added_columns: new_only.iter().map(|&j| name(&new[j])).collect(),
+      removed_columns: old
+        .iter()
+        .zip(&columns)
+        .filter(|(_, j)| j.is_none())
+        .map(|(field, _)| name(field))
+        .collect(),
+      ..Default::default()
+    };
+
+    let mut outputs = Vec::with_capacity(output_paths.len());
+    for path in output_paths {
+      let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+      };
+      outputs.push((NamedTempFile::new_in(dir)?, path));
+    }
+    let writer = |tfile: &NamedTempFile| -> Result<csv::Writer<File>> {
+      Ok(
+        WriterBuilder::new()
+          .delimiter(delimiter)
+          .from_writer(tfile.as_file().try_clone()?),
+      )
+    };
+    let mut wtr = writer(&outputs[0].0)?;
+    let mut cells = writer(&outputs[1].0)?;
+    let mut headers = ByteRecord::new();
+    headers.push_field(b"diff");
+    headers.extend(old.iter());
+    headers.extend(new_only.iter().map(|&j| &new[j]));
+    wtr.write_record(&headers)?;
+    let keys = keys.get_indices().clone();
+    let mut cell_headers: ByteRecord = keys.iter().map(|&i| &old[i]).collect();
+    cell_headers.extend([&b"column"[..], &b"old_value"[..], &b"new_value"[..]]);
+    cells.write_record(&cell_headers)?;
+
+    Ok(DiffWriter {
+      wtr,
+      cells,
+      outputs,
+      headers: old.clone(),
+      keys,
+      columns,
+      new_only,
+      compared,
+      stats,
+    })
+  }
+
+  fn added(&mut self, new: &ByteRecord) -> Result<()> {
+    self.stats.added += 1;
+    let old = self.columns.iter().map(|j| j.map_or(&b""[..], |j| &new[j]));
+    let new_only = self.new_only.iter().map(|&j| &new[j]);
+    let row = [&b"added"[..]].into_iter().chain(old).chain(new_only);
+    Ok(self.wtr.write_record(row)?)
+  }
+
+  fn removed(&mut self, old: &ByteRecord) -> Result<()> {
+    self.stats.removed += 1;
+    let new_only = self.new_only.iter().map(|_| &b""[..]);
+    let row = [&b"removed"[..]].into_iter().chain(old).chain(new_only);
+    Ok(self.wtr.write_record(row)?)
+  }
+
+  /// The rows of the same key, written with the new values when they differ
+  fn compare(&mut self, old: &ByteRecord, new: &ByteRecord) -> Result<()> {
+    let mut changed = false;
+    for (i, column) in self.columns.iter().enumerate() {
+      let Some(j) = *column else { continue };
+      if !self.compared[i] || old[i] == new[j] {
+        continue;
+      }
+      changed = true;
+      self.stats.changed_cells += 1;
+      let key = self.keys.iter().map(|&k| &old[k]);
+      let cell = [&self.headers[i], &old[i], &new[j]];
+      self.cells.write_record(key.chain(cell))?;
+    }
+
+    if !changed {
+      self.stats.unchanged += 1;
+      return Ok(());
+    }
+    self.stats.changed += 1;
+    let values = self
+      .columns
+      .iter()
+      .enumerate()
+      .map(|(i, j)| j.map_or(&old[i], |j| &new[j]));
+    let new_only = self.new_only.iter().map(|&j| &new[j]);
+    let row = [&b"changed"[..]].into_iter().chain(values).chain(new_only);
+    Ok(self.wtr.write_record(row)?)
+  }
+
+  fn finish(mut self, old_rows: usize, new_rows: usize) -> Result<DiffStats> {
+    self.wtr.flush()?;
+    self.cells.flush()?;
+    for (tfile, path) in self.outputs {
+      tfile.persist(path)?;
+    }
+    self.stats.old_rows = old_rows;
+    self.stats.new_rows = new_rows;
+    Ok(self.stats)
+  }
+}
+
+fn duplicate_key(key: &[ByteString], file: &str) -> anyhow::Error {
+  let key: Vec<_> = key.iter().map(|f| String::from_utf8_lossy(f)).collect();
+  anyhow!("Duplicate key '{}' in the {file} file", key.join("|"))
+}
+
+/// Index the keys of the old file, stream the new file and seek the old rows
+fn diff_in_memory(
+  mut old: csv::Reader<File>,
+  mut new: csv::Reader<File>,
+  sel1: &Selection,
+  sel2: &Selection,
+  norm: &TextNorm,
+  dw: &mut DiffWriter,
+) -> Result<(usize, usize)> {
+  let mut positions = Vec::new();
+  let mut index: HashMap<Vec<ByteString>, usize> = HashMap::new();
+  let mut row = ByteRecord::new();
+  while old.read_byte_record(&mut row)? {
+    let key = row_key(sel1, &row, norm);
+    if index.insert(key.clone(), positions.len()).is_some() {
+      return Err(duplicate_key(&key, "old"));
+    }
+    positions.push(row.position().ok_or(anyhow!("position is null"))?.clone());
+  }
+
+  let mut seen = vec![false; positions.len()];
+  let mut added: HashSet<Vec<ByteString>> = HashSet::new();
+  let (mut old_row, mut new_rows) = (ByteRecord::new(), 0);
+  while new.read_byte_record(&mut row)? {
+    new_rows += 1;
+    let key = row_key(sel2, &row, norm);
+    match index.get(&key) {
+      Some(&i) => {
+        if seen[i] {
+          return Err(duplicate_key(&key, "new"));
+        }
+        seen[i] = true;
+        old.seek(positions[i].clone())?;
+        old.read_byte_record(&mut old_row)?;
+        dw.compare(&old_row, &row)?;
+      }
+      None => {
+        if !added.insert(key.clone()) {
+          return Err(duplicate_key(&key, "new"));
+        }
+        dw.added(&row)?;
+      }
+    }
+  }
+
+  // the old rows without a new row, in file order
+  for (i, position) in positions.iter().enumerate() {
+    if !seen[i] {
+      old.seek(position.clone())?;
+      old.read_byte_record(&mut old_row)?;
+      dw.removed(&old_row)?;
+    }
+  }
+  Ok((positions.len(), new_rows))
+}
+
+/// Merge both files sorted on disk (in `tmp_dir`), the output is in key order
+fn diff_sort_merge(
+  old: csv::Reader<File>,
+  new: csv::Reader<File>,
+  sel1: Selection,
+  sel2: Selection,
+  norm: TextNorm,
+  tmp_dir: &Path,
+  dw: &mut DiffWriter,
+) -> Result<(usize, usize)> {
+  let sorter = new_sorter(tmp_dir)?;
+  let mut old = SortedRows::new(old, sel1, norm, tmp_dir, &sorter)?;
+  let mut new = SortedRows::new(new, sel2, norm, tmp_dir, &sorter)?;
+  let next = |rows: &mut SortedRows, file: &str| -> Result<_> {
+    match rows.next_group()? {
+      Some((key, rows)) if rows.len() > 1 => Err(duplicate_key(&key, file)),
+      group => Ok(group.map(|(key, mut rows)| (key, rows.remove(0)))),
+    }
+  };
+
+  let (mut old_rows, mut new_rows) = (0, 0);
+  let (mut a, mut b) = (next(&mut old, "old")?, next(&mut new, "new")?);
+  loop {
+    match (&a, &b) {
+      (None, None) => break,
+      (Some((k1, row1)), Some((k2, row2))) if k1 == k2 => {
+        dw.compare(row1, row2)?;
+        (old_rows, new_rows) = (old_rows + 1, new_rows + 1);
+        (a, b) = (next(&mut old, "old")?, next(&mut new, "new")?);
+      }
+      (Some((k1, row1)), Some((k2, _))) if k1 < k2 => {
+        dw.removed(row1)?;
+        old_rows += 1;
+        a = next(&mut old, "old")?;
+      }
+      (Some((_, row1)), None) => {
+        dw.removed(row1)?;
+        old_rows += 1;
+        a = next(&mut old, "old")?;
+      }
+      (_, Some((_, row2))) => {
+        dw.added(row2)?;
+        new_rows += 1;
+        b = next(&mut new, "new")?;
+      }
+    }
+  }
+  Ok((old_rows, new_rows))
+}
+
+/// Compare the old (`path1`) and new (`path2`) file by `keys`, `|` separated.
+/// The added, removed and changed rows are written to `<old>_diff.csv`,
+/// the changed values to `<old>_diff_cells.csv`.
+pub async fn run_diff<P: AsRef<Path> + Send + Sync>(
+  path1: P,
+  path2: P,
+  keys: String,
+  quoting: bool,
+  options: DiffOptions,
+) -> Result<DiffStats> {
+  let opts1 = CsvOptions::new(&path1);
+  let output_paths = [
+    opts1.output_path(Some("diff"), None)?,
+    opts1.output_path(Some("diff_cells"), None)?,
+  ];
+  let tmp_parent = match opts1.parent_path()? {
+    "" => PathBuf::from("."),
+    parent => PathBuf::from(parent),
+  };
+
+  // the csv inputs are streamed, the others are converted to csv first
+  let mut tmp_dir = None;
+  let src1 = join_source(path1.as_ref(), &mut tmp_dir)?;
+  let src2 = join_source(path2.as_ref(), &mut tmp_dir)?;
+  let path1 = src1.unwrap_or_else(|| path1.as_ref().to_path_buf());
+  let path2 = src2.unwrap_or_else(|| path2.as_ref().to_path_buf());
+
+  let sep1 = CsvOptions::new(&path1).detect_separator()?;
+  let sep2 = CsvOptions::new(&path2).detect_separator()?;
+  let mut rdr1 = ReaderBuilder::new()
+    .delimiter(sep1)
+    .quoting(quoting)
+    .from_reader(File::open(&path1)?);
+  let mut rdr2 = ReaderBuilder::new()
+    .delimiter(sep2)
+    .quoting(quoting)
+    .from_reader(File::open(&path2)?);
+
+  let keys = key_columns(&keys);
+  if keys.is_empty() {
+    return Err(anyhow!("No key column selected"));
+  }
+  let headers1 = rdr1.byte_headers()?.clone();
+  let headers2 = rdr2.byte_headers()?.clone();
+  let sel1 = Selection::from_headers(&headers1, &keys)?;
+  let sel2 = Selection::from_headers(&headers2, &keys)?;
+  let ignore = options.ignore_columns.as_deref().map(key_columns);
+  let mut dw = DiffWriter::new(
+    &headers1,
+    &headers2,
+    &sel1,
+    &ignore.unwrap_or_default(),
+    output_paths,
+    sep1,
+  )?;
+
+  let (old_rows, new_rows) = match options.sort_merge {
+    true => {
+      let sort_dir = TempDir::new_in(tmp_parent)?;
+      diff_sort_merge(
+        rdr1,
+        rdr2,
+        sel1,
+        sel2,
+        options.norm,
+        sort_dir.path(),
+        &mut dw,
+      )?
+    }
+    false => diff_in_memory(rdr1, rdr2, &sel1, &sel2, &options.norm, &mut dw)?,
+  };
+
+  dw.finish(old_rows, new_rows)
+}
+
+#[tauri::command]
+pub async fn diff(
+  path1: String,
+  path2: String,
+  keys: String,
+  quoting: bool,
+  options: Option<DiffOptions>,
+) -> Result<(String, String), String> {
+  let start_time = Instant::now();
+
+  match run_diff(path1, path2, keys, quoting, options.unwrap_or_default()).await {
+    Ok(stats) => {
+      let end_time = Instant::now();
+      let elapsed_time = end_time.duration_since(start_time).as_secs_f64();
+      let stats = serde_json::to_string(&stats).map_err(|e| format!("{e}"))?;
+      Ok((stats, format!("{elapsed_time:.2}")))
+    }
+    Err(err) => Err(format!("{err}")),
+  }
+}
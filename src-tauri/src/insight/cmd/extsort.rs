@@ -12,9 +12,22 @@ use ext_sort::{ExternalSorter, ExternalSorterBuilder, LimitedBufferBuilder};
 use crate::io::csv::{options::CsvOptions, selection::Selection};
 use crate::utils;
 
-const RW_BUFFER_CAPACITY: usize = 1_000_000; // 1 MB
+pub(crate) const RW_BUFFER_CAPACITY: usize = 1_000_000; // 1 MB
 const MEMORY_LIMITED_BUFFER: usize = 100 * 1_000_000; // 100 MB
 
+/// External sorter of text lines, the sorted chunks are written to `tmp_dir`
+pub(crate) fn new_sorter(
+  tmp_dir: &Path,
+) -> Result<ExternalSorter<String, io::Error, LimitedBufferBuilder>> {
+  ExternalSorterBuilder::new()
+    .with_tmp_dir(tmp_dir)
+    .with_buffer(LimitedBufferBuilder::new(MEMORY_LIMITED_BUFFER, true))
+    .with_rw_buf_size(RW_BUFFER_CAPACITY)
+    .with_threads_number(utils::num_cpus())
+    .build()
+    .map_err(|e| anyhow!("cannot create external sorter: {e}"))
+}
+
 pub async fn sort_csv(
   path: String,
   sel_column: String,
@@ -150,19 +163,10 @@ pub async fn extsort(
   let start_time = Instant::now();
   let tmp_dir = "./".to_string();
 
-  let sorter: ExternalSorter<String, io::Error, LimitedBufferBuilder> =
-    match ExternalSorterBuilder::new()
-      .with_tmp_dir(Path::new(&tmp_dir))
-      .with_buffer(LimitedBufferBuilder::new(MEMORY_LIMITED_BUFFER, true))
-      .with_rw_buf_size(RW_BUFFER_CAPACITY)
-      .with_threads_number(utils::num_cpus())
-      .build()
-    {
-      Ok(sorter) => sorter,
-      Err(e) => {
-        return Err(format!("cannot create external sorter: {e}"));
-      }
-    };
+  let sorter = match new_sorter(Path::new(&tmp_dir)) {
+    Ok(sorter) => sorter,
+    Err(e) => return Err(format!("{e}")),
+  };
 
   match sort_csv(path, column, reverse, &tmp_dir, &sorter, quoting).await {
     Ok(_) => {
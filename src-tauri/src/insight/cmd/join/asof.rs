@@ -0,0 +1,195 @@
+use std::{
+  collections::HashMap,
+  io::{Read, Seek, Write},
+};
+
+use anyhow::{Result, anyhow};
+use csv::ByteRecord;
+
+use super::{ByteString, IoState, JoinStats, row_key};
+use crate::cmd::search::dates::parse_date;
+use crate::io::csv::selection::Selection;
+
+/// Options of the `asof` join type
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct AsofJoinOptions {
+  /// the date or number column of the left file
+  pub left_on: String,
+  /// the date or number column of the right file
+  pub right_on: String,
+  /// backward (default), forward or nearest
+  pub direction: Option<String>,
+  /// maximum distance between the values, in days for dates
+  pub tolerance: Option<f64>,
+  /// chrono format of the dates, e.g. `%Y%m%d`, the values are parsed as numbers
+  /// or with the flexible date parser otherwise
+  pub date_format: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+  /// the greatest right value <= the left value
+  Backward,
+  /// the smallest right value >= the left value
+  Forward,
+  /// the closest right value, backward on a tie
+  Nearest,
+}
+
+impl Direction {
+  fn parse(direction: Option<&str>) -> Result<Self> {
+    match direction.unwrap_or("backward") {
+      "" | "backward" => Ok(Direction::Backward),
+      "forward" => Ok(Direction::Forward),
+      "nearest" => Ok(Direction::Nearest),
+      other => Err(anyhow!("Unsupported asof direction: {other}")),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValueKind {
+  Number,
+  Date,
+}
+
+impl ValueKind {
+  fn name(self) -> &'static str {
+    match self {
+      ValueKind::Number => "number",
+      ValueKind::Date => "date",
+    }
+  }
+}
+
+/// A number, or a date as days since the epoch
+fn asof_value(value: &str, format: Option<&str>) -> Option<(f64, ValueKind)> {
+  let value = value.trim();
+  let days = |dt: chrono::NaiveDateTime| dt.and_utc().timestamp() as f64 / 86400.0;
+  let date = |format| parse_date(value, format).map(|dt| (days(dt), ValueKind::Date));
+  match format {
+    None => value
+      .parse::<f64>()
+      .ok()
+      .map(|v| (v, ValueKind::Number))
+      .or_else(|| date(None)),
+    Some(_) => date(format),
+  }
+}
+
+/// The index in `values` (sorted by value) of the match of `x`
+fn asof_search(values: &[(f64, usize)], x: f64, direction: Direction) -> Option<usize> {
+  // the first value > x and the first value >= x
+  let after = values.partition_point(|(v, _)| *v <= x);
+  let from = values.partition_point(|(v, _)| *v < x);
+  let backward = after.checked_sub(1);
+  let forward = (from < values.len()).then_some(from);
+  match direction {
+    Direction::Backward => backward,
+    Direction::Forward => forward,
+    Direction::Nearest => match (backward, forward) {
+      (Some(b), Some(f)) => match x - values[b].0 <= values[f].0 - x {
+        true => Some(b),
+        false => Some(f),
+      },
+      (b, f) => b.or(f),
+    },
+  }
+}
+
+impl<R: Read + Seek, W: Write> IoState<R, W> {
+  /// Every left row with the right row of the same key whose date (or number)
+  /// is the closest before (backward), after (forward) or either side (nearest)
+  /// of the left value, padded when there is none.
+  /// The right file is held in memory, the left file is streamed.
+  pub(super) fn asof_join(mut self, opts: &AsofJoinOptions) -> Result<JoinStats> {
+    let direction = Direction::parse(opts.direction.as_deref())?;
+    if opts.tolerance.is_some_and(|t| t < 0.0) {
+      return Err(anyhow!("The asof tolerance must not be negative"));
+    }
+    let format = opts.date_format.as_deref().filter(|f| !f.is_empty());
+    let on1 = Selection::from_headers(self.rdr1.byte_headers()?, &[opts.left_on.as_str()])?;
+    let on2 = Selection::from_headers(self.rdr2.byte_headers()?, &[opts.right_on.as_str()])?;
+    // `20240105` reads as a number and `2024-01-05` as a date, the two can't be compared
+    let mut kind = None;
+    let mut value_of = |sel: &Selection, row: &ByteRecord| -> Result<Option<f64>> {
+      let Some(raw) = sel.get_row_key(row).into_iter().next() else {
+        return Ok(None);
+      };
+      let raw = String::from_utf8_lossy(&raw);
+      let Some((value, k)) = asof_value(&raw, format) else {
+        return Ok(None);
+      };
+      let first = *kind.get_or_insert(k);
+      match first == k {
+        true => Ok(Some(value)),
+        false => Err(anyhow!(
+          "The asof value '{raw}' is a {} but the earlier values are {}s, set date_format",
+          k.name(),
+          first.name()
+        )),
+      }
+    };
+
+    self.write_headers(true)?;
+    let (_, pad2) = self.get_padding()?;
+
+    // the values of every right key, sorted and in file order on a tie
+    let mut rows2 = Vec::new();
+    let mut groups: HashMap<Vec<ByteString>, Vec<(f64, usize)>> = HashMap::new();
+    for row in self.rdr2.byte_records() {
+      let row = row?;
+      let key = row_key(&self.sel2, &row, &self.norm);
+      let value = value_of(&on2, &row)?;
+      let Some(value) = value.filter(|_| self.nulls || !key.iter().any(|f| f.is_empty())) else {
+        self.tracker.right(Some(&row), false)?;
+        continue;
+      };
+      groups.entry(key).or_default().push((value, rows2.len()));
+      rows2.push(row);
+    }
+    for values in groups.values_mut() {
+      values.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+    // the same key and value more than once, only one of them is ever matched
+    let duplicates = groups
+      .values()
+      .map(|values| {
+        values
+          .chunk_by(|a, b| a.0 == b.0)
+          .filter(|run| run.len() > 1)
+          .count()
+      })
+      .sum();
+    self.tracker.right_duplicate_keys(duplicates);
+    let mut matched_rows = vec![false; rows2.len()];
+
+    for row in self.rdr1.byte_records() {
+      let row = row?;
+      let key = row_key(&self.sel1, &row, &self.norm);
+      let matched = groups
+        .get(&key)
+        .zip(value_of(&on1, &row)?)
+        .and_then(|(values, x)| {
+          asof_search(values, x, direction)
+            .filter(|&i| opts.tolerance.is_none_or(|t| (values[i].0 - x).abs() <= t))
+            .map(|i| values[i].1)
+        });
+      self.tracker.left(&key, &row, matched.map_or(0, |_| 1))?;
+      match matched {
+        Some(i) => {
+          matched_rows[i] = true;
+          self.wtr.write_record(row.iter().chain(&rows2[i]))?
+        }
+        None => self.wtr.write_record(row.iter().chain(&pad2))?,
+      }
+    }
+    self.wtr.flush()?;
+
+    for (row, matched) in rows2.iter().zip(matched_rows) {
+      self.tracker.right(Some(row), matched)?;
+    }
+    self.tracker.finish(self.wtr.rows)
+  }
+}
@@ -0,0 +1,196 @@
+use std::{
+  collections::HashMap,
+  io::{Read, Seek, Write},
+};
+
+use anyhow::{Result, anyhow};
+use csv::ByteRecord;
+
+use super::{ByteString, IoState, JoinStats, key_columns, row_key};
+use crate::cmd::search::fuzzy::{Metric, format_score, parse_threshold};
+use crate::io::csv::selection::Selection;
+
+/// Options of the `fuzzy` join type
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct FuzzyJoinOptions {
+  /// levenshtein (default), jaro_winkler or token_set
+  pub metric: Option<String>,
+  /// minimum similarity, 0.8 by default (0 when only `max_distance` is set)
+  pub threshold: Option<f64>,
+  /// maximum edit distance between the keys
+  pub max_distance: Option<usize>,
+  /// the best N right rows of every left row, 1 by default
+  pub top: Option<usize>,
+  /// only compare keys with the same first N chars
+  pub block_prefix: usize,
+  /// only compare rows with equal values in these columns, `|` separated
+  pub block_left: Option<String>,
+  pub block_right: Option<String>,
+  /// `fuzzy_score` by default
+  pub score_column: Option<String>,
+  /// the key of the matched right row, `fuzzy_key` by default
+  pub key_column: Option<String>,
+}
+
+/// The selections of the blocking columns of both sides
+fn block_selections(
+  opts: &FuzzyJoinOptions,
+  headers1: &ByteRecord,
+  headers2: &ByteRecord,
+) -> Result<(Option<Selection>, Option<Selection>)> {
+  let left = opts
+    .block_left
+    .as_deref()
+    .map(key_columns)
+    .unwrap_or_default();
+  let right = opts
+    .block_right
+    .as_deref()
+    .map(key_columns)
+    .unwrap_or_default();
+  if left.len() != right.len() {
+    return Err(anyhow!(
+      "The number of blocking columns differs: {} in the left file, {} in the right file",
+      left.len(),
+      right.len()
+    ));
+  }
+  if left.is_empty() {
+    return Ok((None, None));
+  }
+  Ok((
+    Some(Selection::from_headers(headers1, &left)?),
+    Some(Selection::from_headers(headers2, &right)?),
+  ))
+}
+
+impl<R: Read + Seek, W: Write> IoState<R, W> {
+  /// Every left row with the best `top` right rows whose keys are similar,
+  /// with the score and the matched right key as extra columns.
+  /// The right file is held in memory, the left file is streamed.
+  pub(super) fn fuzzy_join(mut self, opts: &FuzzyJoinOptions) -> Result<JoinStats> {
+    let metric = Metric::parse(opts.metric.as_deref())?;
+    let threshold = match (opts.threshold, opts.max_distance) {
+      (None, Some(_)) => 0.0,
+      (threshold, _) => parse_threshold(threshold)?,
+    };
+    let top = opts.top.unwrap_or(1).max(1);
+    let (block1, block2) =
+      block_selections(opts, self.rdr1.byte_headers()?, self.rdr2.byte_headers()?)?;
+
+    let score_column = opts
+      .score_column
+      .as_deref()
+      .filter(|c| !c.is_empty())
+      .unwrap_or("fuzzy_score");
+    let key_column = opts
+      .key_column
+      .as_deref()
+      .filter(|c| !c.is_empty())
+      .unwrap_or("fuzzy_key");
+    self.write_headers_with(true, &[score_column.as_bytes(), key_column.as_bytes()])?;
+
+    let (norm, nulls, prefix) = (self.norm, self.nulls, opts.block_prefix);
+    // the normalised key fields joined by ' ', `None` for a null key
+    let fuzzy_key = |sel: &Selection, row: &ByteRecord| -> Option<String> {
+      let fields = row_key(sel, row, &norm);
+      if !nulls && fields.iter().any(|f| f.is_empty()) {
+        return None;
+      }
+      Some(
+        fields
+          .iter()
+          .map(|f| String::from_utf8_lossy(f))
+          .collect::<Vec<_>>()
+          .join(" "),
+      )
+    };
+    let block_key = |sel: &Option<Selection>, row: &ByteRecord, key: &str| -> Vec<ByteString> {
+      let mut block = match sel {
+        Some(sel) => row_key(sel, row, &norm),
+        None => Vec::new(),
+      };
+      if prefix > 0 {
+        block.push(key.chars().take(prefix).collect::<String>().into_bytes());
+      }
+      block
+    };
+
+    // the right rows by block
+    let mut rows2 = Vec::new();
+    let mut keys2 = Vec::new();
+    let mut blocks: HashMap<Vec<ByteString>, Vec<usize>> = HashMap::new();
+    for row in self.rdr2.byte_records() {
+      let row = row?;
+      let Some(key) = fuzzy_key(&self.sel2, &row) else {
+        self.tracker.right(Some(&row), false)?;
+        continue;
+      };
+      blocks
+        .entry(block_key(&block2, &row, &key))
+        .or_default()
+        .push(rows2.len());
+      keys2.push(key);
+      rows2.push(row);
+    }
+
+    let mut matched = vec![false; rows2.len()];
+    let mut candidates: Vec<(f64, usize)> = Vec::new();
+    for row in self.rdr1.byte_records() {
+      let row = row?;
+      let Some(key) = fuzzy_key(&self.sel1, &row) else {
+        self.tracker.left(&[], &row, 0)?;
+        continue;
+      };
+
+      candidates.clear();
+      let block = blocks.get(&block_key(&block1, &row, &key));
+      for &i in block.into_iter().flatten() {
+        let within = opts
+          .max_distance
+          .is_none_or(|d| strsim::levenshtein(&key, &keys2[i]) <= d);
+        let score = metric.score(&key, &keys2[i]);
+        if within && score >= threshold {
+          candidates.push((score, i));
+        }
+      }
+      // best first, ties in the order of the right file
+      candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+      candidates.truncate(top);
+      self
+        .tracker
+        .left(&[key.clone().into_bytes()], &row, candidates.len())?;
+
+      for &(score, i) in &candidates {
+        matched[i] = true;
+        let matched = self
+          .sel2
+          .get_row_key(&rows2[i])
+          .iter()
+          .map(|f| String::from_utf8_lossy(f).into_owned())
+          .collect::<Vec<_>>()
+          .join("|");
+        self.wtr.write_record(
+          row
+            .iter()
+            .chain(&rows2[i])
+            .chain([format_score(score).as_bytes(), matched.as_bytes()]),
+        )?;
+      }
+    }
+    self.wtr.flush()?;
+
+    let mut key_counts: HashMap<&str, usize> = HashMap::new();
+    for key in &keys2 {
+      *key_counts.entry(key.as_str()).or_default() += 1;
+    }
+    self
+      .tracker
+      .right_duplicate_keys(key_counts.values().filter(|&&n| n > 1).count());
+    for (row, matched) in rows2.iter().zip(matched) {
+      self.tracker.right(Some(row), matched)?;
+    }
+    self.tracker.finish(self.wtr.rows)
+  }
+}
@@ -0,0 +1,251 @@
+use std::{
+  fs::File,
+  io::{self, BufRead, BufReader, BufWriter, Write},
+  mem::swap,
+  path::Path,
+};
+
+use anyhow::{Result, anyhow};
+use csv::ByteRecord;
+use ext_sort::{ExternalSorter, LimitedBufferBuilder};
+use tempfile::NamedTempFile;
+
+use super::{ByteString, IoState, JoinStats, RowWriter, Tracker, row_key};
+use crate::cmd::extsort::{RW_BUFFER_CAPACITY, new_sorter};
+use crate::io::csv::selection::Selection;
+use crate::normalize::TextNorm;
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// The sort line of a row: the hex of every key field separated by ' ', a '\t'
+/// and the byte offset of the row padded to 20 digits.
+/// Hex keeps the byte order and ' ', '\t' sort before the hex digits, so the
+/// lines sort like `Vec<ByteString>` and the rows of a key stay in file order.
+fn sort_line(key: &[ByteString], pos: u64) -> String {
+  let mut line = String::with_capacity(key.iter().map(|f| f.len() * 2 + 1).sum::<usize>() + 21);
+  for (i, field) in key.iter().enumerate() {
+    if i > 0 {
+      line.push(' ');
+    }
+    for b in field {
+      line.push(HEX[(b >> 4) as usize] as char);
+      line.push(HEX[(b & 0x0f) as usize] as char);
+    }
+  }
+  line.push('\t');
+  line.push_str(&format!("{pos:020}"));
+  line
+}
+
+/// The rows of one side in key order, sorted on disk and read back by byte offset
+pub(crate) struct SortedRows {
+  rdr: csv::Reader<File>,
+  sel: Selection,
+  norm: TextNorm,
+  lines: io::Lines<BufReader<File>>,
+  _sorted: NamedTempFile,
+  peeked: Option<(Vec<ByteString>, ByteRecord)>,
+}
+
+impl SortedRows {
+  pub(crate) fn new(
+    mut rdr: csv::Reader<File>,
+    sel: Selection,
+    norm: TextNorm,
+    tmp_dir: &Path,
+    sorter: &ExternalSorter<String, io::Error, LimitedBufferBuilder>,
+  ) -> Result<Self> {
+    // first pass, the sort line of every row
+    let line_tfile = NamedTempFile::new_in(tmp_dir)?;
+    let mut line_wtr = BufWriter::with_capacity(RW_BUFFER_CAPACITY, line_tfile.as_file());
+    let mut row = ByteRecord::new();
+    rdr.byte_headers()?;
+    while rdr.read_byte_record(&mut row)? {
+      let pos = row.position().ok_or(anyhow!("position is null"))?.byte();
+      writeln!(line_wtr, "{}", sort_line(&row_key(&sel, &row, &norm), pos))?;
+    }
+    line_wtr.flush()?;
+    drop(line_wtr);
+
+    let line_rdr = BufReader::with_capacity(RW_BUFFER_CAPACITY, File::open(line_tfile.path())?);
+    let sorted = sorter
+      .sort_by(line_rdr.lines(), |a: &String, b: &String| a.cmp(b))
+      .map_err(|e| anyhow!("cannot do external sort: {e:?}"))?;
+
+    let sorted_tfile = NamedTempFile::new_in(tmp_dir)?;
+    let mut sorted_wtr = BufWriter::with_capacity(RW_BUFFER_CAPACITY, sorted_tfile.as_file());
+    for line in sorted {
+      let line = line.map_err(|e| anyhow!("cannot do external sort: {e:?}"))?;
+      writeln!(sorted_wtr, "{line}")?;
+    }
+    sorted_wtr.flush()?;
+    drop(sorted_wtr);
+    line_tfile.close()?;
+
+    let lines =
+      BufReader::with_capacity(RW_BUFFER_CAPACITY, File::open(sorted_tfile.path())?).lines();
+    Ok(SortedRows {
+      rdr,
+      sel,
+      norm,
+      lines,
+      _sorted: sorted_tfile,
+      peeked: None,
+    })
+  }
+
+  pub(crate) fn next_row(&mut self) -> Result<Option<(Vec<ByteString>, ByteRecord)>> {
+    if let Some(peeked) = self.peeked.take() {
+      return Ok(Some(peeked));
+    }
+    let Some(line) = self.lines.next() else {
+      return Ok(None);
+    };
+    let line = line?;
+    let pos = line
+      .rsplit('\t')
+      .next()
+      .and_then(|p| p.parse::<u64>().ok())
+      .ok_or(anyhow!("Failed to retrieve position: {line}"))?;
+
+    let mut position = csv::Position::new();
+    position.set_byte(pos);
+    self.rdr.seek(position)?;
+    let mut row = ByteRecord::new();
+    if !self.rdr.read_byte_record(&mut row)? {
+      return Err(anyhow!("No row at byte {pos}"));
+    }
+    Ok(Some((row_key(&self.sel, &row, &self.norm), row)))
+  }
+
+  /// The next key and all of its rows
+  pub(crate) fn next_group(&mut self) -> Result<Option<(Vec<ByteString>, Vec<ByteRecord>)>> {
+    let Some((key, row)) = self.next_row()? else {
+      return Ok(None);
+    };
+    let mut rows = vec![row];
+    while let Some((next_key, next_row)) = self.next_row()? {
+      if next_key != key {
+        self.peeked = Some((next_key, next_row));
+        break;
+      }
+      rows.push(next_row);
+    }
+    Ok(Some((key, rows)))
+  }
+}
+
+/// Track the rows of a right key the merge is done with,
+/// the unmatched rows are written padded when `keep`
+fn close_group<W: Write>(
+  rows: &[ByteRecord],
+  matched: bool,
+  keep: bool,
+  pad1: &ByteRecord,
+  wtr: &mut RowWriter<W>,
+  tracker: &mut Tracker,
+) -> Result<()> {
+  if rows.len() > 1 {
+    tracker.right_duplicate_keys(1);
+  }
+  for row in rows {
+    if keep && !matched {
+      wtr.write_record(pad1.iter().chain(row))?;
+    }
+    tracker.right(Some(row), matched)?;
+  }
+  Ok(())
+}
+
+impl<W: Write> IoState<File, W> {
+  /// `join_type` as a merge of both sides sorted on disk (in `tmp_dir`),
+  /// only the rows of one key of the right side are held in memory.
+  /// The output is in key order.
+  pub(super) fn sort_merge_join(mut self, join_type: &str, tmp_dir: &Path) -> Result<JoinStats> {
+    // Some(anti) for the semi and anti joins, which only write the left rows
+    let semi = match join_type {
+      "left_semi" => Some(false),
+      "left_anti" => Some(true),
+      "right_semi" | "right_anti" => {
+        swap(&mut self.rdr1, &mut self.rdr2);
+        swap(&mut self.sel1, &mut self.sel2);
+        self.tracker.swap_sides();
+        Some(join_type == "right_anti")
+      }
+      _ => None,
+    };
+    let (keep_left, keep_right) = match join_type {
+      "left" => (true, false),
+      "right" => (false, true),
+      "full" => (true, true),
+      _ => (false, false),
+    };
+    self.write_headers(semi.is_none())?;
+    let (pad1, pad2) = self.get_padding()?;
+
+    let sorter = new_sorter(tmp_dir)?;
+    let IoState {
+      mut wtr,
+      rdr1,
+      sel1,
+      rdr2,
+      sel2,
+      nulls,
+      norm,
+      mut tracker,
+      ..
+    } = self;
+    tracker.sorted();
+    let mut left = SortedRows::new(rdr1, sel1, norm, tmp_dir, &sorter)?;
+    let mut right = SortedRows::new(rdr2, sel2, norm, tmp_dir, &sorter)?;
+    let is_null = |key: &[ByteString]| !nulls && key.iter().any(|f| f.is_empty());
+
+    let mut group = right.next_group()?;
+    // whether a left row matched the current right group
+    let mut matched = false;
+    while let Some((key, row)) = left.next_row()? {
+      // skip the right keys before the left key
+      loop {
+        match &group {
+          Some((right_key, rows)) if *right_key < key => {
+            close_group(rows, matched, keep_right, &pad1, &mut wtr, &mut tracker)?;
+            group = right.next_group()?;
+            matched = false;
+          }
+          _ => break,
+        }
+      }
+
+      let hit = match &group {
+        Some((right_key, rows)) if *right_key == key && !is_null(&key) => Some(rows),
+        _ => None,
+      };
+      tracker.left(&key, &row, hit.map_or(0, |rows| rows.len()))?;
+      matched |= hit.is_some();
+      match (hit, semi) {
+        (Some(_), Some(false)) | (None, Some(true)) => wtr.write_record(&row)?,
+        (_, Some(_)) => {}
+        (Some(rows), None) => {
+          for right_row in rows {
+            wtr.write_record(row.iter().chain(right_row))?;
+          }
+        }
+        (None, None) => {
+          if keep_left {
+            wtr.write_record(row.iter().chain(&pad2))?;
+          }
+        }
+      }
+    }
+
+    // the right keys after the last left key
+    while let Some((_, rows)) = group {
+      close_group(&rows, matched, keep_right, &pad1, &mut wtr, &mut tracker)?;
+      group = right.next_group()?;
+      matched = false;
+    }
+    wtr.flush()?;
+
+    tracker.finish(wtr.rows)
+  }
+}
@@ -0,0 +1,694 @@
+use std::{
+  collections::hash_map::{Entry, HashMap},
+  fmt,
+  fs::File,
+  io::{Cursor, Read, Seek, Write},
+  iter::repeat,
+  mem::swap,
+  num::NonZero,
+  path::{Path, PathBuf},
+  time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+use byteorder::{BigEndian, WriteBytesExt};
+use csv::{ReaderBuilder, WriterBuilder};
+use polars::{
+  io::SerReader,
+  prelude::{CsvWriter, DataFrame, JsonFormat, JsonReader, ParquetReader, SerWriter},
+};
+use tempfile::TempDir;
+
+use crate::index::Indexed;
+use crate::io::csv::{options::CsvOptions, selection::Selection};
+use crate::io::excel::excel_reader::{ExcelReader, ToPolarsDataFrame};
+use crate::normalize::TextNorm;
+
+mod asof;
+mod fuzzy;
+mod merge;
+mod output;
+mod stats;
+
+pub use asof::AsofJoinOptions;
+pub use fuzzy::FuzzyJoinOptions;
+pub(crate) use merge::SortedRows;
+pub use output::OutputOptions;
+use output::Projection;
+pub use stats::JoinStats;
+use stats::{RowWriter, Tracker};
+
+type ByteString = Vec<u8>;
+
+/// Options of `run_join_with_options`
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct JoinOptions {
+  /// normalisation of the key values, the output keeps the original values
+  pub norm: TextNorm,
+  /// sort both sides on disk and merge them instead of holding the keys of the
+  /// right side in memory, the output is in key order
+  pub sort_merge: bool,
+  /// options of the `fuzzy` join type
+  pub fuzzy: FuzzyJoinOptions,
+  /// options of the `asof` join type
+  pub asof: AsofJoinOptions,
+  /// write the unmatched rows of both sides to `<left>_join_unmatched_left.csv`
+  /// and `<left>_join_unmatched_right.csv`
+  pub unmatched: bool,
+  /// kept columns, suffixes and coalesced keys of the output
+  #[serde(flatten)]
+  pub output: OutputOptions,
+}
+
+/// The key of `row`, normalised with `norm`
+pub(crate) fn row_key(sel: &Selection, row: &csv::ByteRecord, norm: &TextNorm) -> Vec<ByteString> {
+  if norm.is_identity() {
+    return sel.get_row_key(row);
+  }
+  sel
+    .get_indices()
+    .iter()
+    .filter_map(|&idx| row.get(idx))
+    .map(|field| {
+      norm
+        .apply(&String::from_utf8_lossy(field))
+        .into_owned()
+        .into_bytes()
+    })
+    .collect()
+}
+
+struct IoState<R, W: Write> {
+  wtr: RowWriter<W>,
+  rdr1: csv::Reader<R>,
+  sel1: Selection,
+  rdr2: csv::Reader<R>,
+  sel2: Selection,
+  nulls: bool,
+  norm: TextNorm,
+  tracker: Tracker,
+  output: OutputOptions,
+  /// holds the csv copies of the xlsx, parquet and json inputs
+  _tmp_dir: Option<TempDir>,
+}
+
+impl<R: Read + Seek, W: Write> IoState<R, W> {
+  fn write_headers(&mut self, extend: bool) -> Result<()> {
+    self.write_headers_with(extend, &[])
+  }
+
+  /// `extra` are the names of the columns a join adds after the fields of both files
+  fn write_headers_with(&mut self, extend: bool, extra: &[&[u8]]) -> Result<()> {
+    let left = self.rdr1.byte_headers()?.clone();
+    let right = match extend {
+      true => Some(self.rdr2.byte_headers()?.clone()),
+      false => None,
+    };
+    let projection = Projection::new(
+      &self.output,
+      &left,
+      right.as_ref(),
+      (&self.sel1, &self.sel2),
+      self.tracker.swapped(),
+      extra,
+    )?;
+    match projection {
+      Some((projection, headers)) => {
+        self.wtr.projection = Some(projection);
+        self.wtr.wtr.write_record(&headers)?;
+      }
+      None => {
+        let mut headers = left;
+        if let Some(right) = right {
+          headers.extend(right.iter());
+        }
+        headers.extend(extra.iter());
+        self.wtr.wtr.write_record(&headers)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  fn inner_join(mut self) -> Result<JoinStats> {
+    let mut scratch = csv::ByteRecord::new();
+    let mut validx = ValueIndex::new(self.rdr2, self.sel2, self.nulls, self.norm)?;
+    let mut matched = vec![false; validx.num_rows];
+    for row in self.rdr1.byte_records() {
+      let row = row?;
+      let key = row_key(&self.sel1, &row, &self.norm);
+      match validx.values.get(&key) {
+        None => self.tracker.left(&key, &row, 0)?,
+        Some(rows) => {
+          self.tracker.left(&key, &row, rows.len())?;
+          for &rowi in rows.iter() {
+            matched[rowi] = true;
+            validx.idx.seek(rowi as u64)?;
+
+            validx.idx.read_byte_record(&mut scratch)?;
+            let combined = row.iter().chain(scratch.iter());
+            self.wtr.write_record(combined)?;
+          }
+        }
+      }
+    }
+    validx.track_right(&matched, &mut self.tracker)?;
+    self.tracker.finish(self.wtr.rows)
+  }
+
+  fn outer_join(mut self, right: bool) -> Result<JoinStats> {
+    if right {
+      swap(&mut self.rdr1, &mut self.rdr2);
+      swap(&mut self.sel1, &mut self.sel2);
+      self.tracker.swap_sides();
+    }
+
+    let mut scratch = csv::ByteRecord::new();
+    let (_, pad2) = self.get_padding()?;
+    let mut validx = ValueIndex::new(self.rdr2, self.sel2, self.nulls, self.norm)?;
+    let mut matched = vec![false; validx.num_rows];
+    for row in self.rdr1.byte_records() {
+      let row = row?;
+      let key = row_key(&self.sel1, &row, &self.norm);
+      match validx.values.get(&key) {
+        None => {
+          self.tracker.left(&key, &row, 0)?;
+          if right {
+            self.wtr.write_record(pad2.iter().chain(&row))?;
+          } else {
+            self.wtr.write_record(row.iter().chain(&pad2))?;
+          }
+        }
+        Some(rows) => {
+          self.tracker.left(&key, &row, rows.len())?;
+          for &rowi in rows.iter() {
+            matched[rowi] = true;
+            validx.idx.seek(rowi as u64)?;
+            let row1 = row.iter();
+            validx.idx.read_byte_record(&mut scratch)?;
+            if right {
+              self.wtr.write_record(scratch.iter().chain(row1))?;
+            } else {
+              self.wtr.write_record(row1.chain(&scratch))?;
+            }
+          }
+        }
+      }
+    }
+    validx.track_right(&matched, &mut self.tracker)?;
+    self.tracker.finish(self.wtr.rows)
+  }
+
+  fn left_join(mut self, anti: bool) -> Result<JoinStats> {
+    let mut validx = ValueIndex::new(self.rdr2, self.sel2, self.nulls, self.norm)?;
+    let mut matched = vec![false; validx.num_rows];
+    let mut row = csv::ByteRecord::new();
+    let mut key;
+
+    while self.rdr1.read_byte_record(&mut row)? {
+      key = row_key(&self.sel1, &row, &self.norm);
+      let rows = validx
+        .values
+        .get(&key)
+        .map_or(&[][..], |rows| rows.as_slice());
+      self.tracker.left(&key, &row, rows.len())?;
+      for &rowi in rows {
+        matched[rowi] = true;
+      }
+      if !validx.values.contains_key(&key) {
+        if anti {
+          self.wtr.write_record(&row)?;
+        }
+      } else if !anti {
+        self.wtr.write_record(&row)?;
+      }
+    }
+    self.wtr.flush()?;
+
+    validx.track_right(&matched, &mut self.tracker)?;
+    self.tracker.finish(self.wtr.rows)
+  }
+
+  fn full_outer_join(mut self) -> Result<JoinStats> {
+    let mut scratch = csv::ByteRecord::new();
+    let (pad1, pad2) = self.get_padding()?;
+    let mut validx = ValueIndex::new(self.rdr2, self.sel2, self.nulls, self.norm)?;
+
+    // Keep track of which rows we've written from rdr2.
+    let mut rdr2_written: Vec<_> = repeat(false).take(validx.num_rows).collect();
+    for row1 in self.rdr1.byte_records() {
+      let row1 = row1?;
+      let key = row_key(&self.sel1, &row1, &self.norm);
+      match validx.values.get(&key) {
+        None => {
+          self.tracker.left(&key, &row1, 0)?;
+          self.wtr.write_record(row1.iter().chain(&pad2))?;
+        }
+        Some(rows) => {
+          self.tracker.left(&key, &row1, rows.len())?;
+          for &rowi in rows.iter() {
+            rdr2_written[rowi] = true;
+
+            validx.idx.seek(rowi as u64)?;
+            validx.idx.read_byte_record(&mut scratch)?;
+            self.wtr.write_record(row1.iter().chain(&scratch))?;
+          }
+        }
+      }
+    }
+
+    // OK, now write any row from rdr2 that didn't get joined with a row
+    // from rdr1.
+    self.tracker.right_duplicate_keys(validx.duplicate_keys());
+    for (i, &written) in rdr2_written.iter().enumerate() {
+      if !written {
+        validx.idx.seek(i as u64)?;
+        validx.idx.read_byte_record(&mut scratch)?;
+        self.wtr.write_record(pad1.iter().chain(&scratch))?;
+      }
+      self
+        .tracker
+        .right((!written).then_some(&scratch), written)?;
+    }
+    self.tracker.finish(self.wtr.rows)
+  }
+
+  fn cross_join(mut self) -> Result<JoinStats> {
+    let mut pos = csv::Position::new();
+    pos.set_byte(0);
+    let mut row2 = csv::ByteRecord::new();
+    let (mut left_rows, mut right_rows) = (0, 0);
+    for row1 in self.rdr1.byte_records() {
+      let row1 = row1?;
+      self.rdr2.seek(pos.clone())?;
+      if self.rdr2.has_headers() {
+        // Read and skip the header row, since CSV readers disable
+        // the header skipping logic after being seeked.
+        self.rdr2.read_byte_record(&mut row2)?;
+      }
+      right_rows = 0;
+      while self.rdr2.read_byte_record(&mut row2)? {
+        self.wtr.write_record(row1.iter().chain(&row2))?;
+        right_rows += 1;
+      }
+      self.tracker.left(&[], &row1, right_rows)?;
+      left_rows += 1;
+    }
+
+    // every right row is matched unless the left file is empty
+    if left_rows == 0 {
+      self.rdr2.seek(pos)?;
+      if self.rdr2.has_headers() {
+        self.rdr2.read_byte_record(&mut row2)?;
+      }
+      while self.rdr2.read_byte_record(&mut row2)? {
+        self.tracker.right(Some(&row2), false)?;
+      }
+    }
+    for _ in 0..right_rows {
+      self.tracker.right(None, true)?;
+    }
+    self.tracker.finish(self.wtr.rows)
+  }
+
+  fn get_padding(&mut self) -> Result<(csv::ByteRecord, csv::ByteRecord)> {
+    let len1 = self.rdr1.byte_headers()?.len();
+    let len2 = self.rdr2.byte_headers()?.len();
+    Ok((
+      repeat(b"").take(len1).collect(),
+      repeat(b"").take(len2).collect(),
+    ))
+  }
+}
+
+/// Key columns of one side, `|` separated for a composite key,
+/// e.g. `company_code|doc_no|fiscal_year`
+pub(crate) fn key_columns(sel: &str) -> Vec<&str> {
+  sel
+    .split('|')
+    .map(|s| s.trim())
+    .filter(|s| !s.is_empty())
+    .collect()
+}
+
+/// The rows of a xlsx/xls sheet (the first one), parquet, json or jsonl file as a
+/// csv in `tmp_dir`, `None` for the csv files which are read in place
+pub(crate) fn join_source(path: &Path, tmp_dir: &mut Option<TempDir>) -> Result<Option<PathBuf>> {
+  let ext = path
+    .extension()
+    .map(|ext| ext.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+  let mut df: DataFrame = match ext.as_str() {
+    "xlsx" | "xls" | "xlsm" | "xlsb" | "ods" => ExcelReader::from_path(path)?
+      .worksheet_range_at(0, 0)?
+      .to_df()?,
+    "parquet" => ParquetReader::new(File::open(path)?).finish()?,
+    "json" | "jsonl" | "ndjson" => {
+      let format = match ext.as_str() {
+        "json" => JsonFormat::Json,
+        _ => JsonFormat::JsonLines,
+      };
+      JsonReader::new(File::open(path)?)
+        .with_json_format(format)
+        .infer_schema_len(NonZero::new(10000))
+        .finish()?
+    }
+    _ => return Ok(None),
+  };
+
+  let dir = match tmp_dir {
+    Some(dir) => dir,
+    None => tmp_dir.insert(TempDir::new()?),
+  };
+  let stem = path
+    .file_stem()
+    .map(|s| s.to_string_lossy().to_string())
+    .unwrap_or_default();
+  let csv_path = dir.path().join(format!("{stem}_{ext}.csv"));
+  let mut wtr = File::create(&csv_path)?;
+  CsvWriter::new(&mut wtr)
+    .finish(&mut df)
+    .map_err(|e| anyhow!("{}: {e}", path.display()))?;
+
+  Ok(Some(csv_path))
+}
+
+fn new_io_state<P: AsRef<Path> + Send + Sync>(
+  path1: P,
+  path2: P,
+  sel1: String,
+  sel2: String,
+  nulls: bool,
+  quoting: bool,
+  options: &JoinOptions,
+) -> Result<IoState<File, Box<dyn Write + 'static>>> {
+  let opts1 = CsvOptions::new(&path1);
+  let output_path = opts1.output_path(Some("join"), None)?;
+  let side_paths = match options.unmatched {
+    true => Some([
+      opts1.output_path(Some("join_unmatched_left"), None)?,
+      opts1.output_path(Some("join_unmatched_right"), None)?,
+    ]),
+    false => None,
+  };
+
+  // the csv inputs are streamed, the others are converted to csv first
+  let mut tmp_dir = None;
+  let src1 = join_source(path1.as_ref(), &mut tmp_dir)?;
+  let src2 = join_source(path2.as_ref(), &mut tmp_dir)?;
+  let path1 = src1.unwrap_or_else(|| path1.as_ref().to_path_buf());
+  let path2 = src2.unwrap_or_else(|| path2.as_ref().to_path_buf());
+
+  let sep1 = CsvOptions::new(&path1).detect_separator()?;
+  let sep2 = CsvOptions::new(&path2).detect_separator()?;
+
+  let mut rdr1 = ReaderBuilder::new()
+    .delimiter(sep1)
+    .quoting(quoting)
+    .from_reader(File::open(&path1)?);
+  let mut rdr2 = ReaderBuilder::new()
+    .delimiter(sep2)
+    .quoting(quoting)
+    .from_reader(File::open(&path2)?);
+
+  let boxed_writer: Box<dyn Write> = Box::new(File::create(output_path)?);
+
+  let wtr = WriterBuilder::new()
+    .delimiter(sep1)
+    .from_writer(boxed_writer);
+
+  let (keys1, keys2) = (key_columns(&sel1), key_columns(&sel2));
+  if keys1.is_empty() || keys2.is_empty() {
+    return Err(anyhow!("No key column selected"));
+  }
+  if keys1.len() != keys2.len() {
+    return Err(anyhow!(
+      "The number of key columns differs: {} in the left file, {} in the right file",
+      keys1.len(),
+      keys2.len()
+    ));
+  }
+  let sel1 = Selection::from_headers(rdr1.byte_headers()?, &keys1)?;
+  let sel2 = Selection::from_headers(rdr2.byte_headers()?, &keys2)?;
+
+  let headers1 = rdr1.byte_headers()?.clone();
+  let headers2 = rdr2.byte_headers()?.clone();
+  let side_files = side_paths.map(|[left, right]| [(left, &headers1), (right, &headers2)]);
+  let tracker = Tracker::new(side_files, sep1)?;
+
+  Ok(IoState {
+    wtr: RowWriter::new(wtr),
+    rdr1: rdr1,
+    sel1: sel1,
+    rdr2: rdr2,
+    sel2: sel2,
+    nulls: nulls,
+    norm: options.norm,
+    tracker,
+    output: options.output.clone(),
+    _tmp_dir: tmp_dir,
+  })
+}
+
+struct ValueIndex<R> {
+  // This maps tuples of values to corresponding rows.
+  values: HashMap<Vec<ByteString>, Vec<usize>>,
+  idx: Indexed<R, Cursor<Vec<u8>>>,
+  num_rows: usize,
+}
+
+impl<R: Read + Seek> ValueIndex<R> {
+  fn new(
+    mut rdr: csv::Reader<R>,
+    sel: Selection,
+    nulls: bool,
+    norm: TextNorm,
+  ) -> Result<ValueIndex<R>> {
+    let mut val_idx = HashMap::with_capacity(10000);
+    let mut row_idx = Cursor::new(Vec::with_capacity(8 * 10000));
+    let (mut rowi, mut count) = (0usize, 0usize);
+
+    // This logic is kind of tricky. Basically, we want to include
+    // the header row in the line index (because that's what csv::index
+    // does), but we don't want to include header values in the ValueIndex.
+    if !rdr.has_headers() {
+      // ... so if there are no headers, we seek to the beginning and
+      // index everything.
+      let mut pos = csv::Position::new();
+      pos.set_byte(0);
+      rdr.seek(pos)?;
+    } else {
+      // ... and if there are headers, we make sure that we've parsed
+      // them, and write the offset of the header row to the index.
+      rdr.byte_headers()?;
+      row_idx.write_u64::<BigEndian>(0)?;
+      count += 1;
+    }
+
+    let mut row = csv::ByteRecord::new();
+    while rdr.read_byte_record(&mut row)? {
+      // This is a bit hokey. We're doing this manually instead of using
+      // the `csv-index` crate directly so that we can create both
+      // indexes in one pass.
+      row_idx.write_u64::<BigEndian>(row.position().unwrap().byte())?;
+
+      let fields: Vec<_> = row_key(&sel, &row, &norm);
+      if nulls || !fields.iter().any(|f| f.is_empty()) {
+        match val_idx.entry(fields) {
+          Entry::Vacant(v) => {
+            let mut rows = Vec::with_capacity(4);
+            rows.push(rowi);
+            v.insert(rows);
+          }
+          Entry::Occupied(mut v) => {
+            v.get_mut().push(rowi);
+          }
+        }
+      }
+      rowi += 1;
+      count += 1;
+    }
+
+    row_idx.write_u64::<BigEndian>(count as u64)?;
+    let idx = Indexed::open(rdr, Cursor::new(row_idx.into_inner()))?;
+    Ok(ValueIndex {
+      values: val_idx,
+      idx: idx,
+      num_rows: rowi,
+    })
+  }
+}
+
+impl<R: Read + Seek> ValueIndex<R> {
+  /// keys with more than one row
+  fn duplicate_keys(&self) -> usize {
+    self.values.values().filter(|rows| rows.len() > 1).count()
+  }
+
+  /// Pass every row to `tracker`, `matched` by row index
+  fn track_right(&mut self, matched: &[bool], tracker: &mut Tracker) -> Result<()> {
+    tracker.right_duplicate_keys(self.duplicate_keys());
+    let mut scratch = csv::ByteRecord::new();
+    for (i, &matched) in matched.iter().enumerate() {
+      let row = match !matched && tracker.keeps_unmatched() {
+        true => {
+          self.idx.seek(i as u64)?;
+          self.idx.read_byte_record(&mut scratch)?;
+          Some(&scratch)
+        }
+        false => None,
+      };
+      tracker.right(row, matched)?;
+    }
+    Ok(())
+  }
+}
+
+impl<R> fmt::Debug for ValueIndex<R> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    // Sort the values by order of first appearance.
+    let mut kvs = self.values.iter().collect::<Vec<_>>();
+    kvs.sort_by(|&(_, v1), &(_, v2)| v1[0].cmp(&v2[0]));
+    for (keys, rows) in kvs.into_iter() {
+      // This is just for debugging, so assume Unicode for now.
+      let keys = keys
+        .iter()
+        .map(|k| String::from_utf8(k.to_vec()).unwrap())
+        .collect::<Vec<_>>();
+      writeln!(f, "({}) => {:?}", keys.join(", "), rows)?
+    }
+    Ok(())
+  }
+}
+
+pub async fn run_join<P: AsRef<Path> + Send + Sync>(
+  path1: P,
+  path2: P,
+  sel1: String,
+  sel2: String,
+  join_type: &str,
+  nulls: bool,
+  quoting: bool,
+) -> Result<JoinStats> {
+  run_join_with_options(
+    path1,
+    path2,
+    sel1,
+    sel2,
+    join_type,
+    nulls,
+    quoting,
+    JoinOptions::default(),
+  )
+  .await
+}
+
+pub async fn run_join_with_options<P: AsRef<Path> + Send + Sync>(
+  path1: P,
+  path2: P,
+  sel1: String,
+  sel2: String,
+  join_type: &str,
+  nulls: bool,
+  quoting: bool,
+  options: JoinOptions,
+) -> Result<JoinStats> {
+  // the sorted chunks are written next to the left file
+  let tmp_parent = match CsvOptions::new(&path1).parent_path()? {
+    "" => PathBuf::from("."),
+    parent => PathBuf::from(parent),
+  };
+  let mut state = new_io_state(path1, path2, sel1, sel2, nulls, quoting, &options)?;
+  if options.sort_merge && !matches!(join_type, "cross" | "fuzzy" | "asof") {
+    let tmp_dir = TempDir::new_in(tmp_parent)?;
+    return state.sort_merge_join(join_type, tmp_dir.path());
+  }
+  match join_type {
+    "left" => {
+      state.write_headers(true)?;
+      state.outer_join(false)
+    }
+    "right" => {
+      state.write_headers(true)?;
+      state.outer_join(true)
+    }
+    "full" => {
+      state.write_headers(true)?;
+      state.full_outer_join()
+    }
+    "cross" => {
+      state.write_headers(true)?;
+      state.cross_join()
+    }
+    "inner" => {
+      state.write_headers(true)?;
+      state.inner_join()
+    }
+    "left_semi" => {
+      state.write_headers(false)?;
+      state.left_join(false)
+    }
+    "left_anti" => {
+      state.write_headers(false)?;
+      state.left_join(true)
+    }
+    "right_semi" => {
+      let mut swapped_join = state;
+      swap(&mut swapped_join.rdr1, &mut swapped_join.rdr2);
+      swap(&mut swapped_join.sel1, &mut swapped_join.sel2);
+      swapped_join.tracker.swap_sides();
+      swapped_join.write_headers(false)?;
+      swapped_join.left_join(false)
+    }
+    "right_anti" => {
+      let mut swapped_join = state;
+      swap(&mut swapped_join.rdr1, &mut swapped_join.rdr2);
+      swap(&mut swapped_join.sel1, &mut swapped_join.sel2);
+      swapped_join.tracker.swap_sides();
+      swapped_join.write_headers(false)?;
+      swapped_join.left_join(true)
+    }
+    "fuzzy" => state.fuzzy_join(&options.fuzzy),
+    "asof" => state.asof_join(&options.asof),
+    _ => {
+      state.write_headers(true)?;
+      state.inner_join()
+    }
+  }
+}
+
+#[tauri::command]
+pub async fn join(
+  path1: String,
+  path2: String,
+  sel1: String,
+  sel2: String,
+  join_type: String,
+  nulls: bool,
+  quoting: bool,
+  options: Option<JoinOptions>,
+) -> Result<(String, String), String> {
+  let start_time = Instant::now();
+
+  match run_join_with_options(
+    path1,
+    path2,
+    sel1,
+    sel2,
+    &join_type,
+    nulls,
+    quoting,
+    options.unwrap_or_default(),
+  )
+  .await
+  {
+    Ok(stats) => {
+      let end_time = Instant::now();
+      let elapsed_time = end_time.duration_since(start_time).as_secs_f64();
+      let stats = serde_json::to_string(&stats).map_err(|e| format!("{e}"))?;
+      Ok((stats, format!("{elapsed_time:.2}")))
+    }
+    Err(err) => Err(format!("{err}")),
+  }
+}
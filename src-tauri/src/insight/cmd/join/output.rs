@@ -0,0 +1,149 @@
+use anyhow::Result;
+use csv::ByteRecord;
+
+use super::key_columns;
+use crate::io::csv::selection::Selection;
+
+/// Columns and names of the join output
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct OutputOptions {
+  /// columns kept from the left file, `|` separated, all by default
+  pub left_columns: Option<String>,
+  /// columns kept from the right file, `|` separated, all by default
+  pub right_columns: Option<String>,
+  /// add `left_suffix`/`right_suffix` to the names that are in both files
+  pub suffixes: bool,
+  /// `_left` by default
+  pub left_suffix: Option<String>,
+  /// `_right` by default
+  pub right_suffix: Option<String>,
+  /// one key column with the left value, or the right value for the unmatched
+  /// right rows, instead of the key columns of both files
+  pub coalesce_keys: bool,
+}
+
+impl OutputOptions {
+  fn is_default(&self) -> bool {
+    self.left_columns.is_none()
+      && self.right_columns.is_none()
+      && !self.suffixes
+      && !self.coalesce_keys
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum OutColumn {
+  Field(usize),
+  /// the first field when it is not empty, the second one otherwise
+  Coalesce(usize, usize),
+}
+
+/// Maps the rows of a join (left fields, right fields and extra fields) to the output columns
+#[derive(Debug)]
+pub(super) struct Projection {
+  columns: Vec<OutColumn>,
+}
+
+/// Indices of the kept columns of one file
+fn kept(columns: Option<&str>, headers: &ByteRecord) -> Result<Vec<usize>> {
+  match columns.map(key_columns).filter(|c| !c.is_empty()) {
+    Some(columns) => Ok(
+      Selection::from_headers(headers, &columns)?
+        .get_indices()
+        .clone(),
+    ),
+    None => Ok((0..headers.len()).collect()),
+  }
+}
+
+impl Projection {
+  /// The projection and the output headers of a join writing the fields of
+  /// both files (`right` is `Some`) or of one file, `None` for the default output.
+  /// `keys` are the key columns of both files and `one_side_right` tells that the
+  /// only file is the right one (right semi and anti joins).
+  pub(super) fn new(
+    opts: &OutputOptions,
+    left: &ByteRecord,
+    right: Option<&ByteRecord>,
+    keys: (&Selection, &Selection),
+    one_side_right: bool,
+    extra: &[&[u8]],
+  ) -> Result<Option<(Self, ByteRecord)>> {
+    if opts.is_default() {
+      return Ok(None);
+    }
+
+    let Some(right) = right else {
+      let columns = match one_side_right {
+        true => opts.right_columns.as_deref(),
+        false => opts.left_columns.as_deref(),
+      };
+      let kept = kept(columns, left)?;
+      let headers = kept.iter().map(|&i| &left[i]).collect();
+      let columns = kept.into_iter().map(OutColumn::Field).collect();
+      return Ok(Some((Projection { columns }, headers)));
+    };
+
+    let n1 = left.len();
+    let mut kept1 = kept(opts.left_columns.as_deref(), left)?;
+    let mut kept2 = kept(opts.right_columns.as_deref(), right)?;
+    let (keys1, keys2) = (keys.0.get_indices(), keys.1.get_indices());
+    if opts.coalesce_keys {
+      // the left key columns stay, those that are not kept go first
+      let missing: Vec<usize> = keys1
+        .iter()
+        .filter(|k| !kept1.contains(k))
+        .copied()
+        .collect();
+      kept1 = missing.into_iter().chain(kept1).collect();
+      kept2.retain(|k| !keys2.contains(k));
+    }
+
+    let name = |headers: &ByteRecord, i: usize| String::from_utf8_lossy(&headers[i]).into_owned();
+    let names1: Vec<String> = kept1.iter().map(|&i| name(left, i)).collect();
+    let names2: Vec<String> = kept2.iter().map(|&i| name(right, i)).collect();
+    let suffix = |names: &[String], others: &[String], with: &Option<String>, default: &str| {
+      names
+        .iter()
+        .map(|n| match opts.suffixes && others.contains(n) {
+          true => format!("{n}{}", with.as_deref().unwrap_or(default)),
+          false => n.clone(),
+        })
+        .collect::<Vec<_>>()
+    };
+    let mut headers: ByteRecord = suffix(&names1, &names2, &opts.left_suffix, "_left")
+      .iter()
+      .chain(&suffix(&names2, &names1, &opts.right_suffix, "_right"))
+      .collect();
+    headers.extend(extra.iter());
+
+    let mut columns: Vec<OutColumn> = kept1
+      .iter()
+      .map(|&i| match keys1.iter().position(|&k| k == i) {
+        Some(k) if opts.coalesce_keys => OutColumn::Coalesce(i, n1 + keys2[k]),
+        _ => OutColumn::Field(i),
+      })
+      .collect();
+    columns.extend(kept2.iter().map(|&i| OutColumn::Field(n1 + i)));
+    let n2 = right.len();
+    columns.extend((0..extra.len()).map(|i| OutColumn::Field(n1 + n2 + i)));
+
+    Ok(Some((Projection { columns }, headers)))
+  }
+
+  /// The output fields of a row
+  pub(super) fn apply<'a, T: AsRef<[u8]>>(
+    &'a self,
+    fields: &'a [T],
+  ) -> impl Iterator<Item = &'a [u8]> + 'a {
+    let field = move |i: usize| -> &'a [u8] { fields.get(i).map_or(&b""[..], |f| f.as_ref()) };
+    self.columns.iter().map(move |c| match *c {
+      OutColumn::Field(i) => field(i),
+      OutColumn::Coalesce(l, r) => match field(l).is_empty() {
+        true => field(r),
+        false => field(l),
+      },
+    })
+  }
+}
@@ -0,0 +1,236 @@
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{self, Write},
+  mem::swap,
+  path::PathBuf,
+};
+
+use anyhow::Result;
+use csv::ByteRecord;
+use serde::Serialize;
+
+use super::{ByteString, output::Projection};
+
+/// Counts of a join, returned by `run_join` as JSON for the UI
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct JoinStats {
+  pub left_rows: usize,
+  pub right_rows: usize,
+  pub output_rows: usize,
+  /// rows with at least one match
+  pub left_matched: usize,
+  pub left_unmatched: usize,
+  pub right_matched: usize,
+  pub right_unmatched: usize,
+  /// keys that occur in more than one row
+  pub left_duplicate_keys: usize,
+  pub right_duplicate_keys: usize,
+  /// the most right rows matched by one left row, and the key of that row
+  pub max_fanout: usize,
+  pub max_fanout_key: String,
+  /// the side files with the unmatched rows
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub unmatched_left_path: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub unmatched_right_path: Option<String>,
+}
+
+/// csv writer counting the written rows, the rows are projected to the output columns
+pub(super) struct RowWriter<W: Write> {
+  pub(super) wtr: csv::Writer<W>,
+  pub(super) rows: usize,
+  pub(super) projection: Option<Projection>,
+}
+
+impl<W: Write> RowWriter<W> {
+  pub(super) fn new(wtr: csv::Writer<W>) -> Self {
+    RowWriter {
+      wtr,
+      rows: 0,
+      projection: None,
+    }
+  }
+
+  pub(super) fn write_record<I, T>(&mut self, record: I) -> csv::Result<()>
+  where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+  {
+    self.rows += 1;
+    match &self.projection {
+      None => self.wtr.write_record(record),
+      Some(projection) => {
+        let fields: Vec<T> = record.into_iter().collect();
+        self.wtr.write_record(projection.apply(&fields))
+      }
+    }
+  }
+
+  pub(super) fn flush(&mut self) -> io::Result<()> {
+    self.wtr.flush()
+  }
+}
+
+/// Collects the `JoinStats` while a join runs and writes the unmatched rows to the side files
+pub(super) struct Tracker {
+  stats: JoinStats,
+  /// count of every left key, 2 at most
+  left_keys: HashMap<Vec<ByteString>, u8>,
+  /// the left rows come in key order, the duplicates are counted without `left_keys`
+  sorted: bool,
+  prev_left: Option<Vec<ByteString>>,
+  prev_count: usize,
+  unmatched_left: Option<csv::Writer<File>>,
+  unmatched_right: Option<csv::Writer<File>>,
+  /// the sides of the join are swapped (right joins)
+  swapped: bool,
+}
+
+impl Tracker {
+  /// `side_files` are the paths of the unmatched rows of both sides with their headers
+  pub(super) fn new(
+    side_files: Option<[(PathBuf, &ByteRecord); 2]>,
+    delimiter: u8,
+  ) -> Result<Self> {
+    let mut stats = JoinStats::default();
+    let mut writers = [None, None];
+    if let Some(side_files) = side_files {
+      for (i, (path, headers)) in side_files.iter().enumerate() {
+        let mut wtr = csv::WriterBuilder::new()
+          .delimiter(delimiter)
+          .from_path(path)?;
+        wtr.write_record(*headers)?;
+        writers[i] = Some(wtr);
+      }
+      let [left, right] = side_files.map(|(path, _)| Some(path.to_string_lossy().to_string()));
+      stats.unmatched_left_path = left;
+      stats.unmatched_right_path = right;
+    }
+    let [unmatched_left, unmatched_right] = writers;
+
+    Ok(Tracker {
+      stats,
+      left_keys: HashMap::new(),
+      sorted: false,
+      prev_left: None,
+      prev_count: 0,
+      unmatched_left,
+      unmatched_right,
+      swapped: false,
+    })
+  }
+
+  /// The left side is the right file from now on
+  pub(super) fn swap_sides(&mut self) {
+    swap(&mut self.unmatched_left, &mut self.unmatched_right);
+    self.swapped = !self.swapped;
+  }
+
+  pub(super) fn swapped(&self) -> bool {
+    self.swapped
+  }
+
+  /// The left rows come in key order
+  pub(super) fn sorted(&mut self) {
+    self.sorted = true;
+  }
+
+  /// Whether the unmatched rows are written to side files
+  pub(super) fn keeps_unmatched(&self) -> bool {
+    self.unmatched_left.is_some()
+  }
+
+  /// A left row with its number of right rows, `key` is empty for a cross join
+  pub(super) fn left(&mut self, key: &[ByteString], row: &ByteRecord, fanout: usize) -> Result<()> {
+    let stats = &mut self.stats;
+    stats.left_rows += 1;
+    match fanout {
+      0 => {
+        stats.left_unmatched += 1;
+        if let Some(wtr) = self.unmatched_left.as_mut() {
+          wtr.write_record(row)?;
+        }
+      }
+      _ => stats.left_matched += 1,
+    }
+    if fanout > stats.max_fanout {
+      stats.max_fanout = fanout;
+      stats.max_fanout_key = key
+        .iter()
+        .map(|f| String::from_utf8_lossy(f))
+        .collect::<Vec<_>>()
+        .join("|");
+    }
+
+    if key.is_empty() {
+      return Ok(());
+    }
+    if self.sorted {
+      match self.prev_left.as_deref() == Some(key) {
+        true => self.prev_count += 1,
+        false => {
+          self.prev_left = Some(key.to_vec());
+          self.prev_count = 1;
+        }
+      }
+      if self.prev_count == 2 {
+        self.stats.left_duplicate_keys += 1;
+      }
+    } else {
+      // the key is only copied the first time it is seen
+      match self.left_keys.get_mut(key) {
+        Some(count) => {
+          if *count == 1 {
+            self.stats.left_duplicate_keys += 1;
+          }
+          *count = 2;
+        }
+        None => {
+          self.left_keys.insert(key.to_vec(), 1);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// A right row, `row` is only needed for the side file of the unmatched rows
+  pub(super) fn right(&mut self, row: Option<&ByteRecord>, matched: bool) -> Result<()> {
+    self.stats.right_rows += 1;
+    match matched {
+      true => self.stats.right_matched += 1,
+      false => {
+        self.stats.right_unmatched += 1;
+        if let (Some(wtr), Some(row)) = (self.unmatched_right.as_mut(), row) {
+          wtr.write_record(row)?;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  pub(super) fn right_duplicate_keys(&mut self, keys: usize) {
+    self.stats.right_duplicate_keys += keys;
+  }
+
+  pub(super) fn finish(mut self, output_rows: usize) -> Result<JoinStats> {
+    for wtr in [self.unmatched_left.as_mut(), self.unmatched_right.as_mut()]
+      .into_iter()
+      .flatten()
+    {
+      wtr.flush()?;
+    }
+    let mut stats = self.stats;
+    stats.output_rows = output_rows;
+    if self.swapped {
+      swap(&mut stats.left_rows, &mut stats.right_rows);
+      swap(&mut stats.left_matched, &mut stats.right_matched);
+      swap(&mut stats.left_unmatched, &mut stats.right_unmatched);
+      swap(
+        &mut stats.left_duplicate_keys,
+        &mut stats.right_duplicate_keys,
+      );
+    }
+    Ok(stats)
+  }
+}
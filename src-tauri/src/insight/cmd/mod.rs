@@ -3,6 +3,8 @@ pub mod cat;
 pub mod convert;
 pub mod count;
 pub mod datefmt;
+pub mod dedup;
+pub mod diff;
 pub mod enumerate;
 pub mod extsort;
 pub mod fill;
@@ -0,0 +1,114 @@
+use std::{
+  fs::File,
+  io::{BufReader, BufWriter, Read},
+};
+
+use anyhow::{Result, anyhow};
+use csv::StringRecord;
+use regex::Regex;
+
+use crate::{cmd::search::generic::generic_tagged_search_chain, utils::EventEmitter};
+
+/// Columns a search runs on
+#[derive(Debug, Clone)]
+pub enum ColumnSelector {
+  Single(String),
+  /// `*`, every column
+  All,
+  /// `a|b|c`
+  List(Vec<String>),
+  /// `re:<pattern>`, the columns whose name matches the pattern
+  Regex(Regex),
+}
+
+impl ColumnSelector {
+  pub fn parse(column: &str) -> Result<Self> {
+    if column.trim() == "*" {
+      return Ok(ColumnSelector::All);
+    }
+    if let Some(pattern) = column.strip_prefix("re:") {
+      return Ok(ColumnSelector::Regex(
+        Regex::new(pattern).map_err(|e| anyhow!("Invalid column regex '{pattern}': {e}"))?,
+      ));
+    }
+    if column.contains('|') {
+      let names: Vec<String> = column
+        .split('|')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+      return Ok(ColumnSelector::List(names));
+    }
+    Ok(ColumnSelector::Single(column.to_string()))
+  }
+
+  pub fn is_single(&self) -> bool {
+    matches!(self, ColumnSelector::Single(_))
+  }
+
+  /// Names of the selected columns, in the order of `headers` for `*` and regex
+  pub fn resolve(&self, headers: &StringRecord) -> Result<Vec<String>> {
+    let names: Vec<String> = match self {
+      ColumnSelector::Single(name) => vec![name.clone()],
+      ColumnSelector::List(names) => names.clone(),
+      ColumnSelector::All => headers.iter().map(|h| h.to_string()).collect(),
+      ColumnSelector::Regex(re) => headers
+        .iter()
+        .filter(|h| re.is_match(h))
+        .map(|h| h.to_string())
+        .collect(),
+    };
+    if names.is_empty() {
+      return Err(anyhow!("No column matches the column selector"));
+    }
+    for name in &names {
+      if !headers.iter().any(|h| h == name) {
+        return Err(anyhow!("Column '{name}' not found in headers."));
+      }
+    }
+    Ok(names)
+  }
+}
+
+/// Search the columns of `selector`, a row matches when any column matches,
+/// or every column when `require_all` (the negated modes).
+/// The names of the matching columns are written to `matched_column`.
+pub async fn columns_search<E, F>(
+  mut rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
+  wtr: csv::Writer<BufWriter<File>>,
+  selector: &ColumnSelector,
+  match_fn: F,
+  require_all: bool,
+  matched_column: Option<String>,
+  progress: bool,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+  F: Fn(&str) -> bool + Send + Sync + 'static,
+{
+  let names = selector.resolve(rdr.headers()?)?;
+  let tag_headers = vec![
+    matched_column
+      .filter(|c| !c.is_empty())
+      .unwrap_or_else(|| "matched_columns".to_string()),
+  ];
+
+  let columns = names.clone();
+  let row_fn = move |values: &[&str]| {
+    let matched: Vec<&str> = values
+      .iter()
+      .zip(&names)
+      .filter(|(value, _)| match_fn(value))
+      .map(|(_, name)| name.as_str())
+      .collect();
+    let hit = if require_all {
+      matched.len() == names.len()
+    } else {
+      !matched.is_empty()
+    };
+    hit.then(|| vec![matched.join("|")])
+  };
+
+  generic_tagged_search_chain(rdr, wtr, columns, tag_headers, progress, row_fn, emitter).await
+}
@@ -0,0 +1,232 @@
+use std::{
+  fs::File,
+  io::{BufReader, BufWriter, Read},
+};
+
+use anyhow::{Result, anyhow};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+use crate::{
+  cmd::{
+    datefmt::parse_to_naive_datetime,
+    search::generic::{generic_parallel_search, generic_search},
+  },
+  index::Indexed,
+  io::csv::options::CsvOptions,
+  utils::EventEmitter,
+};
+
+pub const DATE_MODES: &[&str] = &[
+  "date_before",
+  "date_after",
+  "date_between",
+  "same_month",
+  "same_quarter",
+  "same_year",
+  "weekday",
+  "weekend",
+];
+
+pub fn is_date_mode(mode: &str) -> bool {
+  DATE_MODES.contains(&mode)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateMode {
+  Before,
+  After,
+  Between,
+  SameMonth,
+  SameQuarter,
+  SameYear,
+  Weekday,
+  Weekend,
+}
+
+/// Parse with `format` when given, otherwise with the flexible parser of `datefmt`
+pub fn parse_date(s: &str, format: Option<&str>) -> Option<NaiveDateTime> {
+  let s = s.trim();
+  if s.is_empty() {
+    return None;
+  }
+  match format {
+    Some(fmt) => NaiveDateTime::parse_from_str(s, fmt).ok().or_else(|| {
+      NaiveDate::parse_from_str(s, fmt)
+        .ok()
+        .map(|d| d.and_time(NaiveTime::MIN))
+    }),
+    None => parse_to_naive_datetime(s),
+  }
+}
+
+/// A condition of the same_* modes, a full date or a period: `2024-03`, `2024/3`,
+/// `202403`, `2024Q1`, `2024-Q1` or `2024`
+fn parse_period(s: &str, format: Option<&str>) -> Option<NaiveDate> {
+  if let Some(dt) = parse_date(s, format) {
+    return Some(dt.date());
+  }
+  let s = s.trim();
+  let upper = s.to_uppercase();
+  if let Some((year, quarter)) = upper.split_once('Q') {
+    let year = year.trim_end_matches(['-', '/', ' ']).parse::<i32>().ok()?;
+    let quarter = quarter
+      .parse::<u32>()
+      .ok()
+      .filter(|q| (1..=4).contains(q))?;
+    return NaiveDate::from_ymd_opt(year, (quarter - 1) * 3 + 1, 1);
+  }
+  let month = if s.len() == 6 && s.bytes().all(|b| b.is_ascii_digit()) {
+    NaiveDate::parse_from_str(&format!("{s}01"), "%Y%m%d").ok()
+  } else {
+    NaiveDate::parse_from_str(&format!("{s}-01"), "%Y-%m-%d")
+      .or_else(|_| NaiveDate::parse_from_str(&format!("{s}/01"), "%Y/%m/%d"))
+      .ok()
+  };
+  if month.is_some() {
+    return month;
+  }
+  match s.len() {
+    4 => NaiveDate::from_ymd_opt(s.parse::<i32>().ok()?, 1, 1),
+    _ => None,
+  }
+}
+
+fn quarter(date: &NaiveDate) -> u32 {
+  (date.month() - 1) / 3 + 1
+}
+
+/// A date search mode compiled with its conditions, cells that are not a date never match
+pub struct DateMatcher {
+  mode: DateMode,
+  /// before/after: the date, between: start and exclusive end
+  bounds: (NaiveDateTime, NaiveDateTime),
+  periods: Vec<NaiveDate>,
+  format: Option<String>,
+}
+
+impl DateMatcher {
+  /// `condition` is split on `|`, `format` is a chrono format for the cells
+  /// and conditions, e.g. `%d/%m/%Y`
+  pub fn new(mode: &str, condition: &str, format: Option<&str>) -> Result<Self> {
+    let format = format.map(str::trim).filter(|f| !f.is_empty());
+    let mode = match mode {
+      "date_before" => DateMode::Before,
+      "date_after" => DateMode::After,
+      "date_between" => DateMode::Between,
+      "same_month" => DateMode::SameMonth,
+      "same_quarter" => DateMode::SameQuarter,
+      "same_year" => DateMode::SameYear,
+      "weekday" => DateMode::Weekday,
+      "weekend" => DateMode::Weekend,
+      _ => return Err(anyhow!("Unsupported date search mode: {mode}")),
+    };
+
+    let conditions: Vec<&str> = condition
+      .split('|')
+      .map(str::trim)
+      .filter(|s| !s.is_empty())
+      .collect();
+    let date = |s: &str| parse_date(s, format).ok_or_else(|| anyhow!("Invalid date: {s}"));
+
+    let mut bounds = (NaiveDateTime::MIN, NaiveDateTime::MAX);
+    let mut periods = Vec::new();
+    match mode {
+      DateMode::Before | DateMode::After => {
+        let value = conditions
+          .first()
+          .ok_or_else(|| anyhow!("A date is required"))?;
+        let value = date(value)?;
+        bounds = (value, value);
+      }
+      DateMode::Between => {
+        if conditions.len() != 2 {
+          return Err(anyhow!(
+            "Exactly two dates required for date_between: start and end"
+          ));
+        }
+        let (a, b) = (date(conditions[0])?, date(conditions[1])?);
+        let (start, end) = if a <= b { (a, b) } else { (b, a) };
+        // an end without time includes the whole day
+        let end = if end.time() == NaiveTime::MIN {
+          end + Duration::days(1)
+        } else {
+          end + Duration::nanoseconds(1)
+        };
+        bounds = (start, end);
+      }
+      DateMode::SameMonth | DateMode::SameQuarter | DateMode::SameYear => {
+        if conditions.is_empty() {
+          return Err(anyhow!("A date or period is required"));
+        }
+        for cond in conditions {
+          periods.push(parse_period(cond, format).ok_or_else(|| anyhow!("Invalid date: {cond}"))?);
+        }
+      }
+      DateMode::Weekday | DateMode::Weekend => {}
+    }
+
+    Ok(DateMatcher {
+      mode,
+      bounds,
+      periods,
+      format: format.map(|f| f.to_string()),
+    })
+  }
+
+  pub fn is_match(&self, value: &str) -> bool {
+    let Some(dt) = parse_date(value, self.format.as_deref()) else {
+      return false;
+    };
+    let date = dt.date();
+    match self.mode {
+      DateMode::Before => dt < self.bounds.0,
+      DateMode::After => dt > self.bounds.0,
+      DateMode::Between => dt >= self.bounds.0 && dt < self.bounds.1,
+      DateMode::SameMonth => self
+        .periods
+        .iter()
+        .any(|p| p.year() == date.year() && p.month() == date.month()),
+      DateMode::SameQuarter => self
+        .periods
+        .iter()
+        .any(|p| p.year() == date.year() && quarter(p) == quarter(&date)),
+      DateMode::SameYear => self.periods.iter().any(|p| p.year() == date.year()),
+      DateMode::Weekday => !matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+      DateMode::Weekend => matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+    }
+  }
+}
+
+pub async fn date_search<E>(
+  rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
+  wtr: csv::Writer<BufWriter<File>>,
+  opts: CsvOptions<String>,
+  idx: Option<Indexed<File, File>>,
+  column: String,
+  matcher: DateMatcher,
+  progress: bool,
+  threads: Option<usize>,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+{
+  let jobs = threads.unwrap_or(1);
+  let match_fn = move |value: &str, _: &[String]| matcher.is_match(value);
+  match jobs {
+    1 => generic_search(rdr, wtr, column, Vec::new(), progress, match_fn, emitter).await,
+    _ => tokio::task::spawn_blocking(move || {
+      generic_parallel_search(
+        opts,
+        &mut idx.unwrap(),
+        wtr,
+        column,
+        Vec::new(),
+        jobs,
+        match_fn,
+      )
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?,
+  }
+}
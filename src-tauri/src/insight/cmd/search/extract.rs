@@ -0,0 +1,195 @@
+use std::{
+  fs::File,
+  io::{BufRead, BufReader, BufWriter, Read},
+  path::PathBuf,
+};
+
+use anyhow::{Result, anyhow};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use crate::{
+  cmd::search::generic::{generic_parallel_tagged_search, generic_tagged_search},
+  index::Indexed,
+  io::csv::options::CsvOptions,
+  utils::{EventEmitter, WTR_BUFFER_SIZE},
+};
+
+/// Options of a regex search writing its capture groups to new columns
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExtractOptions {
+  /// every match joined by `separator`, only the first match otherwise
+  #[serde(default)]
+  pub all: bool,
+  /// `|` by default
+  pub separator: Option<String>,
+}
+
+/// Capture groups of a regex as output columns: the named groups, the numbered
+/// groups (`group_1`, ...) when none is named, or the whole match (`match`)
+pub struct Extractor {
+  re: Regex,
+  /// index of the capture group of every column
+  groups: Vec<usize>,
+  headers: Vec<String>,
+  all: bool,
+  separator: String,
+}
+
+impl Extractor {
+  pub fn new(pattern: &str, opts: &ExtractOptions, case_insensitive: bool) -> Result<Self> {
+    let re = RegexBuilder::new(pattern)
+      .case_insensitive(case_insensitive)
+      .build()
+      .map_err(|e| anyhow!("Invalid regex '{pattern}': {e}"))?;
+
+    let names: Vec<Option<&str>> = re.capture_names().collect();
+    let mut groups = Vec::new();
+    let mut headers = Vec::new();
+    for (i, name) in names.iter().enumerate().skip(1) {
+      if let Some(name) = name {
+        groups.push(i);
+        headers.push(name.to_string());
+      }
+    }
+    if groups.is_empty() {
+      for i in 1..names.len() {
+        groups.push(i);
+        headers.push(format!("group_{i}"));
+      }
+    }
+    if groups.is_empty() {
+      groups.push(0);
+      headers.push("match".to_string());
+    }
+
+    Ok(Extractor {
+      re,
+      groups,
+      headers,
+      all: opts.all,
+      separator: opts.separator.clone().unwrap_or_else(|| "|".to_string()),
+    })
+  }
+
+  pub fn headers(&self) -> &[String] {
+    &self.headers
+  }
+
+  /// Add `_2`, `_3`, ... to the columns whose name is already in `existing`
+  pub fn rename_clashes<T: AsRef<str>>(&mut self, existing: &[T]) {
+    let mut taken: Vec<String> = existing.iter().map(|h| h.as_ref().to_string()).collect();
+    for header in self.headers.iter_mut() {
+      let base = header.clone();
+      let mut n = 1;
+      while taken.contains(header) {
+        n += 1;
+        *header = format!("{base}_{n}");
+      }
+      taken.push(header.clone());
+    }
+  }
+
+  /// The value of every column, `None` when the regex does not match
+  pub fn extract(&self, value: &str) -> Option<Vec<String>> {
+    if !self.all {
+      let caps = self.re.captures(value)?;
+      return Some(
+        self
+          .groups
+          .iter()
+          .map(|&i| caps.get(i).map_or("", |m| m.as_str()).to_string())
+          .collect(),
+      );
+    }
+
+    let mut columns: Vec<Vec<&str>> = vec![Vec::new(); self.groups.len()];
+    let mut matched = false;
+    for caps in self.re.captures_iter(value) {
+      matched = true;
+      // a group outside the matched alternative is empty, the columns stay aligned
+      for (column, &i) in columns.iter_mut().zip(&self.groups) {
+        column.push(caps.get(i).map_or("", |m| m.as_str()));
+      }
+    }
+    matched.then(|| columns.iter().map(|c| c.join(&self.separator)).collect())
+  }
+}
+
+/// Regex search writing the capture groups of the matched rows to new columns
+pub async fn extract_search<E>(
+  mut rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
+  wtr: csv::Writer<BufWriter<File>>,
+  opts: CsvOptions<String>,
+  idx: Option<Indexed<File, File>>,
+  column: String,
+  mut extractor: Extractor,
+  progress: bool,
+  threads: Option<usize>,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+{
+  let jobs = threads.unwrap_or(1);
+  extractor.rename_clashes(&rdr.headers()?.iter().collect::<Vec<_>>());
+  let tag_headers = extractor.headers().to_vec();
+  let match_fn = move |value: &str, _: &[String]| extractor.extract(value);
+  match jobs {
+    1 => {
+      generic_tagged_search(
+        rdr,
+        wtr,
+        column,
+        Vec::new(),
+        tag_headers,
+        progress,
+        match_fn,
+        emitter,
+      )
+      .await
+    }
+    _ => tokio::task::spawn_blocking(move || {
+      generic_parallel_tagged_search(
+        opts,
+        &mut idx.unwrap(),
+        wtr,
+        column,
+        Vec::new(),
+        tag_headers,
+        jobs,
+        match_fn,
+      )
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?,
+  }
+}
+
+/// Line mode of `extract_search` for files that are not valid csv:
+/// writes a csv with the matched line and the capture groups
+pub async fn irregular_extract(
+  reader: BufReader<Box<dyn Read + Send>>,
+  output_path: PathBuf,
+  mut extractor: Extractor,
+) -> Result<String> {
+  extractor.rename_clashes(&["line"]);
+  let mut wtr = csv::WriterBuilder::new().from_writer(BufWriter::with_capacity(
+    WTR_BUFFER_SIZE,
+    File::create(output_path)?,
+  ));
+  wtr
+    .write_record(std::iter::once("line").chain(extractor.headers().iter().map(|h| h.as_str())))?;
+
+  let mut total = 0;
+  for line in reader.lines() {
+    let line = line?;
+    if let Some(values) = extractor.extract(&line) {
+      wtr.write_record(std::iter::once(line.as_str()).chain(values.iter().map(|v| v.as_str())))?;
+      total += 1;
+    }
+  }
+  wtr.flush()?;
+
+  Ok(total.to_string())
+}
@@ -3,11 +3,245 @@ use std::path::Path;
 use anyhow::{Result, anyhow};
 
 use crate::{
-  cmd::search::{generic, perform::ColumnConfig},
+  cmd::search::{
+    columns::ColumnSelector,
+    dates::{self, DateMatcher},
+    fuzzy::{self, Fuzzy, FuzzyOptions, Metric},
+    generic,
+    highlight::Highlighter,
+    perform::ColumnConfig,
+    stats::{self, StatsOptions},
+  },
   io::csv::{config::CsvConfigBuilder, options::CsvOptions},
+  normalize::TextNorm,
   utils::EventEmitter,
 };
 
+/// 单个值是否满足条件
+fn match_value(mode: &str, sub_conds: &[String], value: &str) -> bool {
+  match mode {
+    "equal" => sub_conds.iter().any(|c| value == c),
+    "not_equal" => sub_conds.iter().all(|c| value != c),
+    "contains" => sub_conds.iter().any(|c| value.contains(c)),
+    "not_contains" => sub_conds.iter().all(|c| !value.contains(c)),
+    "starts_with" => sub_conds.iter().any(|c| value.starts_with(c)),
+    "not_starts_with" => sub_conds.iter().all(|c| !value.starts_with(c)),
+    "ends_with" => sub_conds.iter().any(|c| value.ends_with(c)),
+    "not_ends_with" => sub_conds.iter().all(|c| !value.ends_with(c)),
+    "regex" => sub_conds.iter().any(|pattern| {
+      regex::Regex::new(pattern)
+        .ok()
+        .map_or(false, |re| re.is_match(value))
+    }),
+    "is_null" => value.is_empty(),
+    "is_not_null" => !value.is_empty(),
+    "gt" => {
+      if let (Some(threshold), Ok(val)) = (sub_conds.get(0), value.parse::<f64>()) {
+        if let Ok(t) = threshold.parse::<f64>() {
+          val > t
+        } else {
+          false
+        }
+      } else {
+        false
+      }
+    }
+    "ge" => {
+      if let (Some(threshold), Ok(val)) = (sub_conds.get(0), value.parse::<f64>()) {
+        if let Ok(t) = threshold.parse::<f64>() {
+          val >= t
+        } else {
+          false
+        }
+      } else {
+        false
+      }
+    }
+    "lt" => {
+      if let (Some(threshold), Ok(val)) = (sub_conds.get(0), value.parse::<f64>()) {
+        if let Ok(t) = threshold.parse::<f64>() {
+          val < t
+        } else {
+          false
+        }
+      } else {
+        false
+      }
+    }
+    "le" => {
+      if let (Some(threshold), Ok(val)) = (sub_conds.get(0), value.parse::<f64>()) {
+        if let Ok(t) = threshold.parse::<f64>() {
+          val <= t
+        } else {
+          false
+        }
+      } else {
+        false
+      }
+    }
+    "between" => {
+      if sub_conds.len() == 2 {
+        if let (Ok(low), Ok(high)) = (sub_conds[0].parse::<f64>(), sub_conds[1].parse::<f64>()) {
+          if let Ok(val) = value.parse::<f64>() {
+            return val >= low && val <= high;
+          }
+        }
+      }
+      false
+    }
+    _ => false,
+  }
+}
+
+/// Selected columns, tag headers and the row predicate of a search chain.
+/// The predicate pushes the index of every config that holds for the row.
+fn build_chain(
+  headers: &csv::StringRecord,
+  configs: Vec<ColumnConfig>,
+  logics: Vec<String>,
+) -> Result<(
+  Vec<String>,
+  Vec<String>,
+  impl Fn(&[&str], &mut Vec<usize>) -> Option<Vec<String>> + Send + Sync + 'static,
+)> {
+  if configs.is_empty() {
+    return Err(anyhow!("No filters added"));
+  }
+  if logics.len() != configs.len().saturating_sub(1) {
+    return Err(anyhow!("logics length must be configs.len() - 1"));
+  }
+
+  // 每个条件可以选择多列: `*`, `a|b` 或 `re:<pattern>`
+  let mut columns: Vec<String> = Vec::new();
+  let mut ranges = Vec::with_capacity(configs.len());
+  let mut multi_column = false;
+  // `<column>_score` for a single column, `fuzzy_score_<n>` for the n-th condition otherwise
+  let mut score_names = Vec::with_capacity(configs.len());
+  for (i, cfg) in configs.iter().enumerate() {
+    let selector = ColumnSelector::parse(&cfg.column)?;
+    multi_column |= !selector.is_single();
+    let names = selector.resolve(headers)?;
+    score_names.push(match selector.is_single() {
+      true => format!("{}_score", names[0]),
+      false => format!("fuzzy_score_{}", i + 1),
+    });
+    ranges.push(columns.len()..columns.len() + names.len());
+    columns.extend(names);
+  }
+
+  // 预解析每个 condition
+  let mut parsed_configs: Vec<(String, Vec<String>, Option<Fuzzy>, Option<DateMatcher>)> =
+    Vec::new();
+  let mut score_headers = Vec::new();
+  for (cfg, score_name) in configs.into_iter().zip(score_names) {
+    let sub_conds = cfg
+      .condition
+      .split('|')
+      .map(|s| s.trim().to_string())
+      .collect::<Vec<_>>();
+    let fuzzy = match cfg.mode.as_str() {
+      "fuzzy" => {
+        score_headers.push(score_name);
+        Some(Fuzzy::new(
+          Metric::parse(cfg.metric.as_deref())?,
+          cfg.threshold,
+          &sub_conds,
+          TextNorm::default(),
+        )?)
+      }
+      _ => None,
+    };
+    let date = match dates::is_date_mode(&cfg.mode) {
+      true => Some(DateMatcher::new(
+        &cfg.mode,
+        &cfg.condition,
+        cfg.date_format.as_deref(),
+      )?),
+      false => None,
+    };
+    parsed_configs.push((cfg.mode, sub_conds, fuzzy, date));
+  }
+
+  let mut tag_headers = score_headers;
+  if multi_column {
+    tag_headers.push("matched_columns".to_string());
+  }
+  let names = columns.clone();
+
+  // 构造 match_fn
+  let match_fn = move |values: &[&str], held: &mut Vec<usize>| -> Option<Vec<String>> {
+    // fuzzy 条件的得分
+    let mut scores = Vec::new();
+    // 匹配到的列
+    let mut matched: Vec<&str> = Vec::new();
+    // 每个条件独立判断, 多列时任意一列满足即可, 否定条件需要每列都满足
+    let col_results: Vec<bool> = ranges
+      .iter()
+      .enumerate()
+      .map(|(i, range)| {
+        let (mode, sub_conds, fuzzy, date) = &parsed_configs[i];
+        let negated = mode.starts_with("not_");
+        let mut hits = 0;
+        let mut best: Option<f64> = None;
+        for j in range.clone() {
+          let hit = match fuzzy {
+            Some(fuzzy) => {
+              let score = fuzzy.best(values[j]);
+              if score > best {
+                best = score;
+              }
+              score.is_some()
+            }
+            None => match date {
+              Some(date) => date.is_match(values[j]),
+              None => match_value(mode, sub_conds, values[j]),
+            },
+          };
+          if hit {
+            hits += 1;
+            if range.len() > 1 && !matched.contains(&names[j].as_str()) {
+              matched.push(names[j].as_str());
+            }
+          }
+        }
+        if fuzzy.is_some() {
+          scores.push(best.map(fuzzy::format_score).unwrap_or_default());
+        }
+        if negated {
+          hits == range.len()
+        } else {
+          hits > 0
+        }
+      })
+      .collect();
+    held.extend((0..col_results.len()).filter(|&i| col_results[i]));
+
+    // 链式组合
+    let mut result = col_results[0];
+    for i in 1..col_results.len() {
+      match logics[i - 1].as_str() {
+        "and" => result = result && col_results[i],
+        "or" => result = result || col_results[i],
+        _ => {} // fallback to AND
+      }
+    }
+    if multi_column {
+      scores.push(matched.join("|"));
+    }
+    result.then_some(scores)
+  };
+
+  Ok((columns, tag_headers, match_fn))
+}
+
+/// 每个条件的标签, 用于 count-only 的统计
+fn chain_labels(configs: &[ColumnConfig]) -> Vec<String> {
+  configs
+    .iter()
+    .map(|cfg| format!("{} {} {}", cfg.column, cfg.mode, cfg.condition))
+    .collect()
+}
+
 pub async fn search_with_chain<E, P>(
   path: P,
   configs: Vec<ColumnConfig>,
@@ -22,15 +256,6 @@ where
   E: EventEmitter + Send + Sync + 'static,
   P: AsRef<Path> + Send + Sync,
 {
-  if configs.is_empty() {
-    return Err(anyhow!("No filters added"));
-  }
-  if logics.len() != configs.len().saturating_sub(1) {
-    return Err(anyhow!("logics length must be configs.len() - 1"));
-  }
-
-  let columns: Vec<String> = configs.iter().map(|c| c.column.clone()).collect();
-
   let mut opts = CsvOptions::new(path.as_ref().to_string_lossy().to_string());
   opts.set_skiprows(skiprows);
   let (sep, reader) = opts.skiprows_and_delimiter()?;
@@ -40,7 +265,10 @@ where
     .delimiter(sep)
     .quoting(quoting)
     .build();
-  let rdr = config.build_reader(reader);
+  let mut rdr = config.build_reader(reader);
+
+  let headers = rdr.headers()?.clone();
+  let (columns, tag_headers, match_fn) = build_chain(&headers, configs, logics)?;
   let wtr = config.build_writer(&output_path)?;
 
   let total_rows = if progress {
@@ -50,118 +278,105 @@ where
   };
   emitter.emit_total_rows(total_rows).await?;
 
-  // 预解析每个 condition
-  let parsed_configs: Vec<(String, Vec<String>)> = configs
-    .into_iter()
-    .map(|cfg| {
-      let sub_conds = cfg
-        .condition
-        .split('|')
-        .map(|s| s.trim().to_string())
-        .collect::<Vec<_>>();
-      (cfg.mode, sub_conds)
-    })
-    .collect();
+  let match_count = generic::generic_tagged_search_chain(
+    rdr,
+    wtr,
+    columns,
+    tag_headers,
+    progress,
+    move |values: &[&str]| match_fn(values, &mut Vec::new()),
+    emitter,
+  )
+  .await?;
 
-  // 构造 match_fn
-  let match_fn = move |values: &[&str]| -> bool {
-    // 每列独立判断
-    let col_results: Vec<bool> = values
-      .iter()
-      .enumerate()
-      .map(|(i, &value)| {
-        let (mode, sub_conds) = &parsed_configs[i];
-        match mode.as_str() {
-          "equal" => sub_conds.iter().any(|c| value == c),
-          "not_equal" => sub_conds.iter().all(|c| value != c),
-          "contains" => sub_conds.iter().any(|c| value.contains(c)),
-          "not_contains" => sub_conds.iter().all(|c| !value.contains(c)),
-          "starts_with" => sub_conds.iter().any(|c| value.starts_with(c)),
-          "not_starts_with" => sub_conds.iter().all(|c| !value.starts_with(c)),
-          "ends_with" => sub_conds.iter().any(|c| value.ends_with(c)),
-          "not_ends_with" => sub_conds.iter().all(|c| !value.ends_with(c)),
-          "regex" => sub_conds.iter().any(|pattern| {
-            regex::Regex::new(pattern)
-              .ok()
-              .map_or(false, |re| re.is_match(value))
-          }),
-          "is_null" => value.is_empty(),
-          "is_not_null" => !value.is_empty(),
-          "gt" => {
-            if let (Some(threshold), Ok(val)) = (sub_conds.get(0), value.parse::<f64>()) {
-              if let Ok(t) = threshold.parse::<f64>() {
-                val > t
-              } else {
-                false
-              }
-            } else {
-              false
-            }
-          }
-          "ge" => {
-            if let (Some(threshold), Ok(val)) = (sub_conds.get(0), value.parse::<f64>()) {
-              if let Ok(t) = threshold.parse::<f64>() {
-                val >= t
-              } else {
-                false
-              }
-            } else {
-              false
-            }
-          }
-          "lt" => {
-            if let (Some(threshold), Ok(val)) = (sub_conds.get(0), value.parse::<f64>()) {
-              if let Ok(t) = threshold.parse::<f64>() {
-                val < t
-              } else {
-                false
-              }
-            } else {
-              false
-            }
-          }
-          "le" => {
-            if let (Some(threshold), Ok(val)) = (sub_conds.get(0), value.parse::<f64>()) {
-              if let Ok(t) = threshold.parse::<f64>() {
-                val <= t
-              } else {
-                false
-              }
-            } else {
-              false
-            }
-          }
-          "between" => {
-            if sub_conds.len() == 2 {
-              if let (Ok(low), Ok(high)) =
-                (sub_conds[0].parse::<f64>(), sub_conds[1].parse::<f64>())
-              {
-                if let Ok(val) = value.parse::<f64>() {
-                  return val >= low && val <= high;
-                }
-              }
-            }
-            false
-          }
-          _ => false,
-        }
-      })
-      .collect();
+  Ok(match_count)
+}
 
-    // 链式组合
-    let mut result = col_results[0];
-    for i in 1..col_results.len() {
-      match logics[i - 1].as_str() {
-        "and" => result = result && col_results[i],
-        "or" => result = result || col_results[i],
-        _ => {} // fallback to AND
+/// Count-only search chain, nothing is written.
+/// Returns the counts of every condition and a preview of the first matches as json.
+pub async fn count_with_chain<E, P>(
+  path: P,
+  configs: Vec<ColumnConfig>,
+  logics: Vec<String>,
+  skiprows: usize,
+  quoting: bool,
+  flexible: bool,
+  stats_opts: StatsOptions,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+  P: AsRef<Path> + Send + Sync,
+{
+  let mut opts = CsvOptions::new(path.as_ref().to_string_lossy().to_string());
+  opts.set_skiprows(skiprows);
+  let (sep, reader) = opts.skiprows_and_delimiter()?;
+  let config = CsvConfigBuilder::new()
+    .flexible(flexible)
+    .delimiter(sep)
+    .quoting(quoting)
+    .build();
+  let mut rdr = config.build_reader(reader);
+
+  let headers = rdr.headers()?.clone();
+  let labels = chain_labels(&configs);
+
+  // 预览行中每个条件匹配到的位置
+  let mut highlighters = Vec::new();
+  if stats_opts.highlight {
+    for cfg in &configs {
+      let fuzzy_opts = FuzzyOptions {
+        metric: cfg.metric.clone(),
+        threshold: cfg.threshold,
+        ..Default::default()
+      };
+      let highlighter = Highlighter::new(
+        &cfg.mode,
+        &cfg.condition,
+        TextNorm::default(),
+        Some(&fuzzy_opts),
+      )?;
+      if let Some(h) = highlighter {
+        highlighters.push((ColumnSelector::parse(&cfg.column)?.resolve(&headers)?, h));
       }
     }
-    result
-  };
+  }
+  let (columns, tag_headers, match_fn) = build_chain(&headers, configs, logics)?;
 
-  let match_count =
-    generic::generic_search_chain(rdr, wtr, columns, progress, match_fn, emitter).await?;
+  let limit = stats_opts.preview;
+  let search_stats = match opts.indexed()? {
+    Some(_) => {
+      tokio::task::spawn_blocking(move || {
+        stats::stats_parallel_search(
+          &opts,
+          &columns,
+          labels,
+          tag_headers,
+          limit,
+          highlighters,
+          0,
+          match_fn,
+        )
+      })
+      .await??
+    }
+    None => {
+      tokio::task::spawn_blocking(move || {
+        stats::stats_search(
+          rdr,
+          &columns,
+          labels,
+          tag_headers,
+          limit,
+          highlighters,
+          match_fn,
+        )
+      })
+      .await??
+    }
+  };
+  emitter.emit_total_rows(search_stats.rows).await?;
+  emitter.emit_update_rows(search_stats.rows).await?;
 
-  Ok(match_count)
+  Ok(serde_json::to_string(&search_stats)?)
 }
@@ -1,8 +1,8 @@
 use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 
-use crate::{cmd::search::generic::generic_multi_search, utils::EventEmitter};
+use crate::{cmd::search::generic::generic_multi_search, normalize::TextNorm, utils::EventEmitter};
 
 pub async fn equal_multi<E, P>(
   path: P,
@@ -107,3 +107,45 @@ where
   )
   .await
 }
+
+/// `equal_multi`, `contains_multi`, `starts_with_multi` or `ends_with_multi`
+/// with the cells and the conditions normalised by `norm`
+pub async fn multi_with_norm<E, P>(
+  path: P,
+  column: String,
+  conditions: Vec<String>,
+  mode: &str,
+  norm: TextNorm,
+  skiprows: usize,
+  quoting: bool,
+  progress: bool,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+  P: AsRef<Path> + Send + Sync + 'static,
+{
+  let compare: fn(&str, &str) -> bool = match mode {
+    "equal_multi" => |value, condition| value == condition,
+    "contains_multi" => |value, condition| value.contains(condition),
+    "starts_with_multi" => |value, condition| value.starts_with(condition),
+    "ends_with_multi" => |value, condition| value.ends_with(condition),
+    _ => return Err(anyhow!("Unsupported search mode: {mode}")),
+  };
+  let conditions = conditions
+    .iter()
+    .map(|c| norm.apply(c).into_owned())
+    .collect();
+
+  generic_multi_search(
+    path,
+    column,
+    conditions,
+    skiprows,
+    quoting,
+    progress,
+    move |value, condition| compare(&norm.apply(value), condition),
+    emitter,
+  )
+  .await
+}
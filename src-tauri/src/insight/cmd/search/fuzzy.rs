@@ -0,0 +1,205 @@
+use std::{
+  collections::BTreeSet,
+  fs::File,
+  io::{BufReader, BufWriter, Read},
+};
+
+use anyhow::{Result, anyhow};
+
+use crate::{
+  cmd::search::generic::{generic_parallel_tagged_search, generic_tagged_search},
+  index::Indexed,
+  io::csv::options::CsvOptions,
+  normalize::TextNorm,
+  utils::EventEmitter,
+};
+
+/// Similarity used by `fuzzy`, every metric scores from 0 (different) to 1 (equal)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+  /// 1 - edit distance / length of the longer value
+  Levenshtein,
+  JaroWinkler,
+  /// compares the sets of words, ignoring word order and repeated words
+  TokenSet,
+}
+
+impl Metric {
+  pub fn parse(metric: Option<&str>) -> Result<Self> {
+    match metric.unwrap_or("levenshtein") {
+      "" | "levenshtein" => Ok(Metric::Levenshtein),
+      "jaro_winkler" => Ok(Metric::JaroWinkler),
+      "token_set" => Ok(Metric::TokenSet),
+      other => Err(anyhow!("Unsupported fuzzy metric: {other}")),
+    }
+  }
+
+  pub fn score(&self, a: &str, b: &str) -> f64 {
+    match self {
+      Metric::Levenshtein => strsim::normalized_levenshtein(a, b),
+      Metric::JaroWinkler => strsim::jaro_winkler(a, b),
+      Metric::TokenSet => token_set_ratio(a, b),
+    }
+  }
+}
+
+/// token set ratio of fuzzywuzzy: the common words compared with
+/// the common words plus the rest of either side
+fn token_set_ratio(a: &str, b: &str) -> f64 {
+  let ta: BTreeSet<&str> = a.split_whitespace().collect();
+  let tb: BTreeSet<&str> = b.split_whitespace().collect();
+  if ta.is_empty() || tb.is_empty() {
+    return if ta.is_empty() && tb.is_empty() {
+      1.0
+    } else {
+      0.0
+    };
+  }
+
+  let join = |words: Vec<&str>| words.join(" ");
+  let common = join(ta.intersection(&tb).copied().collect());
+  let with = |rest: Vec<&str>| {
+    let rest = join(rest);
+    match (common.is_empty(), rest.is_empty()) {
+      (true, _) => rest,
+      (_, true) => common.clone(),
+      _ => format!("{common} {rest}"),
+    }
+  };
+  let sa = with(ta.difference(&tb).copied().collect());
+  let sb = with(tb.difference(&ta).copied().collect());
+
+  let ratio = strsim::normalized_levenshtein;
+  let mut best = ratio(&sa, &sb);
+  if !common.is_empty() {
+    best = best.max(ratio(&common, &sa)).max(ratio(&common, &sb));
+  }
+  best
+}
+
+/// A minimum score between 0 and 1, 0.8 by default, larger values are read as a percentage
+pub fn parse_threshold(threshold: Option<f64>) -> Result<f64> {
+  let mut threshold = threshold.unwrap_or(0.8);
+  if threshold > 1.0 {
+    threshold /= 100.0;
+  }
+  if !(0.0..=1.0).contains(&threshold) {
+    return Err(anyhow!("Fuzzy threshold must be between 0 and 1"));
+  }
+  Ok(threshold)
+}
+
+/// Options of the `fuzzy` search mode
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct FuzzyOptions {
+  /// levenshtein (default), jaro_winkler or token_set
+  pub metric: Option<String>,
+  /// minimum score, 0.8 by default
+  pub threshold: Option<f64>,
+  /// name of the output column with the best score, `fuzzy_score` by default
+  pub score_column: Option<String>,
+}
+
+/// `fuzzy` search mode: the best score of the cell against the conditions
+pub struct Fuzzy {
+  metric: Metric,
+  threshold: f64,
+  conditions: Vec<String>,
+  norm: TextNorm,
+}
+
+impl Fuzzy {
+  /// `threshold`, see `parse_threshold`
+  pub fn new(
+    metric: Metric,
+    threshold: Option<f64>,
+    conditions: &[String],
+    norm: TextNorm,
+  ) -> Result<Self> {
+    let threshold = parse_threshold(threshold)?;
+    let conditions: Vec<String> = conditions
+      .iter()
+      .map(|c| norm.apply(c.trim()).into_owned())
+      .filter(|c| !c.is_empty())
+      .collect();
+    if conditions.is_empty() {
+      return Err(anyhow!("Fuzzy search needs at least one condition"));
+    }
+
+    Ok(Fuzzy {
+      metric,
+      threshold,
+      conditions,
+      norm,
+    })
+  }
+
+  /// The best score of `value`, `None` below the threshold
+  pub fn best(&self, value: &str) -> Option<f64> {
+    let value = self.norm.apply(value);
+    let best = self
+      .conditions
+      .iter()
+      .map(|c| self.metric.score(&value, c))
+      .fold(0.0, f64::max);
+    (best >= self.threshold).then_some(best)
+  }
+}
+
+pub fn format_score(score: f64) -> String {
+  format!("{:.4}", score)
+}
+
+pub async fn fuzzy_search<E>(
+  rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
+  wtr: csv::Writer<BufWriter<File>>,
+  opts: CsvOptions<String>,
+  idx: Option<Indexed<File, File>>,
+  column: String,
+  fuzzy: Fuzzy,
+  score_column: Option<String>,
+  progress: bool,
+  threads: Option<usize>,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+{
+  let tag_headers = vec![
+    score_column
+      .filter(|c| !c.is_empty())
+      .unwrap_or_else(|| "fuzzy_score".to_string()),
+  ];
+  let match_fn = move |value: &str, _: &[String]| fuzzy.best(value).map(|s| vec![format_score(s)]);
+
+  let jobs = threads.unwrap_or(1);
+  match jobs {
+    1 => {
+      generic_tagged_search(
+        rdr,
+        wtr,
+        column,
+        Vec::new(),
+        tag_headers,
+        progress,
+        match_fn,
+        emitter,
+      )
+      .await
+    }
+    _ => tokio::task::spawn_blocking(move || {
+      generic_parallel_tagged_search(
+        opts,
+        &mut idx.unwrap(),
+        wtr,
+        column,
+        Vec::new(),
+        tag_headers,
+        jobs,
+        match_fn,
+      )
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?,
+  }
+}
@@ -36,21 +36,55 @@ fn sanitize_condition(condition: &str) -> String {
 }
 
 pub(crate) async fn generic_search<E, F>(
+  rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
+  wtr: csv::Writer<BufWriter<File>>,
+  column: String,
+  conditions: Vec<String>,
+  progress: bool,
+  match_fn: F,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+  F: Fn(&str, &[String]) -> bool + Send + Sync + 'static,
+{
+  generic_tagged_search(
+    rdr,
+    wtr,
+    column,
+    conditions,
+    Vec::new(),
+    progress,
+    move |value, conds| match_fn(value, conds).then(Vec::new),
+    emitter,
+  )
+  .await
+}
+
+/// Like `generic_search`, but `match_fn` returns the values of the `tag_headers` columns
+/// appended to every matched row, `None` when the row does not match
+pub(crate) async fn generic_tagged_search<E, F>(
   mut rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
   mut wtr: csv::Writer<BufWriter<File>>,
   column: String,
   conditions: Vec<String>,
+  tag_headers: Vec<String>,
   progress: bool,
   match_fn: F,
   emitter: E,
 ) -> Result<String>
 where
   E: EventEmitter + Send + Sync + 'static,
-  F: Fn(&str, &[String]) -> bool + Send + Sync + 'static,
+  F: Fn(&str, &[String]) -> Option<Vec<String>> + Send + Sync + 'static,
 {
   let sel = Selection::from_headers(rdr.byte_headers()?, &[column.as_str()][..])?;
 
-  wtr.write_record(rdr.headers()?)?;
+  wtr.write_record(
+    rdr
+      .headers()?
+      .iter()
+      .chain(tag_headers.iter().map(|h| h.as_str())),
+  )?;
 
   let rows = Arc::new(AtomicUsize::new(0));
   let match_rows = Arc::new(AtomicUsize::new(0));
@@ -89,8 +123,12 @@ where
     for result in rdr.records() {
       let record = result?;
       if let Some(value) = record.get(sel.first_indices()?) {
-        if match_fn(value, &conditions) {
-          wtr.write_record(&record)?;
+        if let Some(tags) = match_fn(value, &conditions) {
+          if tags.is_empty() {
+            wtr.write_record(&record)?;
+          } else {
+            wtr.write_record(record.iter().chain(tags.iter().map(|t| t.as_str())))?;
+          }
 
           match_rows.fetch_add(1, Ordering::Relaxed);
         }
@@ -251,7 +289,7 @@ where
 pub(crate) fn generic_parallel_search<F>(
   opts: CsvOptions<String>,
   idx: &mut Indexed<File, File>,
-  mut wtr: Writer<BufWriter<File>>,
+  wtr: Writer<BufWriter<File>>,
   column: String,
   conditions: Vec<String>,
   jobs: usize,
@@ -259,6 +297,32 @@ pub(crate) fn generic_parallel_search<F>(
 ) -> Result<String>
 where
   F: Fn(&str, &[String]) -> bool + Send + Sync + 'static,
+{
+  generic_parallel_tagged_search(
+    opts,
+    idx,
+    wtr,
+    column,
+    conditions,
+    Vec::new(),
+    jobs,
+    move |value, conds| match_fn(value, conds).then(Vec::new),
+  )
+}
+
+/// Parallel version of `generic_tagged_search`
+pub(crate) fn generic_parallel_tagged_search<F>(
+  opts: CsvOptions<String>,
+  idx: &mut Indexed<File, File>,
+  mut wtr: Writer<BufWriter<File>>,
+  column: String,
+  conditions: Vec<String>,
+  tag_headers: Vec<String>,
+  jobs: usize,
+  match_fn: F,
+) -> Result<String>
+where
+  F: Fn(&str, &[String]) -> Option<Vec<String>> + Send + Sync + 'static,
 {
   let total_data_rows = idx.count() as usize;
   if total_data_rows == 0 {
@@ -315,7 +379,11 @@ where
   let sel = Selection::from_headers(&true_header, &[column.as_str()])?;
   let field_index = sel.first_indices()?;
 
-  wtr.write_byte_record(&true_header)?;
+  let mut output_header = true_header.clone();
+  for h in &tag_headers {
+    output_header.push_field(h.as_bytes());
+  }
+  wtr.write_byte_record(&output_header)?;
 
   // Configure thread count
   let njobs = utils::njobs(Some(jobs));
@@ -389,14 +457,20 @@ where
 
         let mut count = 0;
         for record_result in reader.into_byte_records() {
-          let record = record_result?;
-          if let Some(value) = record.get(field_index) {
-            if let Ok(s) = std::str::from_utf8(value) {
-              if match_fn(s, &conditions) {
-                local_wtr.write_byte_record(&record)?;
-                count += 1;
-              }
+          let mut record = record_result?;
+          let tags = match record.get(field_index) {
+            Some(value) => match std::str::from_utf8(value) {
+              Ok(s) => match_fn(s, &conditions),
+              Err(_) => None,
+            },
+            None => None,
+          };
+          if let Some(tags) = tags {
+            for tag in &tags {
+              record.push_field(tag.as_bytes());
             }
+            local_wtr.write_byte_record(&record)?;
+            count += 1;
           }
         }
         local_wtr.flush()?;
@@ -429,16 +503,42 @@ where
 }
 
 pub(crate) async fn generic_search_chain<E, F>(
+  rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
+  wtr: csv::Writer<BufWriter<File>>,
+  columns: Vec<String>,
+  progress: bool,
+  match_fn: F,
+  emitter: E,
+) -> Result<String, anyhow::Error>
+where
+  E: EventEmitter + Send + Sync + 'static,
+  F: Fn(&[&str]) -> bool + Send + Sync + 'static,
+{
+  generic_tagged_search_chain(
+    rdr,
+    wtr,
+    columns,
+    Vec::new(),
+    progress,
+    move |values| match_fn(values).then(Vec::new),
+    emitter,
+  )
+  .await
+}
+
+/// `generic_search_chain` appending the `tag_headers` columns returned by `match_fn`
+pub(crate) async fn generic_tagged_search_chain<E, F>(
   mut rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
   mut wtr: csv::Writer<BufWriter<File>>,
   columns: Vec<String>,
+  tag_headers: Vec<String>,
   progress: bool,
   match_fn: F,
   emitter: E,
 ) -> Result<String, anyhow::Error>
 where
   E: EventEmitter + Send + Sync + 'static,
-  F: Fn(&[&str]) -> bool + Send + Sync + 'static,
+  F: Fn(&[&str]) -> Option<Vec<String>> + Send + Sync + 'static,
 {
   if columns.is_empty() {
     return Err(anyhow::anyhow!("At least one column must be specified"));
@@ -451,7 +551,12 @@ where
   )?;
 
   // 写入 header 到输出
-  wtr.write_record(rdr.headers()?)?;
+  wtr.write_record(
+    rdr
+      .headers()?
+      .iter()
+      .chain(tag_headers.iter().map(|h| h.as_str())),
+  )?;
 
   let rows = Arc::new(AtomicUsize::new(0));
   let match_rows = Arc::new(AtomicUsize::new(0));
@@ -490,8 +595,12 @@ where
         .map(|&idx| record.get(idx).unwrap_or(""))
         .collect();
 
-      if match_fn(&values) {
-        wtr.write_record(&record)?;
+      if let Some(tags) = match_fn(&values) {
+        if tags.is_empty() {
+          wtr.write_record(&record)?;
+        } else {
+          wtr.write_record(record.iter().chain(tags.iter().map(|t| t.as_str())))?;
+        }
         match_rows.fetch_add(1, Ordering::Relaxed);
       }
       rows.fetch_add(1, Ordering::Relaxed);
@@ -0,0 +1,184 @@
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::{
+  cmd::search::{
+    fuzzy::{Fuzzy, FuzzyOptions, Metric},
+    matcher::Matcher,
+  },
+  normalize::TextNorm,
+};
+
+/// A matched part of a cell, `start`/`end` are byte offsets,
+/// `char_start`/`char_end` are char offsets for the frontend
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+  pub char_start: usize,
+  pub char_end: usize,
+}
+
+impl Span {
+  fn new(value: &str, start: usize, end: usize) -> Self {
+    let char_start = value[..start].chars().count();
+    Span {
+      start,
+      end,
+      char_start,
+      char_end: char_start + value[start..end].chars().count(),
+    }
+  }
+}
+
+/// A capture group of a regex search, `name` is the group name or its number
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Capture {
+  pub name: String,
+  pub text: String,
+  pub span: Span,
+}
+
+/// Why a cell matched
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Highlight {
+  pub column: String,
+  pub spans: Vec<Span>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub captures: Vec<Capture>,
+  /// score of a fuzzy match
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub score: Option<f64>,
+}
+
+enum Kind {
+  Pattern(Regex),
+  /// the whole cell, when the normalised cell matches
+  Whole(Matcher),
+  Fuzzy(Fuzzy),
+}
+
+/// Finds the spans of a search mode in a cell.
+/// Only the modes that match a part of the cell are supported: equal, contains,
+/// starts_with, ends_with (and their `_multi` modes), regex and fuzzy.
+pub struct Highlighter {
+  kind: Kind,
+}
+
+impl Highlighter {
+  /// `None` for the modes without spans
+  pub fn new(
+    mode: &str,
+    condition: &str,
+    norm: TextNorm,
+    fuzzy_opts: Option<&FuzzyOptions>,
+  ) -> Result<Option<Self>> {
+    let mode = mode.strip_suffix("_multi").unwrap_or(mode);
+    if mode == "fuzzy" {
+      let opts = fuzzy_opts.cloned().unwrap_or_default();
+      let conditions: Vec<String> = condition.split('|').map(|s| s.to_string()).collect();
+      let fuzzy = Fuzzy::new(
+        Metric::parse(opts.metric.as_deref())?,
+        opts.threshold,
+        &conditions,
+        norm,
+      )?;
+      return Ok(Some(Highlighter {
+        kind: Kind::Fuzzy(fuzzy),
+      }));
+    }
+    if mode == "regex" {
+      let re = RegexBuilder::new(condition)
+        .case_insensitive(norm.case)
+        .build()?;
+      return Ok(Some(Highlighter {
+        kind: Kind::Pattern(re),
+      }));
+    }
+
+    let (prefix, suffix) = match mode {
+      "equal" => ("^(?:", ")$"),
+      "contains" => ("(?:", ")"),
+      "starts_with" => ("^(?:", ")"),
+      "ends_with" => ("(?:", ")$"),
+      _ => return Ok(None),
+    };
+    // the spans of trimmed, collapsed or width folded text don't map back to
+    // the cell, the whole cell is highlighted
+    if !(TextNorm {
+      case: false,
+      ..norm
+    })
+    .is_identity()
+    {
+      return Ok(Some(Highlighter {
+        kind: Kind::Whole(Matcher::new(mode, condition, norm)?),
+      }));
+    }
+
+    let mut conditions: Vec<&str> = condition
+      .split('|')
+      .map(str::trim)
+      .filter(|s| !s.is_empty())
+      .collect();
+    // longest first, so the alternation prefers the longest condition
+    conditions.sort_by_key(|c| std::cmp::Reverse(c.len()));
+    let alternation = conditions
+      .iter()
+      .map(|c| regex::escape(c))
+      .collect::<Vec<_>>()
+      .join("|");
+    let re = RegexBuilder::new(&format!("{prefix}{alternation}{suffix}"))
+      .case_insensitive(norm.case)
+      .build()?;
+    Ok(Some(Highlighter {
+      kind: Kind::Pattern(re),
+    }))
+  }
+
+  /// `None` when nothing in `value` matches
+  pub fn highlight(&self, column: &str, value: &str) -> Option<Highlight> {
+    let mut highlight = Highlight {
+      column: column.to_string(),
+      spans: Vec::new(),
+      captures: Vec::new(),
+      score: None,
+    };
+    match &self.kind {
+      Kind::Whole(matcher) => {
+        if value.is_empty() || !matcher.is_match(value) {
+          return None;
+        }
+        highlight.spans.push(Span::new(value, 0, value.len()));
+      }
+      Kind::Fuzzy(fuzzy) => {
+        highlight.score = Some(fuzzy.best(value)?);
+        highlight.spans.push(Span::new(value, 0, value.len()));
+      }
+      Kind::Pattern(re) => {
+        let names: Vec<Option<&str>> = re.capture_names().collect();
+        for caps in re.captures_iter(value) {
+          let Some(m) = caps.get(0) else { continue };
+          if m.is_empty() {
+            continue;
+          }
+          highlight.spans.push(Span::new(value, m.start(), m.end()));
+          for (i, group) in caps.iter().enumerate().skip(1) {
+            if let Some(g) = group {
+              highlight.captures.push(Capture {
+                name: names[i].map_or_else(|| i.to_string(), |n| n.to_string()),
+                text: g.as_str().to_string(),
+                span: Span::new(value, g.start(), g.end()),
+              });
+            }
+          }
+        }
+        if highlight.spans.is_empty() {
+          return None;
+        }
+      }
+    }
+    Some(highlight)
+  }
+}
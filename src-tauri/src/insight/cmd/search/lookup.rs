@@ -0,0 +1,224 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs::File,
+  io::{BufRead, BufReader, BufWriter, Read},
+  path::Path,
+};
+
+use aho_corasick::{AhoCorasick, Anchored, Input, MatchKind, StartKind};
+use anyhow::{Result, anyhow};
+use calamine::Reader;
+
+use crate::{
+  cmd::search::generic::{generic_parallel_tagged_search, generic_tagged_search},
+  index::Indexed,
+  io::csv::{config::CsvConfigBuilder, options::CsvOptions, selection::Selection},
+  normalize::TextNorm,
+  utils::EventEmitter,
+};
+
+/// Where the search values come from
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct LookupOptions {
+  /// csv, excel or text file with one value per line
+  pub path: String,
+  /// column holding the values, a text file is read line by line when omitted
+  pub column: Option<String>,
+  /// name of the output column with the lookup value that matched
+  pub match_column: Option<String>,
+}
+
+/// Read the lookup values, trimmed, without empty and duplicated values
+pub fn load_values<P: AsRef<Path>>(path: P, column: Option<&str>) -> Result<Vec<String>> {
+  let path = path.as_ref();
+  let column = column.filter(|c| !c.is_empty());
+  let extension = path
+    .extension()
+    .map(|e| e.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+
+  let raw: Vec<String> = match (extension.as_str(), column) {
+    ("xls" | "xlsx" | "xlsm" | "xlsb" | "ods", _) => {
+      let mut workbook = calamine::open_workbook_auto(path)?;
+      let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| anyhow!("No worksheet in {}", path.display()))??;
+      let mut rows = range.rows();
+      let headers = rows.next().ok_or_else(|| anyhow!("Lookup file is empty"))?;
+      let idx = match column {
+        Some(col) => headers
+          .iter()
+          .position(|h| h.to_string() == col)
+          .ok_or_else(|| anyhow!("Column '{col}' not found in lookup file"))?,
+        None => 0,
+      };
+      rows
+        .map(|row| row.get(idx).map(|c| c.to_string()).unwrap_or_default())
+        .collect()
+    }
+    (_, Some(col)) => {
+      let opts = CsvOptions::new(path);
+      let (sep, reader) = opts.skiprows_and_delimiter()?;
+      let mut rdr = CsvConfigBuilder::new()
+        .delimiter(sep)
+        .flexible(true)
+        .build()
+        .build_reader(reader);
+      let sel = Selection::from_headers(rdr.byte_headers()?, &[col][..])?;
+      let idx = sel.first_indices()?;
+      let mut values = Vec::new();
+      for record in rdr.records() {
+        values.push(record?.get(idx).unwrap_or("").to_string());
+      }
+      values
+    }
+    (_, None) => BufReader::new(File::open(path)?)
+      .lines()
+      .collect::<std::io::Result<Vec<_>>>()?,
+  };
+
+  let mut seen = HashSet::new();
+  let values: Vec<String> = raw
+    .into_iter()
+    .map(|v| v.trim().trim_start_matches('\u{feff}').to_string())
+    .filter(|v| !v.is_empty() && seen.insert(v.clone()))
+    .collect();
+
+  if values.is_empty() {
+    return Err(anyhow!("No values found in lookup file"));
+  }
+
+  Ok(values)
+}
+
+enum Table {
+  Set(HashMap<String, usize>),
+  Automaton(AhoCorasick, bool),
+}
+
+/// Lookup values compiled for `equal`, `contains` or `starts_with`
+pub struct Lookup {
+  values: Vec<String>,
+  table: Table,
+  norm: TextNorm,
+}
+
+impl Lookup {
+  pub fn new(values: Vec<String>, mode: &str) -> Result<Self> {
+    Lookup::with_norm(values, mode, TextNorm::default())
+  }
+
+  /// The values and the searched cells are normalised by `norm`,
+  /// `find` still returns the value as written in the lookup file
+  pub fn with_norm(values: Vec<String>, mode: &str, norm: TextNorm) -> Result<Self> {
+    let keys: Vec<String> = values.iter().map(|v| norm.apply(v).into_owned()).collect();
+    let table = match mode {
+      "equal" => {
+        let mut set = HashMap::with_capacity(keys.len());
+        for (i, key) in keys.into_iter().enumerate() {
+          set.entry(key).or_insert(i);
+        }
+        Table::Set(set)
+      }
+      "contains" | "starts_with" => {
+        let anchored = mode == "starts_with";
+        let ac = AhoCorasick::builder()
+          .match_kind(MatchKind::LeftmostLongest)
+          .start_kind(if anchored {
+            StartKind::Anchored
+          } else {
+            StartKind::Unanchored
+          })
+          .build(&keys)?;
+        Table::Automaton(ac, anchored)
+      }
+      _ => {
+        return Err(anyhow!(
+          "Lookup file only supports equal, contains and starts_with, not {mode}"
+        ));
+      }
+    };
+
+    Ok(Lookup {
+      values,
+      table,
+      norm,
+    })
+  }
+
+  /// The lookup value matching `value`
+  pub fn find(&self, value: &str) -> Option<&str> {
+    let value = self.norm.apply(value);
+    let idx = match &self.table {
+      Table::Set(set) => set.get(value.as_ref()).copied(),
+      Table::Automaton(ac, anchored) => {
+        let input = Input::new(value.as_ref()).anchored(if *anchored {
+          Anchored::Yes
+        } else {
+          Anchored::No
+        });
+        ac.find(input).map(|m| m.pattern().as_usize())
+      }
+    };
+    idx.map(|i| self.values[i].as_str())
+  }
+}
+
+pub async fn lookup_search<E>(
+  rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
+  wtr: csv::Writer<BufWriter<File>>,
+  opts: CsvOptions<String>,
+  idx: Option<Indexed<File, File>>,
+  column: String,
+  lookup: Lookup,
+  match_column: Option<String>,
+  progress: bool,
+  threads: Option<usize>,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+{
+  let tag_headers: Vec<String> = match_column.into_iter().filter(|c| !c.is_empty()).collect();
+  let tagged = !tag_headers.is_empty();
+  let match_fn = move |value: &str, _: &[String]| {
+    lookup.find(value).map(|m| {
+      if tagged {
+        vec![m.to_string()]
+      } else {
+        Vec::new()
+      }
+    })
+  };
+
+  let jobs = threads.unwrap_or(1);
+  match jobs {
+    1 => {
+      generic_tagged_search(
+        rdr,
+        wtr,
+        column,
+        Vec::new(),
+        tag_headers,
+        progress,
+        match_fn,
+        emitter,
+      )
+      .await
+    }
+    _ => tokio::task::spawn_blocking(move || {
+      generic_parallel_tagged_search(
+        opts,
+        &mut idx.unwrap(),
+        wtr,
+        column,
+        Vec::new(),
+        tag_headers,
+        jobs,
+        match_fn,
+      )
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?,
+  }
+}
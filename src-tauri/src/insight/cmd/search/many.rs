@@ -0,0 +1,470 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs::{self, File},
+  io::BufWriter,
+  path::{Path, PathBuf},
+  time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+use calamine::{Data, Reader};
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use rayon::{
+  ThreadPoolBuilder,
+  iter::{IntoParallelRefIterator, ParallelIterator},
+};
+use serde::Serialize;
+use tauri::AppHandle;
+use tempfile::TempDir;
+
+use crate::{
+  cmd::search::{
+    fuzzy::FuzzyOptions,
+    lookup::{self, Lookup, LookupOptions},
+    perform::{Predicate, cell_predicate},
+  },
+  io::csv::{config::CsvConfigBuilder, options::CsvOptions},
+  normalize::TextNorm,
+  utils::{self, WTR_BUFFER_SIZE},
+};
+
+const EXCEL_EXTENSIONS: &[&str] = &["xls", "xlsx", "xlsm", "xlsb", "ods"];
+const CSV_EXTENSIONS: &[&str] = &["csv", "tsv", "txt"];
+/// Columns added in front of the combined output
+const SOURCE_HEADERS: &[&str] = &["source_file", "sheet", "line"];
+
+/// Matches of one file, or of one sheet of an Excel file
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSummary {
+  pub file: String,
+  pub sheet: String,
+  pub rows: usize,
+  pub matches: usize,
+  pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManySummary {
+  pub output: String,
+  pub matches: usize,
+  pub files: Vec<FileSummary>,
+}
+
+fn extension(path: &Path) -> String {
+  path
+    .extension()
+    .map(|e| e.to_string_lossy().to_lowercase())
+    .unwrap_or_default()
+}
+
+/// `|`-separated files and folders, a folder gives its csv and Excel files
+pub fn collect_inputs(path: &str) -> Result<Vec<PathBuf>> {
+  let mut inputs = Vec::new();
+  for p in path.split('|').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+    let p = PathBuf::from(p);
+    if p.is_dir() {
+      let mut files: Vec<PathBuf> = fs::read_dir(&p)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|f| {
+          let ext = extension(f);
+          let generated = f
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with("search_many"));
+          f.is_file()
+            && !generated
+            && (CSV_EXTENSIONS.contains(&ext.as_str()) || EXCEL_EXTENSIONS.contains(&ext.as_str()))
+        })
+        .collect();
+      files.sort();
+      inputs.extend(files);
+    } else {
+      inputs.push(p);
+    }
+  }
+  if inputs.is_empty() {
+    return Err(anyhow!("No files to search"));
+  }
+  Ok(inputs)
+}
+
+fn cell_to_string(cell: &Data) -> String {
+  match cell {
+    Data::Empty => String::new(),
+    Data::String(s) => s.clone(),
+    Data::Int(i) => i.to_string(),
+    Data::Float(f) => {
+      if f.fract() == 0.0 && f.abs() < i64::MAX as f64 {
+        (*f as i64).to_string()
+      } else {
+        f.to_string()
+      }
+    }
+    Data::DateTime(edt) => edt
+      .as_datetime()
+      .map(|d| d.to_string())
+      .unwrap_or_else(|| edt.to_string()),
+    Data::Bool(b) => b.to_string(),
+    Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+    Data::Error(e) => format!("{e:?}"),
+  }
+}
+
+/// Matched rows of one file or sheet, written to a temporary csv as `sheet, line, fields...`
+struct Part {
+  summary: FileSummary,
+  headers: Vec<String>,
+  path: Option<PathBuf>,
+}
+
+fn failed(file: &str, sheet: &str, err: anyhow::Error) -> Part {
+  Part {
+    summary: FileSummary {
+      file: file.to_string(),
+      sheet: sheet.to_string(),
+      rows: 0,
+      matches: 0,
+      error: Some(err.to_string()),
+    },
+    headers: Vec::new(),
+    path: None,
+  }
+}
+
+/// Headers with the repeated names numbered, `a,a` gives `a,a_2`
+fn unique_headers(headers: &[String]) -> Vec<String> {
+  let mut used = HashSet::new();
+  headers
+    .iter()
+    .map(|h| {
+      let (mut name, mut n) = (h.clone(), 1);
+      while !used.insert(name.clone()) {
+        n += 1;
+        name = format!("{h}_{n}");
+      }
+      name
+    })
+    .collect()
+}
+
+/// Search the rows of one table, `first_line` is the line number of the first row
+fn search_rows<I>(
+  file: &str,
+  sheet: &str,
+  headers: Vec<String>,
+  rows: I,
+  first_line: usize,
+  column: &str,
+  predicate: &Predicate,
+  part_path: PathBuf,
+) -> Result<Part>
+where
+  I: Iterator<Item = Result<StringRecord>>,
+{
+  let idx = headers
+    .iter()
+    .position(|h| h == column)
+    .ok_or_else(|| anyhow!("Column '{column}' not found"))?;
+
+  let mut wtr = WriterBuilder::new().from_path(&part_path)?;
+  let (mut total, mut matches) = (0, 0);
+  for (i, row) in rows.enumerate() {
+    let row = row?;
+    total += 1;
+    if predicate(row.get(idx).unwrap_or("")) {
+      let line = (first_line + i).to_string();
+      wtr.write_record([sheet, line.as_str()].into_iter().chain(row.iter()))?;
+      matches += 1;
+    }
+  }
+  wtr.flush()?;
+
+  Ok(Part {
+    summary: FileSummary {
+      file: file.to_string(),
+      sheet: sheet.to_string(),
+      rows: total,
+      matches,
+      error: None,
+    },
+    headers: unique_headers(&headers),
+    path: Some(part_path),
+  })
+}
+
+fn search_file(
+  path: &Path,
+  n: usize,
+  column: &str,
+  predicate: &Predicate,
+  skiprows: usize,
+  quoting: bool,
+  temp_dir: &Path,
+) -> Vec<Part> {
+  let file = path.to_string_lossy().to_string();
+
+  if EXCEL_EXTENSIONS.contains(&extension(path).as_str()) {
+    let mut workbook = match calamine::open_workbook_auto(path) {
+      Ok(wb) => wb,
+      Err(e) => return vec![failed(&file, "", e.into())],
+    };
+    let sheets = workbook.sheet_names();
+    let mut parts = Vec::with_capacity(sheets.len());
+    for (s, sheet) in sheets.iter().enumerate() {
+      let range = match workbook.worksheet_range(sheet) {
+        Ok(range) => range,
+        Err(e) => {
+          parts.push(failed(&file, sheet, e.into()));
+          continue;
+        }
+      };
+      // calamine drops the empty rows above the data
+      let start = range.start().map_or(0, |(r, _)| r as usize);
+      let mut rows = range.rows().skip(skiprows);
+      let headers: Vec<String> = match rows.next() {
+        Some(row) => row.iter().map(cell_to_string).collect(),
+        None => continue,
+      };
+      // sheets without the searched column are skipped, the file fails only if none has it
+      if !headers.iter().any(|h| h == column) {
+        continue;
+      }
+      let records = rows.map(|row| {
+        Ok::<_, anyhow::Error>(row.iter().map(cell_to_string).collect::<StringRecord>())
+      });
+      let part_path = temp_dir.join(format!("part_{n}_{s}.csv"));
+      parts.push(
+        search_rows(
+          &file,
+          sheet,
+          headers,
+          records,
+          start + skiprows + 2,
+          column,
+          predicate,
+          part_path,
+        )
+        .unwrap_or_else(|e| failed(&file, sheet, e)),
+      );
+    }
+    if parts.is_empty() {
+      parts.push(failed(
+        &file,
+        "",
+        anyhow!("Column '{column}' not found in any sheet"),
+      ));
+    }
+    return parts;
+  }
+
+  let result = (|| {
+    let mut opts = CsvOptions::new(path);
+    opts.set_skiprows(skiprows);
+    let (sep, reader) = opts.skiprows_and_delimiter()?;
+    let mut rdr = CsvConfigBuilder::new()
+      .delimiter(sep)
+      .quoting(quoting)
+      .flexible(true)
+      .build()
+      .build_reader(reader);
+    let headers: Vec<String> = rdr.headers()?.iter().map(|h| h.to_string()).collect();
+    let records = rdr.into_records().map(|r| r.map_err(anyhow::Error::from));
+    search_rows(
+      &file,
+      "",
+      headers,
+      records,
+      skiprows + 2,
+      column,
+      predicate,
+      temp_dir.join(format!("part_{n}.csv")),
+    )
+  })();
+  vec![result.unwrap_or_else(|e| failed(&file, "", e))]
+}
+
+/// Search every file in parallel and write the matches to one csv at `output_path`,
+/// the columns of the files are aligned by name, a repeated name goes to `name_2`, ...
+pub fn search_files(
+  inputs: &[PathBuf],
+  column: &str,
+  predicate: Predicate,
+  skiprows: usize,
+  quoting: bool,
+  threads: Option<usize>,
+  output_path: &Path,
+) -> Result<ManySummary> {
+  let temp_dir = TempDir::new()?;
+  let pool = ThreadPoolBuilder::new()
+    .num_threads(utils::njobs(threads))
+    .build()
+    .map_err(|e| anyhow!("Failed to create thread pool: {}", e))?;
+
+  let indexed: Vec<(usize, &PathBuf)> = inputs.iter().enumerate().collect();
+  let parts: Vec<Part> = pool.install(|| {
+    indexed
+      .par_iter()
+      .flat_map_iter(|(n, path)| {
+        search_file(
+          path,
+          *n,
+          column,
+          &predicate,
+          skiprows,
+          quoting,
+          temp_dir.path(),
+        )
+      })
+      .collect()
+  });
+
+  // union of the headers, in the order they are first seen
+  let mut headers: Vec<String> = Vec::new();
+  let mut positions: HashMap<String, usize> = HashMap::new();
+  for part in parts.iter().filter(|p| p.path.is_some()) {
+    for h in &part.headers {
+      if !positions.contains_key(h) {
+        positions.insert(h.clone(), headers.len());
+        headers.push(h.clone());
+      }
+    }
+  }
+
+  let mut wtr = WriterBuilder::new().from_writer(BufWriter::with_capacity(
+    WTR_BUFFER_SIZE,
+    File::create(output_path)?,
+  ));
+  wtr.write_record(
+    SOURCE_HEADERS
+      .iter()
+      .copied()
+      .chain(headers.iter().map(|h| h.as_str())),
+  )?;
+
+  let mut matches = 0;
+  let mut row = vec![String::new(); headers.len()];
+  for part in &parts {
+    let Some(part_path) = &part.path else {
+      continue;
+    };
+    let targets: Vec<usize> = part.headers.iter().map(|h| positions[h]).collect();
+    let mut rdr = ReaderBuilder::new()
+      .has_headers(false)
+      .flexible(true)
+      .from_path(part_path)?;
+    for record in rdr.records() {
+      let record = record?;
+      row.iter_mut().for_each(|f| f.clear());
+      for (field, &target) in record.iter().skip(2).zip(&targets) {
+        row[target] = field.to_string();
+      }
+      wtr.write_record(
+        [
+          part.summary.file.as_str(),
+          record.get(0).unwrap_or(""),
+          record.get(1).unwrap_or(""),
+        ]
+        .into_iter()
+        .chain(row.iter().map(|f| f.as_str())),
+      )?;
+      matches += 1;
+    }
+  }
+  wtr.flush()?;
+
+  Ok(ManySummary {
+    output: output_path.to_string_lossy().to_string(),
+    matches,
+    files: parts.into_iter().map(|p| p.summary).collect(),
+  })
+}
+
+/// Write the per file summary next to the combined output
+pub fn write_summary(summary: &ManySummary, path: &Path) -> Result<()> {
+  let mut wtr = WriterBuilder::new().from_path(path)?;
+  wtr.write_record(["file", "sheet", "rows", "matches", "error"])?;
+  for f in &summary.files {
+    wtr.write_record([
+      f.file.as_str(),
+      f.sheet.as_str(),
+      &f.rows.to_string(),
+      &f.matches.to_string(),
+      f.error.as_deref().unwrap_or(""),
+    ])?;
+  }
+  Ok(wtr.flush()?)
+}
+
+#[tauri::command]
+pub async fn search_many(
+  path: String,
+  column: String,
+  mode: String,
+  condition: String,
+  quoting: bool,
+  skiprows: usize,
+  threads: usize,
+  lookup: Option<LookupOptions>,
+  norm: Option<TextNorm>,
+  fuzzy: Option<FuzzyOptions>,
+  date_format: Option<String>,
+  _app_handle: AppHandle,
+) -> Result<(String, String), String> {
+  let start_time = Instant::now();
+
+  let run = async {
+    let inputs = collect_inputs(&path)?;
+    let norm = norm.unwrap_or_default();
+    let lookup = match lookup {
+      Some(opts) => Some(Lookup::with_norm(
+        lookup::load_values(&opts.path, opts.column.as_deref())?,
+        &mode,
+        norm,
+      )?),
+      None => None,
+    };
+    let (predicate, _) = cell_predicate(
+      &mode,
+      &condition,
+      lookup,
+      fuzzy,
+      norm,
+      date_format.as_deref(),
+    )?;
+
+    // the combined output goes to the folder searched, or next to the first file
+    let first = PathBuf::from(path.split('|').next().unwrap_or("").trim());
+    let dir = if first.is_dir() {
+      first
+    } else {
+      inputs[0]
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+    };
+    let output_path = dir.join("search_many.csv");
+    let summary_path = dir.join("search_many_summary.csv");
+
+    let summary = tokio::task::spawn_blocking(move || {
+      search_files(
+        &inputs,
+        &column,
+        predicate,
+        skiprows,
+        quoting,
+        Some(threads),
+        &output_path,
+      )
+    })
+    .await??;
+    write_summary(&summary, &summary_path)?;
+    Ok::<_, anyhow::Error>(serde_json::to_string(&summary)?)
+  };
+
+  match run.await {
+    Ok(summary) => {
+      let elapsed_time = start_time.elapsed().as_secs_f64();
+      Ok((summary, format!("{elapsed_time:.2}")))
+    }
+    Err(err) => Err(format!("{err}")),
+  }
+}
@@ -0,0 +1,186 @@
+use std::{
+  fs::File,
+  io::{BufReader, BufWriter, Read},
+};
+
+use anyhow::{Result, anyhow};
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+  cmd::search::generic::{generic_parallel_search, generic_search},
+  index::Indexed,
+  io::csv::options::CsvOptions,
+  normalize::TextNorm,
+  utils::EventEmitter,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+  Equal,
+  NotEqual,
+  Contains,
+  NotContains,
+  StartsWith,
+  NotStartsWith,
+  EndsWith,
+  NotEndsWith,
+  Regex,
+  IsNull,
+  IsNotNull,
+  GreaterThan,
+  GreaterThanEqual,
+  LessThan,
+  LessThanEqual,
+  Between,
+}
+
+fn parse_number(s: &str) -> Result<f64> {
+  s.trim()
+    .parse::<f64>()
+    .map_err(|_| anyhow!("Invalid number: {s}"))
+}
+
+/// A search mode compiled with its conditions, the cell and the conditions
+/// are normalised the same way before they are compared
+pub struct Matcher {
+  mode: Mode,
+  conditions: Vec<String>,
+  regex: Option<Regex>,
+  bounds: (f64, f64),
+  norm: TextNorm,
+}
+
+impl Matcher {
+  /// `condition` is split on `|` for the modes taking a list of values
+  pub fn new(mode: &str, condition: &str, norm: TextNorm) -> Result<Self> {
+    let mode = match mode {
+      "equal" => Mode::Equal,
+      "not_equal" => Mode::NotEqual,
+      "contains" => Mode::Contains,
+      "not_contains" => Mode::NotContains,
+      "starts_with" => Mode::StartsWith,
+      "not_starts_with" => Mode::NotStartsWith,
+      "ends_with" => Mode::EndsWith,
+      "not_ends_with" => Mode::NotEndsWith,
+      "regex" => Mode::Regex,
+      "is_null" => Mode::IsNull,
+      "is_not_null" => Mode::IsNotNull,
+      "gt" => Mode::GreaterThan,
+      "ge" => Mode::GreaterThanEqual,
+      "lt" => Mode::LessThan,
+      "le" => Mode::LessThanEqual,
+      "between" => Mode::Between,
+      _ => return Err(anyhow!("Unsupported search mode: {mode}")),
+    };
+
+    let mut conditions: Vec<String> = Vec::new();
+    for cond in condition.split('|') {
+      let cond = norm.apply(cond.trim()).into_owned();
+      if !conditions.contains(&cond) {
+        conditions.push(cond);
+      }
+    }
+
+    let mut regex = None;
+    let mut bounds = (f64::NEG_INFINITY, f64::INFINITY);
+    match mode {
+      Mode::Regex => {
+        regex = Some(
+          RegexBuilder::new(condition)
+            .case_insensitive(norm.case)
+            .build()?,
+        )
+      }
+      Mode::GreaterThan | Mode::GreaterThanEqual | Mode::LessThan | Mode::LessThanEqual => {
+        let threshold = parse_number(&norm.apply(condition))?;
+        bounds = (threshold, threshold);
+      }
+      Mode::Between => {
+        if conditions.len() != 2 {
+          return Err(anyhow!(
+            "Exactly two values required for between: min and max"
+          ));
+        }
+        let (a, b) = (parse_number(&conditions[0])?, parse_number(&conditions[1])?);
+        bounds = if a <= b { (a, b) } else { (b, a) };
+      }
+      _ => {}
+    }
+
+    Ok(Matcher {
+      mode,
+      conditions,
+      regex,
+      bounds,
+      norm,
+    })
+  }
+
+  /// The `not_*` modes, a search over many columns needs all of them to match
+  pub fn is_negated(&self) -> bool {
+    matches!(
+      self.mode,
+      Mode::NotEqual | Mode::NotContains | Mode::NotStartsWith | Mode::NotEndsWith
+    )
+  }
+
+  pub fn is_match(&self, value: &str) -> bool {
+    let value = self.norm.apply(value);
+    let value = value.as_ref();
+    let conds = &self.conditions;
+    let number = || value.trim().parse::<f64>().ok();
+
+    match self.mode {
+      Mode::Equal => conds.iter().any(|c| value == c),
+      Mode::NotEqual => !conds.iter().any(|c| value == c),
+      Mode::Contains => conds.iter().any(|c| value.contains(c.as_str())),
+      Mode::NotContains => !conds.iter().any(|c| value.contains(c.as_str())),
+      Mode::StartsWith => conds.iter().any(|c| value.starts_with(c.as_str())),
+      Mode::NotStartsWith => !conds.iter().any(|c| value.starts_with(c.as_str())),
+      Mode::EndsWith => conds.iter().any(|c| value.ends_with(c.as_str())),
+      Mode::NotEndsWith => !conds.iter().any(|c| value.ends_with(c.as_str())),
+      Mode::Regex => self.regex.as_ref().is_some_and(|re| re.is_match(value)),
+      Mode::IsNull => value.trim().is_empty(),
+      Mode::IsNotNull => !value.trim().is_empty(),
+      Mode::GreaterThan => number().is_some_and(|v| v > self.bounds.0),
+      Mode::GreaterThanEqual => number().is_some_and(|v| v >= self.bounds.0),
+      Mode::LessThan => number().is_some_and(|v| v < self.bounds.0),
+      Mode::LessThanEqual => number().is_some_and(|v| v <= self.bounds.0),
+      Mode::Between => number().is_some_and(|v| v >= self.bounds.0 && v <= self.bounds.1),
+    }
+  }
+}
+
+pub async fn matcher_search<E>(
+  rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
+  wtr: csv::Writer<BufWriter<File>>,
+  opts: CsvOptions<String>,
+  idx: Option<Indexed<File, File>>,
+  column: String,
+  matcher: Matcher,
+  progress: bool,
+  threads: Option<usize>,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+{
+  let jobs = threads.unwrap_or(1);
+  let match_fn = move |value: &str, _: &[String]| matcher.is_match(value);
+  match jobs {
+    1 => generic_search(rdr, wtr, column, Vec::new(), progress, match_fn, emitter).await,
+    _ => tokio::task::spawn_blocking(move || {
+      generic_parallel_search(
+        opts,
+        &mut idx.unwrap(),
+        wtr,
+        column,
+        Vec::new(),
+        jobs,
+        match_fn,
+      )
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?,
+  }
+}
@@ -1,5 +1,15 @@
+pub mod columns;
+pub mod dates;
+pub mod extract;
 pub mod filters;
 pub mod filters_chain;
 pub mod filters_multi;
+pub mod fuzzy;
 pub mod generic;
+pub mod highlight;
+pub mod lookup;
+pub mod many;
+pub mod matcher;
 pub mod perform;
+pub mod stats;
+pub mod tree;
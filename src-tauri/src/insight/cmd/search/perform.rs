@@ -5,9 +5,20 @@ use smallvec::SmallVec;
 use tauri::AppHandle;
 
 use crate::{
-  cmd::search::{filters, filters_chain, filters_multi},
+  cmd::search::{
+    columns::{self, ColumnSelector},
+    dates::{self, DateMatcher},
+    extract::{self, ExtractOptions, Extractor},
+    filters, filters_chain, filters_multi,
+    fuzzy::{self, Fuzzy, FuzzyOptions, Metric},
+    lookup::{self, Lookup, LookupOptions},
+    matcher::{self, Matcher},
+    stats::{self, StatsOptions},
+    tree::{self, ConditionNode},
+  },
   index::Indexed,
   io::csv::{config::CsvConfigBuilder, options::CsvOptions},
+  normalize::TextNorm,
   utils::EventEmitter,
 };
 
@@ -33,6 +44,8 @@ enum SearchMode {
   LessThan,
   LessThanEqual,
   Between,
+  Fuzzy,
+  Date,
   IrregularRegex,
 }
 
@@ -55,12 +68,50 @@ impl From<&str> for SearchMode {
       "lt" => SearchMode::LessThan,
       "le" => SearchMode::LessThanEqual,
       "between" => SearchMode::Between,
+      "fuzzy" => SearchMode::Fuzzy,
+      "date_before" | "date_after" | "date_between" | "same_month" | "same_quarter"
+      | "same_year" | "weekday" | "weekend" => SearchMode::Date,
       "irregular_regex" => SearchMode::IrregularRegex,
       _ => SearchMode::Equal,
     }
   }
 }
 
+pub type Predicate = Box<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// The search mode as a predicate on one cell, for the searches that don't
+/// go through the per mode functions. The flag is `true` for the negated modes.
+pub(crate) fn cell_predicate(
+  mode: &str,
+  conditions: &str,
+  lookup: Option<Lookup>,
+  fuzzy_opts: Option<FuzzyOptions>,
+  norm: TextNorm,
+  date_format: Option<&str>,
+) -> Result<(Predicate, bool)> {
+  if let Some(lookup) = lookup {
+    return Ok((Box::new(move |v: &str| lookup.find(v).is_some()), false));
+  }
+  if dates::is_date_mode(mode) {
+    let matcher = DateMatcher::new(mode, conditions, date_format)?;
+    return Ok((Box::new(move |v: &str| matcher.is_match(v)), false));
+  }
+  if mode == "fuzzy" {
+    let fuzzy_opts = fuzzy_opts.unwrap_or_default();
+    let fuzzy_conditions: Vec<String> = conditions.split('|').map(|s| s.to_string()).collect();
+    let fuzzy = Fuzzy::new(
+      Metric::parse(fuzzy_opts.metric.as_deref())?,
+      fuzzy_opts.threshold,
+      &fuzzy_conditions,
+      norm,
+    )?;
+    return Ok((Box::new(move |v: &str| fuzzy.best(v).is_some()), false));
+  }
+  let matcher = Matcher::new(mode, conditions, norm)?;
+  let negated = matcher.is_negated();
+  Ok((Box::new(move |v: &str| matcher.is_match(v)), negated))
+}
+
 async fn perform_search<P: AsRef<Path> + Send + Sync + 'static>(
   path: P,
   column: String,
@@ -71,8 +122,54 @@ async fn perform_search<P: AsRef<Path> + Send + Sync + 'static>(
   flexible: bool,
   skiprows: usize,
   threads: Option<usize>,
+  lookup: Option<LookupOptions>,
+  norm: TextNorm,
+  fuzzy_opts: Option<FuzzyOptions>,
+  date_format: Option<String>,
+  stats_opts: Option<StatsOptions>,
+  extract_opts: Option<ExtractOptions>,
   emitter: AppHandle,
 ) -> Result<String> {
+  // `*_multi` modes write one file per typed condition
+  if lookup.is_some() && mode.ends_with("_multi") {
+    return Err(anyhow!(
+      "A lookup file is not supported with {mode}, use equal, contains or starts_with"
+    ));
+  }
+
+  // values from a lookup file replace the conditions
+  let lookup = match lookup {
+    Some(lookup_opts) => {
+      let values = lookup::load_values(&lookup_opts.path, lookup_opts.column.as_deref())?;
+      Some((
+        Lookup::with_norm(values, mode, norm)?,
+        lookup_opts.match_column,
+      ))
+    }
+    None => None,
+  };
+
+  // count only, nothing is written
+  if let Some(stats_opts) = stats_opts {
+    return stats::count_search(
+      path,
+      &column,
+      mode,
+      &conditions,
+      skiprows,
+      quoting,
+      flexible,
+      threads,
+      lookup.map(|(lookup, _)| lookup),
+      norm,
+      fuzzy_opts,
+      date_format.as_deref(),
+      stats_opts,
+      emitter,
+    )
+    .await;
+  }
+
   let multi_conditions = if conditions.contains('|') {
     conditions
       .split('|')
@@ -94,7 +191,32 @@ async fn perform_search<P: AsRef<Path> + Send + Sync + 'static>(
     _ => mode.into(),
   };
 
+  // 多列搜索: `*`, `a|b` 或 `re:<pattern>`
+  let selector = ColumnSelector::parse(&column)?;
+  // `*_multi` modes write one file per condition of a single column
+  if mode.ends_with("_multi") {
+    if !selector.is_single() {
+      return Err(anyhow!("{mode} searches a single column, not `{column}`"));
+    }
+    if extract_opts.is_some() {
+      return Err(anyhow!(
+        "Capture extraction needs the regex or irregular_regex mode"
+      ));
+    }
+  }
+
   match search_mode {
+    SearchMode::EqualMulti(conditions)
+    | SearchMode::StartsWithMulti(conditions)
+    | SearchMode::ContainsMulti(conditions)
+    | SearchMode::EndsWithMulti(conditions)
+      if !norm.is_identity() =>
+    {
+      filters_multi::multi_with_norm(
+        path, column, conditions, mode, norm, skiprows, quoting, progress, emitter,
+      )
+      .await
+    }
     SearchMode::EqualMulti(conditions) => {
       filters_multi::equal_multi(
         path, column, conditions, skiprows, quoting, progress, emitter,
@@ -133,6 +255,35 @@ async fn perform_search<P: AsRef<Path> + Send + Sync + 'static>(
       let rdr = config.build_reader(reader);
       let wtr = config.build_writer(&output_path)?;
 
+      if !selector.is_single() {
+        let total_rows = if progress {
+          opts.idx_count_rows().await?
+        } else {
+          0
+        };
+        emitter.emit_total_rows(total_rows).await?;
+
+        let (match_fn, require_all) = cell_predicate(
+          mode,
+          &conditions,
+          lookup.map(|(lookup, _)| lookup),
+          fuzzy_opts,
+          norm,
+          date_format.as_deref(),
+        )?;
+        return columns::columns_search(
+          rdr,
+          wtr,
+          &selector,
+          match_fn,
+          require_all,
+          None,
+          progress,
+          emitter,
+        )
+        .await;
+      }
+
       let mut idx: Option<Indexed<File, File>> = None;
 
       if let Some(threads) = threads {
@@ -154,6 +305,82 @@ async fn perform_search<P: AsRef<Path> + Send + Sync + 'static>(
         }
       }
 
+      if let Some((lookup, match_column)) = lookup {
+        return lookup::lookup_search(
+          rdr,
+          wtr,
+          opts,
+          idx,
+          column,
+          lookup,
+          match_column,
+          progress,
+          threads,
+          emitter,
+        )
+        .await;
+      }
+
+      if let SearchMode::Fuzzy = search_mode {
+        let fuzzy_opts = fuzzy_opts.unwrap_or_default();
+        let fuzzy = Fuzzy::new(
+          Metric::parse(fuzzy_opts.metric.as_deref())?,
+          fuzzy_opts.threshold,
+          &vec_conditions,
+          norm,
+        )?;
+        return fuzzy::fuzzy_search(
+          rdr,
+          wtr,
+          opts,
+          idx,
+          column,
+          fuzzy,
+          fuzzy_opts.score_column,
+          progress,
+          threads,
+          emitter,
+        )
+        .await;
+      }
+
+      if let SearchMode::Date = search_mode {
+        let matcher = DateMatcher::new(mode, &conditions, date_format.as_deref())?;
+        return dates::date_search(
+          rdr, wtr, opts, idx, column, matcher, progress, threads, emitter,
+        )
+        .await;
+      }
+
+      // capture groups to new columns
+      if let Some(extract_opts) = extract_opts {
+        let extractor = Extractor::new(&conditions, &extract_opts, norm.case)?;
+        return match search_mode {
+          SearchMode::Regex => {
+            extract::extract_search(
+              rdr, wtr, opts, idx, column, extractor, progress, threads, emitter,
+            )
+            .await
+          }
+          SearchMode::IrregularRegex => {
+            let (_, reader) = opts.skiprows_and_delimiter()?;
+            extract::irregular_extract(reader, output_path, extractor).await
+          }
+          _ => Err(anyhow!(
+            "Capture extraction needs the regex or irregular_regex mode"
+          )),
+        };
+      }
+
+      // normalised comparison goes through the compiled matcher
+      if !norm.is_identity() && !matches!(search_mode, SearchMode::IrregularRegex) {
+        let matcher = Matcher::new(mode, &conditions, norm)?;
+        return matcher::matcher_search(
+          rdr, wtr, opts, idx, column, matcher, progress, threads, emitter,
+        )
+        .await;
+      }
+
       match search_mode {
         SearchMode::Equal => {
           filters::equal(
@@ -360,6 +587,12 @@ pub async fn search(
   flexible: bool,
   skiprows: usize,
   threads: usize,
+  lookup: Option<LookupOptions>,
+  norm: Option<TextNorm>,
+  fuzzy: Option<FuzzyOptions>,
+  date_format: Option<String>,
+  stats: Option<StatsOptions>,
+  extract: Option<ExtractOptions>,
   app_handle: AppHandle,
 ) -> Result<(String, String), String> {
   let start_time = Instant::now();
@@ -374,6 +607,12 @@ pub async fn search(
     flexible,
     skiprows,
     Some(threads),
+    lookup,
+    norm.unwrap_or_default(),
+    fuzzy,
+    date_format,
+    stats,
+    extract,
     app_handle,
   )
   .await
@@ -392,6 +631,11 @@ pub struct ColumnConfig {
   pub(crate) column: String,
   pub(crate) mode: String,
   pub(crate) condition: String,
+  /// metric and threshold of the `fuzzy` mode
+  pub(crate) metric: Option<String>,
+  pub(crate) threshold: Option<f64>,
+  /// explicit format of the date modes, e.g. `%d/%m/%Y`
+  pub(crate) date_format: Option<String>,
 }
 
 #[tauri::command]
@@ -403,15 +647,40 @@ pub async fn search_chain(
   quoting: bool,
   flexible: bool,
   skiprows: usize,
+  stats: Option<StatsOptions>,
+  tree: Option<ConditionNode>,
   app_handle: AppHandle,
 ) -> Result<(String, String), String> {
   let start_time = Instant::now();
 
-  match filters_chain::search_with_chain(
-    path, configs, logics, skiprows, quoting, flexible, progress, app_handle,
-  )
-  .await
-  {
+  // a condition tree replaces configs and logics
+  let res = match (tree, stats) {
+    (Some(tree), Some(stats_opts)) => {
+      tree::count_with_tree(
+        path, tree, skiprows, quoting, flexible, stats_opts, app_handle,
+      )
+      .await
+    }
+    (Some(tree), None) => {
+      tree::search_with_tree(
+        path, tree, skiprows, quoting, flexible, progress, app_handle,
+      )
+      .await
+    }
+    (None, Some(stats_opts)) => {
+      filters_chain::count_with_chain(
+        path, configs, logics, skiprows, quoting, flexible, stats_opts, app_handle,
+      )
+      .await
+    }
+    (None, None) => {
+      filters_chain::search_with_chain(
+        path, configs, logics, skiprows, quoting, flexible, progress, app_handle,
+      )
+      .await
+    }
+  };
+  match res {
     Ok(match_rows) => {
       let end_time = Instant::now();
       let elapsed_time = end_time.duration_since(start_time).as_secs_f64();
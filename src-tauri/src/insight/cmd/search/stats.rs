@@ -0,0 +1,394 @@
+use std::{
+  fs::File,
+  io::{BufReader, Cursor, Read},
+  path::Path,
+};
+
+use anyhow::{Result, anyhow};
+use csv::{ReaderBuilder, StringRecord};
+use indexmap::IndexMap;
+use rayon::{
+  ThreadPoolBuilder,
+  iter::{IntoParallelIterator, ParallelIterator},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  cmd::search::{
+    columns::ColumnSelector,
+    fuzzy::FuzzyOptions,
+    highlight::{Highlight, Highlighter},
+    lookup::Lookup,
+    matcher::Matcher,
+    perform::cell_predicate,
+  },
+  io::csv::{config::CsvConfigBuilder, options::CsvOptions, selection::Selection},
+  normalize::TextNorm,
+  utils::{self, EventEmitter, MmapOffsets},
+};
+
+fn default_preview() -> usize {
+  20
+}
+
+/// Count-only search: nothing is written, the counts and a preview are returned
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatsOptions {
+  /// number of matching rows in the preview
+  #[serde(default = "default_preview")]
+  pub preview: usize,
+  /// return the matched spans of the preview rows
+  #[serde(default)]
+  pub highlight: bool,
+}
+
+impl Default for StatsOptions {
+  fn default() -> Self {
+    StatsOptions {
+      preview: default_preview(),
+      highlight: false,
+    }
+  }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SearchStats {
+  pub rows: usize,
+  pub matches: usize,
+  /// rows each condition value (multi modes) or condition (chain) holds for
+  pub counts: IndexMap<String, usize>,
+  /// the first matching rows, keyed by header
+  pub preview: Vec<IndexMap<String, String>>,
+  /// the matched spans of every preview row, when `highlight` is on
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub highlights: Vec<Vec<Highlight>>,
+}
+
+/// Counts of a part of the file, merged in file order
+struct Partial {
+  rows: usize,
+  matches: usize,
+  counts: Vec<usize>,
+  preview: Vec<Vec<String>>,
+}
+
+impl Partial {
+  fn new(labels: usize) -> Self {
+    Partial {
+      rows: 0,
+      matches: 0,
+      counts: vec![0; labels],
+      preview: Vec::new(),
+    }
+  }
+
+  /// `match_fn` gets the values of the selected columns and pushes the index of
+  /// every matched label, it returns the tag values when the row matches
+  fn push<F>(&mut self, record: &StringRecord, indices: &[usize], limit: usize, match_fn: &F)
+  where
+    F: Fn(&[&str], &mut Vec<usize>) -> Option<Vec<String>>,
+  {
+    let values: Vec<&str> = indices
+      .iter()
+      .map(|&i| record.get(i).unwrap_or(""))
+      .collect();
+    let mut hits = Vec::new();
+    self.rows += 1;
+    let tags = match_fn(&values, &mut hits);
+    for i in hits {
+      self.counts[i] += 1;
+    }
+    if let Some(tags) = tags {
+      self.matches += 1;
+      if self.preview.len() < limit {
+        self
+          .preview
+          .push(record.iter().map(|s| s.to_string()).chain(tags).collect());
+      }
+    }
+  }
+}
+
+fn finish(
+  parts: Vec<Partial>,
+  headers: Vec<String>,
+  labels: Vec<String>,
+  limit: usize,
+  highlighters: &[(Vec<String>, Highlighter)],
+) -> SearchStats {
+  let mut stats = SearchStats::default();
+  let mut counts = vec![0; labels.len()];
+  for part in parts {
+    stats.rows += part.rows;
+    stats.matches += part.matches;
+    for (total, n) in counts.iter_mut().zip(part.counts) {
+      *total += n;
+    }
+    for row in part.preview {
+      if stats.preview.len() < limit {
+        if !highlighters.is_empty() {
+          stats
+            .highlights
+            .push(highlight_row(&headers, &row, highlighters));
+        }
+        stats
+          .preview
+          .push(headers.iter().cloned().zip(row).collect());
+      }
+    }
+  }
+  stats.counts = labels.into_iter().zip(counts).collect();
+  stats
+}
+
+/// The spans of every highlighted column of a preview row
+fn highlight_row(
+  headers: &[String],
+  row: &[String],
+  highlighters: &[(Vec<String>, Highlighter)],
+) -> Vec<Highlight> {
+  let mut highlights = Vec::new();
+  for (columns, highlighter) in highlighters {
+    for column in columns {
+      let cell = headers
+        .iter()
+        .position(|h| h == column)
+        .and_then(|i| row.get(i));
+      if let Some(highlight) = cell.and_then(|cell| highlighter.highlight(column, cell)) {
+        highlights.push(highlight);
+      }
+    }
+  }
+  highlights
+}
+
+/// Count the matching rows of `rdr` without writing them
+pub(crate) fn stats_search<F>(
+  mut rdr: csv::Reader<BufReader<Box<dyn Read + Send>>>,
+  columns: &[String],
+  labels: Vec<String>,
+  tag_headers: Vec<String>,
+  limit: usize,
+  highlighters: Vec<(Vec<String>, Highlighter)>,
+  match_fn: F,
+) -> Result<SearchStats>
+where
+  F: Fn(&[&str], &mut Vec<usize>) -> Option<Vec<String>>,
+{
+  let columns: Vec<&str> = columns.iter().map(|s| s.as_str()).collect();
+  let sel = Selection::from_headers(rdr.byte_headers()?, &columns)?;
+  let headers: Vec<String> = rdr
+    .headers()?
+    .iter()
+    .map(|h| h.to_string())
+    .chain(tag_headers)
+    .collect();
+
+  let mut part = Partial::new(labels.len());
+  let mut record = StringRecord::new();
+  while rdr.read_record(&mut record)? {
+    part.push(&record, sel.get_indices(), limit, &match_fn);
+  }
+
+  Ok(finish(vec![part], headers, labels, limit, &highlighters))
+}
+
+/// Parallel version of `stats_search` over the rows of the `.idx` file
+pub(crate) fn stats_parallel_search<F>(
+  opts: &CsvOptions<String>,
+  columns: &[String],
+  labels: Vec<String>,
+  tag_headers: Vec<String>,
+  limit: usize,
+  highlighters: Vec<(Vec<String>, Highlighter)>,
+  jobs: usize,
+  match_fn: F,
+) -> Result<SearchStats>
+where
+  F: Fn(&[&str], &mut Vec<usize>) -> Option<Vec<String>> + Send + Sync,
+{
+  let offsets = MmapOffsets::from_file(opts.idx_path())?;
+  let sep = opts.get_delimiter()?;
+  let csv_file = File::open(opts.file_path()?)?;
+  let csv_mmap = unsafe { memmap2::Mmap::map(&csv_file)? };
+  let file_size = csv_mmap.len();
+  if offsets.len() == 0 {
+    return Err(anyhow!("Empty index, create index first"));
+  }
+
+  let range_end = |i: usize| match i < offsets.len() {
+    true => offsets.get(i) as usize,
+    false => file_size,
+  };
+
+  let header_slice = &csv_mmap[offsets.get(0) as usize..range_end(1)];
+  let raw_header = ReaderBuilder::new()
+    .has_headers(false)
+    .delimiter(sep)
+    .from_reader(Cursor::new(header_slice))
+    .byte_records()
+    .next()
+    .ok_or_else(|| anyhow!("Failed to parse header"))??;
+  let true_header = utils::clean_header(&raw_header);
+  let columns: Vec<&str> = columns.iter().map(|s| s.as_str()).collect();
+  let sel = Selection::from_headers(&true_header, &columns)?;
+  let headers: Vec<String> = true_header
+    .iter()
+    .map(|h| String::from_utf8_lossy(h).into_owned())
+    .chain(tag_headers)
+    .collect();
+
+  // data rows start at index 1
+  let data_rows = offsets.len() - 1;
+  let njobs = utils::njobs(Some(jobs));
+  let chunk = data_rows.div_ceil(njobs * 4).max(1);
+  let ranges: Vec<(usize, usize)> = (1..=data_rows)
+    .step_by(chunk)
+    .map(|start| (start, (start + chunk).min(data_rows + 1)))
+    .collect();
+
+  let pool = ThreadPoolBuilder::new()
+    .num_threads(njobs)
+    .build()
+    .map_err(|e| anyhow!("Failed to create thread pool: {}", e))?;
+
+  let parts = pool.install(|| {
+    ranges
+      .into_par_iter()
+      .map(|(start, end)| -> Result<Partial> {
+        let slice = &csv_mmap[offsets.get(start) as usize..range_end(end)];
+        let mut rdr = ReaderBuilder::new()
+          .has_headers(false)
+          .flexible(true)
+          .delimiter(sep)
+          .from_reader(Cursor::new(slice));
+        let mut part = Partial::new(labels.len());
+        for result in rdr.byte_records() {
+          let record = StringRecord::from_byte_record_lossy(result?);
+          part.push(&record, sel.get_indices(), limit, &match_fn);
+        }
+        Ok(part)
+      })
+      .collect::<Result<Vec<_>>>()
+  })?;
+
+  Ok(finish(parts, headers, labels, limit, &highlighters))
+}
+
+/// Count-only `search`: the matches of every condition value for the `*_multi` modes,
+/// the rows of any (or for the negated modes every) selected column otherwise.
+/// Returns the stats as json, the `.idx` file is used for a parallel count when it exists.
+pub async fn count_search<E, P>(
+  path: P,
+  column: &str,
+  mode: &str,
+  conditions: &str,
+  skiprows: usize,
+  quoting: bool,
+  flexible: bool,
+  threads: Option<usize>,
+  lookup: Option<Lookup>,
+  norm: TextNorm,
+  fuzzy_opts: Option<FuzzyOptions>,
+  date_format: Option<&str>,
+  stats_opts: StatsOptions,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+  P: AsRef<Path> + Send + Sync,
+{
+  let mut opts = CsvOptions::new(path.as_ref().to_string_lossy().to_string());
+  opts.set_skiprows(skiprows);
+  let (sep, reader) = opts.skiprows_and_delimiter()?;
+  let config = CsvConfigBuilder::new()
+    .flexible(flexible)
+    .delimiter(sep)
+    .quoting(quoting)
+    .build();
+  let mut rdr = config.build_reader(reader);
+  let columns = ColumnSelector::parse(column)?.resolve(rdr.headers()?)?;
+
+  let highlighter = match stats_opts.highlight && lookup.is_none() {
+    true => Highlighter::new(mode, conditions, norm, fuzzy_opts.as_ref())?,
+    false => None,
+  };
+  let highlighters: Vec<_> = highlighter
+    .map(|h| (columns.clone(), h))
+    .into_iter()
+    .collect();
+
+  type RowFn = Box<dyn Fn(&[&str], &mut Vec<usize>) -> Option<Vec<String>> + Send + Sync>;
+  let (labels, match_fn): (Vec<String>, RowFn) = match mode.strip_suffix("_multi") {
+    Some(base) if lookup.is_none() => {
+      let mut labels: Vec<String> = Vec::new();
+      for cond in conditions.split('|').map(str::trim) {
+        if !labels.iter().any(|l| l == cond) {
+          labels.push(cond.to_string());
+        }
+      }
+      let matchers = labels
+        .iter()
+        .map(|cond| Matcher::new(base, cond, norm))
+        .collect::<Result<Vec<_>>>()?;
+      let match_fn = move |values: &[&str], held: &mut Vec<usize>| {
+        held
+          .extend((0..matchers.len()).filter(|&i| values.iter().any(|v| matchers[i].is_match(v))));
+        (!held.is_empty()).then(Vec::new)
+      };
+      (labels, Box::new(match_fn))
+    }
+    _ if mode == "irregular_regex" => {
+      return Err(anyhow!("irregular_regex does not support count only"));
+    }
+    _ => {
+      let (predicate, require_all) =
+        cell_predicate(mode, conditions, lookup, fuzzy_opts, norm, date_format)?;
+      let match_fn = move |values: &[&str], _: &mut Vec<usize>| {
+        let hit = match require_all {
+          true => values.iter().all(|v| predicate(v)),
+          false => values.iter().any(|v| predicate(v)),
+        };
+        hit.then(Vec::new)
+      };
+      (Vec::new(), Box::new(match_fn))
+    }
+  };
+
+  let limit = stats_opts.preview;
+  let search_stats = match opts.indexed()? {
+    Some(_) => {
+      tokio::task::spawn_blocking(move || {
+        stats_parallel_search(
+          &opts,
+          &columns,
+          labels,
+          Vec::new(),
+          limit,
+          highlighters,
+          threads.unwrap_or(0),
+          match_fn,
+        )
+      })
+      .await??
+    }
+    None => {
+      tokio::task::spawn_blocking(move || {
+        stats_search(
+          rdr,
+          &columns,
+          labels,
+          Vec::new(),
+          limit,
+          highlighters,
+          match_fn,
+        )
+      })
+      .await??
+    }
+  };
+  emitter.emit_total_rows(search_stats.rows).await?;
+  emitter.emit_update_rows(search_stats.rows).await?;
+
+  Ok(serde_json::to_string(&search_stats)?)
+}
@@ -0,0 +1,307 @@
+use std::{ops::Range, path::Path};
+
+use anyhow::{Result, anyhow};
+use csv::StringRecord;
+use serde::{Deserialize, Deserializer, de::Error as _};
+use serde_json::Value;
+
+use crate::{
+  cmd::search::{
+    columns::ColumnSelector,
+    fuzzy::FuzzyOptions,
+    generic,
+    perform::{ColumnConfig, Predicate, cell_predicate},
+    stats::{self, StatsOptions},
+  },
+  io::csv::{config::CsvConfigBuilder, options::CsvOptions},
+  normalize::TextNorm,
+  utils::EventEmitter,
+};
+
+/// A search chain as a tree:
+/// `{"and": [..]}`, `{"or": [..]}`, `{"not": {..}}` or a condition
+/// `{"column": "age", "mode": "between", "condition": "18|30"}`
+pub enum ConditionNode {
+  And { and: Vec<ConditionNode> },
+  Or { or: Vec<ConditionNode> },
+  Not { not: Box<ConditionNode> },
+  Leaf(ColumnConfig),
+}
+
+/// Keys of a condition node
+const LEAF_KEYS: &[&str] = &[
+  "column",
+  "mode",
+  "condition",
+  "metric",
+  "threshold",
+  "date_format",
+];
+
+impl ConditionNode {
+  /// Check the structure of `value` node by node, errors start with the path of the bad node
+  fn parse(value: Value, path: &str) -> Result<ConditionNode> {
+    let Value::Object(mut map) = value else {
+      return Err(anyhow!("{path}: expected an object"));
+    };
+
+    if let Some(key) = ["and", "or", "not"]
+      .into_iter()
+      .find(|k| map.contains_key(*k))
+    {
+      if map.len() > 1 {
+        return Err(anyhow!("{path}: '{key}' can not have other keys"));
+      }
+      let inner = map.remove(key).unwrap_or_default();
+      return match (key, inner) {
+        ("not", inner) => Ok(ConditionNode::Not {
+          not: Box::new(ConditionNode::parse(inner, &format!("{path}.not"))?),
+        }),
+        (key, Value::Array(nodes)) => {
+          let nodes = nodes
+            .into_iter()
+            .enumerate()
+            .map(|(i, n)| ConditionNode::parse(n, &format!("{path}.{key}[{i}]")))
+            .collect::<Result<Vec<_>>>()?;
+          Ok(match key {
+            "and" => ConditionNode::And { and: nodes },
+            _ => ConditionNode::Or { or: nodes },
+          })
+        }
+        (key, _) => Err(anyhow!("{path}: '{key}' expects a list of nodes")),
+      };
+    }
+
+    if let Some(key) = map.keys().find(|k| !LEAF_KEYS.contains(&k.as_str())) {
+      return Err(anyhow!(
+        "{path}: unknown key '{key}', expected and, or, not or a condition"
+      ));
+    }
+    serde_json::from_value(Value::Object(map))
+      .map(ConditionNode::Leaf)
+      .map_err(|e| anyhow!("{path}: {e}"))
+  }
+}
+
+impl<'de> Deserialize<'de> for ConditionNode {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    ConditionNode::parse(value, "root").map_err(D::Error::custom)
+  }
+}
+
+enum Compiled {
+  And(Vec<Compiled>),
+  Or(Vec<Compiled>),
+  Not(Box<Compiled>),
+  Leaf {
+    /// the columns of the condition in the selected values
+    range: Range<usize>,
+    predicate: Predicate,
+    /// negated modes need every column to match
+    require_all: bool,
+  },
+}
+
+impl Compiled {
+  fn eval(&self, values: &[&str]) -> bool {
+    match self {
+      Compiled::And(nodes) => nodes.iter().all(|n| n.eval(values)),
+      Compiled::Or(nodes) => nodes.iter().any(|n| n.eval(values)),
+      Compiled::Not(node) => !node.eval(values),
+      Compiled::Leaf {
+        range,
+        predicate,
+        require_all,
+      } => {
+        let mut cells = values[range.clone()].iter();
+        match require_all {
+          true => cells.all(|v| predicate(v)),
+          false => cells.any(|v| predicate(v)),
+        }
+      }
+    }
+  }
+}
+
+/// Validate and compile `node`, the columns of the conditions are appended to `columns`.
+/// Errors start with the path of the bad node, e.g. `root.and[1].not: ...`
+fn compile(
+  node: ConditionNode,
+  path: &str,
+  headers: &StringRecord,
+  columns: &mut Vec<String>,
+) -> Result<Compiled> {
+  let group = |nodes: Vec<ConditionNode>, name: &str, columns: &mut Vec<String>| {
+    if nodes.is_empty() {
+      return Err(anyhow!("{path}: empty '{name}' group"));
+    }
+    nodes
+      .into_iter()
+      .enumerate()
+      .map(|(i, n)| compile(n, &format!("{path}.{name}[{i}]"), headers, columns))
+      .collect::<Result<Vec<_>>>()
+  };
+
+  match node {
+    ConditionNode::And { and } => Ok(Compiled::And(group(and, "and", columns)?)),
+    ConditionNode::Or { or } => Ok(Compiled::Or(group(or, "or", columns)?)),
+    ConditionNode::Not { not } => Ok(Compiled::Not(Box::new(compile(
+      *not,
+      &format!("{path}.not"),
+      headers,
+      columns,
+    )?))),
+    ConditionNode::Leaf(cfg) => {
+      let err = |e: anyhow::Error| anyhow!("{path} ({} {}): {e}", cfg.column, cfg.mode);
+      if cfg.mode == "irregular_regex" {
+        return Err(err(anyhow!("irregular_regex can not be used in a chain")));
+      }
+      let names = ColumnSelector::parse(&cfg.column)
+        .and_then(|s| s.resolve(headers))
+        .map_err(err)?;
+      // `*_multi` modes take the same `|` list as their single value modes
+      let mode = cfg.mode.strip_suffix("_multi").unwrap_or(&cfg.mode);
+      let fuzzy_opts = FuzzyOptions {
+        metric: cfg.metric.clone(),
+        threshold: cfg.threshold,
+        ..Default::default()
+      };
+      let (predicate, require_all) = cell_predicate(
+        mode,
+        &cfg.condition,
+        None,
+        Some(fuzzy_opts),
+        TextNorm::default(),
+        cfg.date_format.as_deref(),
+      )
+      .map_err(err)?;
+
+      let range = columns.len()..columns.len() + names.len();
+      columns.extend(names);
+      Ok(Compiled::Leaf {
+        range,
+        predicate,
+        require_all,
+      })
+    }
+  }
+}
+
+/// The selected columns and the row predicate of a condition tree
+fn build_tree(
+  headers: &StringRecord,
+  tree: ConditionNode,
+) -> Result<(
+  Vec<String>,
+  impl Fn(&[&str]) -> bool + Send + Sync + 'static,
+)> {
+  let mut columns = Vec::new();
+  let compiled = compile(tree, "root", headers, &mut columns)?;
+  Ok((columns, move |values: &[&str]| compiled.eval(values)))
+}
+
+/// `search_with_chain` for a condition tree
+pub async fn search_with_tree<E, P>(
+  path: P,
+  tree: ConditionNode,
+  skiprows: usize,
+  quoting: bool,
+  flexible: bool,
+  progress: bool,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+  P: AsRef<Path> + Send + Sync,
+{
+  let mut opts = CsvOptions::new(path.as_ref().to_string_lossy().to_string());
+  opts.set_skiprows(skiprows);
+  let (sep, reader) = opts.skiprows_and_delimiter()?;
+  let output_path = opts.output_path(Some("search"), None)?;
+  let config = CsvConfigBuilder::new()
+    .flexible(flexible)
+    .delimiter(sep)
+    .quoting(quoting)
+    .build();
+  let mut rdr = config.build_reader(reader);
+
+  let headers = rdr.headers()?.clone();
+  let (columns, match_fn) = build_tree(&headers, tree)?;
+  let wtr = config.build_writer(&output_path)?;
+
+  let total_rows = if progress {
+    opts.idx_count_rows().await?
+  } else {
+    0
+  };
+  emitter.emit_total_rows(total_rows).await?;
+
+  generic::generic_search_chain(rdr, wtr, columns, progress, match_fn, emitter).await
+}
+
+/// `count_with_chain` for a condition tree
+pub async fn count_with_tree<E, P>(
+  path: P,
+  tree: ConditionNode,
+  skiprows: usize,
+  quoting: bool,
+  flexible: bool,
+  stats_opts: StatsOptions,
+  emitter: E,
+) -> Result<String>
+where
+  E: EventEmitter + Send + Sync + 'static,
+  P: AsRef<Path> + Send + Sync,
+{
+  let mut opts = CsvOptions::new(path.as_ref().to_string_lossy().to_string());
+  opts.set_skiprows(skiprows);
+  let (sep, reader) = opts.skiprows_and_delimiter()?;
+  let config = CsvConfigBuilder::new()
+    .flexible(flexible)
+    .delimiter(sep)
+    .quoting(quoting)
+    .build();
+  let mut rdr = config.build_reader(reader);
+
+  let headers = rdr.headers()?.clone();
+  let (columns, match_fn) = build_tree(&headers, tree)?;
+  let match_fn = move |values: &[&str], _: &mut Vec<usize>| match_fn(values).then(Vec::new);
+
+  let limit = stats_opts.preview;
+  let search_stats = match opts.indexed()? {
+    Some(_) => {
+      tokio::task::spawn_blocking(move || {
+        stats::stats_parallel_search(
+          &opts,
+          &columns,
+          Vec::new(),
+          Vec::new(),
+          limit,
+          Vec::new(),
+          0,
+          match_fn,
+        )
+      })
+      .await??
+    }
+    None => {
+      tokio::task::spawn_blocking(move || {
+        stats::stats_search(
+          rdr,
+          &columns,
+          Vec::new(),
+          Vec::new(),
+          limit,
+          Vec::new(),
+          match_fn,
+        )
+      })
+      .await??
+    }
+  };
+  emitter.emit_total_rows(search_stats.rows).await?;
+  emitter.emit_update_rows(search_stats.rows).await?;
+
+  Ok(serde_json::to_string(&search_stats)?)
+}
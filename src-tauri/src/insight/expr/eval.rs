@@ -0,0 +1,265 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use anyhow::{Result, anyhow};
+use csv::StringRecord;
+use rust_decimal::{Decimal, MathematicalOps, RoundingStrategy};
+
+use crate::{
+  cmd::datefmt::parse_to_naive_datetime,
+  expr::{
+    Value,
+    parser::{Ast, BinaryOp, Function, UnaryOp},
+  },
+};
+
+fn parse_decimal(s: &str) -> Option<Decimal> {
+  let s = s.trim();
+  Decimal::from_str(s)
+    .or_else(|_| Decimal::from_scientific(s))
+    .ok()
+}
+
+impl Value {
+  fn truthy(&self) -> bool {
+    match self {
+      Value::Null => false,
+      Value::Bool(b) => *b,
+      Value::Num(n) => !n.is_zero(),
+      Value::Str(s) => !s.is_empty(),
+    }
+  }
+
+  /// `None` for null, an error when the value is not a number
+  fn number(&self) -> Result<Option<Decimal>> {
+    match self {
+      Value::Null => Ok(None),
+      Value::Num(n) => Ok(Some(*n)),
+      Value::Str(s) => parse_decimal(s)
+        .map(Some)
+        .ok_or_else(|| anyhow!("`{s}` is not a number")),
+      Value::Bool(b) => Err(anyhow!("`{b}` is not a number")),
+    }
+  }
+
+  fn as_number(&self) -> Option<Decimal> {
+    match self {
+      Value::Num(n) => Some(*n),
+      Value::Str(s) => parse_decimal(s),
+      _ => None,
+    }
+  }
+}
+
+/// Numbers compare as numbers when both sides are numeric, otherwise as text
+fn compare(a: &Value, b: &Value) -> Ordering {
+  match (a, b) {
+    (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+    _ => match (a.as_number(), b.as_number()) {
+      (Some(x), Some(y)) => x.cmp(&y),
+      _ => a.to_string().cmp(&b.to_string()),
+    },
+  }
+}
+
+fn arithmetic(op: BinaryOp, a: Decimal, b: Decimal) -> Result<Decimal> {
+  let result = match op {
+    BinaryOp::Add => a.checked_add(b),
+    BinaryOp::Sub => a.checked_sub(b),
+    BinaryOp::Mul => a.checked_mul(b),
+    BinaryOp::Div | BinaryOp::Rem if b.is_zero() => return Err(anyhow!("division by zero")),
+    BinaryOp::Div => a.checked_div(b),
+    BinaryOp::Rem => a.checked_rem(b),
+    _ => return power(a, b),
+  };
+  result.ok_or_else(|| anyhow!("numeric overflow"))
+}
+
+/// The largest exponent of `^`, any base > 1 overflows long before
+const MAX_EXPONENT: i64 = 1024;
+
+fn power(base: Decimal, exponent: Decimal) -> Result<Decimal> {
+  if !exponent.fract().is_zero() {
+    return Err(anyhow!("the exponent `{exponent}` is not an integer"));
+  }
+  let n = i64::try_from(exponent)
+    .ok()
+    .filter(|n| n.abs() <= MAX_EXPONENT)
+    .ok_or_else(|| anyhow!("the exponent `{exponent}` is too large"))?;
+  if n < 0 && base.is_zero() {
+    return Err(anyhow!("division by zero"));
+  }
+  base
+    .checked_powi(n)
+    .ok_or_else(|| anyhow!("numeric overflow"))
+}
+
+impl Ast {
+  pub(crate) fn eval(&self, record: &StringRecord) -> Result<Value> {
+    match self {
+      Ast::Literal(v) => Ok(v.clone()),
+      Ast::Column(idx) => Ok(match record.get(*idx).unwrap_or("") {
+        "" => Value::Null,
+        s => Value::Str(s.to_string()),
+      }),
+      Ast::Unary(UnaryOp::Not, inner) => Ok(Value::Bool(!inner.eval(record)?.truthy())),
+      Ast::Unary(UnaryOp::Neg, inner) => Ok(match inner.eval(record)?.number()? {
+        Some(n) => Value::Num(-n),
+        None => Value::Null,
+      }),
+      Ast::Binary(BinaryOp::And, a, b) => Ok(Value::Bool(
+        a.eval(record)?.truthy() && b.eval(record)?.truthy(),
+      )),
+      Ast::Binary(BinaryOp::Or, a, b) => Ok(Value::Bool(
+        a.eval(record)?.truthy() || b.eval(record)?.truthy(),
+      )),
+      Ast::Binary(op, a, b) => {
+        let (a, b) = (a.eval(record)?, b.eval(record)?);
+        match op {
+          BinaryOp::Eq | BinaryOp::Ne => {
+            let equal = match (&a, &b) {
+              (Value::Null, Value::Null) => true,
+              (Value::Null, _) | (_, Value::Null) => false,
+              _ => compare(&a, &b) == Ordering::Equal,
+            };
+            Ok(Value::Bool(equal == (*op == BinaryOp::Eq)))
+          }
+          BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            if a == Value::Null || b == Value::Null {
+              return Ok(Value::Null);
+            }
+            let ordering = compare(&a, &b);
+            Ok(Value::Bool(match op {
+              BinaryOp::Lt => ordering.is_lt(),
+              BinaryOp::Le => ordering.is_le(),
+              BinaryOp::Gt => ordering.is_gt(),
+              _ => ordering.is_ge(),
+            }))
+          }
+          _ => match (a.number()?, b.number()?) {
+            (Some(x), Some(y)) => Ok(Value::Num(arithmetic(*op, x, y)?)),
+            // null in arithmetic gives null
+            _ => Ok(Value::Null),
+          },
+        }
+      }
+      Ast::Call(function, args) => call(*function, args, record),
+    }
+  }
+}
+
+fn call(function: Function, args: &[Ast], record: &StringRecord) -> Result<Value> {
+  // `if` and `coalesce` only evaluate the arguments they need
+  match function {
+    Function::If => {
+      return if args[0].eval(record)?.truthy() {
+        args[1].eval(record)
+      } else {
+        args[2].eval(record)
+      };
+    }
+    Function::Coalesce => {
+      for arg in args {
+        let value = arg.eval(record)?;
+        if value != Value::Null {
+          return Ok(value);
+        }
+      }
+      return Ok(Value::Null);
+    }
+    _ => {}
+  }
+
+  let values = args
+    .iter()
+    .map(|arg| arg.eval(record))
+    .collect::<Result<Vec<_>>>()?;
+
+  let integer = |v: &Value, what: &str| -> Result<Option<i64>> {
+    v.number()?
+      .map(|n| i64::try_from(n.trunc()).map_err(|_| anyhow!("{what} is out of range")))
+      .transpose()
+  };
+
+  match function {
+    Function::Round => {
+      let places = match values.get(1) {
+        Some(v) => integer(v, "decimal places")?.unwrap_or(0).max(0) as u32,
+        None => 0,
+      };
+      Ok(match values[0].number()? {
+        Some(n) => {
+          Value::Num(n.round_dp_with_strategy(places, RoundingStrategy::MidpointNearestEven))
+        }
+        None => Value::Null,
+      })
+    }
+    Function::Abs => Ok(
+      values[0]
+        .number()?
+        .map_or(Value::Null, |n| Value::Num(n.abs())),
+    ),
+    Function::Substr => {
+      if values[0] == Value::Null {
+        return Ok(Value::Null);
+      }
+      let text = values[0].to_string();
+      let start = integer(&values[1], "start")?.unwrap_or(1).max(1) as usize - 1;
+      let chars = text.chars().skip(start);
+      Ok(Value::Str(match values.get(2) {
+        Some(v) => chars
+          .take(integer(v, "length")?.unwrap_or(0).max(0) as usize)
+          .collect(),
+        None => chars.collect(),
+      }))
+    }
+    Function::DateDiff => {
+      if values[0] == Value::Null || values[1] == Value::Null {
+        return Ok(Value::Null);
+      }
+      let date = |v: &Value| {
+        let s = v.to_string();
+        parse_to_naive_datetime(s.trim()).ok_or_else(|| anyhow!("`{s}` is not a date"))
+      };
+      let delta = date(&values[0])? - date(&values[1])?;
+      let unit = values.get(2).map(|v| v.to_string().to_lowercase());
+      let diff = match unit.as_deref().unwrap_or("days") {
+        "days" | "day" | "d" => delta.num_days(),
+        "weeks" | "week" | "w" => delta.num_weeks(),
+        "hours" | "hour" | "h" => delta.num_hours(),
+        "minutes" | "minute" | "m" => delta.num_minutes(),
+        "seconds" | "second" | "s" => delta.num_seconds(),
+        other => return Err(anyhow!("unknown datediff unit `{other}`")),
+      };
+      Ok(Value::Num(Decimal::from(diff)))
+    }
+    Function::Concat => Ok(Value::Str(values.iter().map(|v| v.to_string()).collect())),
+    Function::Len => Ok(Value::Num(Decimal::from(
+      values[0].to_string().chars().count(),
+    ))),
+    Function::Upper | Function::Lower | Function::Trim => Ok(match &values[0] {
+      Value::Null => Value::Null,
+      v => {
+        let s = v.to_string();
+        Value::Str(match function {
+          Function::Upper => s.to_uppercase(),
+          Function::Lower => s.to_lowercase(),
+          _ => s.trim().to_string(),
+        })
+      }
+    }),
+    Function::Min | Function::Max => {
+      let mut best: Option<Decimal> = None;
+      for value in &values {
+        if let Some(n) = value.number()? {
+          best = Some(match best {
+            None => n,
+            Some(b) if function == Function::Min => b.min(n),
+            Some(b) => b.max(n),
+          });
+        }
+      }
+      Ok(best.map_or(Value::Null, Value::Num))
+    }
+    Function::If | Function::Coalesce => unreachable!(),
+  }
+}
@@ -0,0 +1,101 @@
+//! Expression engine of `calcconv`.
+//!
+//! `{column}` references, decimal arithmetic (`+ - * / % ^`), comparison
+//! (`= != < <= > >=`), logical operators (`and or not`) and the functions
+//! round, abs, if, coalesce, substr, datediff, concat, len, upper, lower, trim, min and max.
+//! Empty cells are null, arithmetic with null gives null.
+
+use std::{
+  fmt,
+  sync::{
+    OnceLock,
+    atomic::{AtomicUsize, Ordering},
+  },
+};
+
+use anyhow::{Result, anyhow};
+use csv::StringRecord;
+use rust_decimal::Decimal;
+
+mod eval;
+mod parser;
+
+/// `calcconv` templates ending with `<UNIT>` or `<CPC>` are left to the cpc calculator
+pub fn is_cpc_template(template: &str) -> bool {
+  let template = template.trim_end();
+  template.ends_with("<UNIT>") || template.ends_with("<CPC>")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Null,
+  Num(Decimal),
+  Str(String),
+  Bool(bool),
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::Null => Ok(()),
+      Value::Num(n) => write!(f, "{}", n.normalize()),
+      Value::Str(s) => write!(f, "{s}"),
+      Value::Bool(b) => write!(f, "{b}"),
+    }
+  }
+}
+
+/// A compiled expression, errors of the rows it fails on are collected instead of written
+pub struct Expression {
+  source: String,
+  ast: parser::Ast,
+  errors: AtomicUsize,
+  first_error: OnceLock<String>,
+}
+
+impl Expression {
+  /// Parse `source` and resolve its column references against `headers`
+  pub fn compile<S: AsRef<str>>(source: &str, headers: &[S]) -> Result<Self> {
+    let ast = parser::parse(source, headers).map_err(|e| anyhow!("expression `{source}`: {e}"))?;
+
+    Ok(Expression {
+      source: source.to_string(),
+      ast,
+      errors: AtomicUsize::new(0),
+      first_error: OnceLock::new(),
+    })
+  }
+
+  pub fn eval(&self, record: &StringRecord) -> Result<Value> {
+    self.ast.eval(record)
+  }
+
+  /// Evaluate into a cell, a row that fails gives an empty cell and is counted
+  pub fn eval_cell(&self, record: &StringRecord) -> String {
+    match self.ast.eval(record) {
+      Ok(value) => value.to_string(),
+      Err(err) => {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        let _ = self.first_error.set(err.to_string());
+        String::new()
+      }
+    }
+  }
+
+  /// Number of rows the expression failed on
+  pub fn errors(&self) -> usize {
+    self.errors.load(Ordering::Relaxed)
+  }
+
+  /// Summary of the failed rows, `None` when every row succeeded
+  pub fn report(&self) -> Option<String> {
+    let errors = self.errors();
+    (errors > 0).then(|| {
+      format!(
+        "expression `{}` failed on {errors} rows, first error: {}",
+        self.source,
+        self.first_error.get().map_or("", |s| s.as_str())
+      )
+    })
+  }
+}
@@ -0,0 +1,431 @@
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use rust_decimal::Decimal;
+
+use crate::expr::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Num(Decimal),
+  Str(String),
+  Column(String),
+  Ident(String),
+  Op(&'static str),
+  LParen,
+  RParen,
+  Comma,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum UnaryOp {
+  Neg,
+  Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BinaryOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+  Pow,
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+  And,
+  Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Function {
+  Round,
+  Abs,
+  If,
+  Coalesce,
+  Substr,
+  DateDiff,
+  Concat,
+  Len,
+  Upper,
+  Lower,
+  Trim,
+  Min,
+  Max,
+}
+
+impl Function {
+  fn from_name(name: &str) -> Option<Self> {
+    match name.to_lowercase().as_str() {
+      "round" => Some(Function::Round),
+      "abs" => Some(Function::Abs),
+      "if" => Some(Function::If),
+      "coalesce" => Some(Function::Coalesce),
+      "substr" => Some(Function::Substr),
+      "datediff" => Some(Function::DateDiff),
+      "concat" => Some(Function::Concat),
+      "len" => Some(Function::Len),
+      "upper" => Some(Function::Upper),
+      "lower" => Some(Function::Lower),
+      "trim" => Some(Function::Trim),
+      "min" => Some(Function::Min),
+      "max" => Some(Function::Max),
+      _ => None,
+    }
+  }
+
+  /// Minimum and maximum number of arguments
+  fn arity(&self) -> (usize, usize) {
+    match self {
+      Function::Round => (1, 2),
+      Function::Abs | Function::Len | Function::Upper | Function::Lower | Function::Trim => (1, 1),
+      Function::If => (3, 3),
+      Function::Substr | Function::DateDiff => (2, 3),
+      Function::Coalesce | Function::Concat | Function::Min | Function::Max => (1, usize::MAX),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Ast {
+  Literal(Value),
+  Column(usize),
+  Unary(UnaryOp, Box<Ast>),
+  Binary(BinaryOp, Box<Ast>, Box<Ast>),
+  Call(Function, Vec<Ast>),
+}
+
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>> {
+  let chars: Vec<char> = src.chars().collect();
+  let mut tokens = Vec::new();
+  let mut i = 0;
+
+  while i < chars.len() {
+    let c = chars[i];
+    let start = i;
+    match c {
+      c if c.is_whitespace() => {
+        i += 1;
+        continue;
+      }
+      '0'..='9' | '.' => {
+        while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+          i += 1;
+        }
+        let text: String = chars[start..i].iter().collect();
+        let num =
+          Decimal::from_str(&text).map_err(|_| anyhow!("invalid number `{text}` at {start}"))?;
+        tokens.push((start, Token::Num(num)));
+        continue;
+      }
+      '\'' | '"' => {
+        let quote = c;
+        let mut text = String::new();
+        i += 1;
+        loop {
+          match chars.get(i) {
+            None => return Err(anyhow!("unterminated string at {start}")),
+            // a doubled quote stands for the quote itself
+            Some(&q) if q == quote && chars.get(i + 1) == Some(&quote) => {
+              text.push(quote);
+              i += 2;
+            }
+            Some(&q) if q == quote => {
+              i += 1;
+              break;
+            }
+            Some(&ch) => {
+              text.push(ch);
+              i += 1;
+            }
+          }
+        }
+        tokens.push((start, Token::Str(text)));
+        continue;
+      }
+      '{' => {
+        let end = chars[i..]
+          .iter()
+          .position(|&ch| ch == '}')
+          .ok_or_else(|| anyhow!("unclosed column reference at {start}"))?;
+        let name: String = chars[i + 1..i + end].iter().collect();
+        tokens.push((start, Token::Column(name)));
+        i += end + 1;
+        continue;
+      }
+      c if c.is_alphabetic() || c == '_' => {
+        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+          i += 1;
+        }
+        let ident: String = chars[start..i].iter().collect();
+        let token = match ident.to_lowercase().as_str() {
+          "and" => Token::Op("and"),
+          "or" => Token::Op("or"),
+          "not" => Token::Op("not"),
+          _ => Token::Ident(ident),
+        };
+        tokens.push((start, token));
+        continue;
+      }
+      '(' => tokens.push((start, Token::LParen)),
+      ')' => tokens.push((start, Token::RParen)),
+      ',' => tokens.push((start, Token::Comma)),
+      _ => {
+        let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        let op = match two.as_str() {
+          "==" => Some("="),
+          "!=" | "<>" => Some("!="),
+          "<=" => Some("<="),
+          ">=" => Some(">="),
+          "&&" => Some("and"),
+          "||" => Some("or"),
+          _ => None,
+        };
+        if let Some(op) = op {
+          tokens.push((start, Token::Op(op)));
+          i += 2;
+          continue;
+        }
+        let op = match c {
+          '+' => "+",
+          '-' => "-",
+          '*' => "*",
+          '/' => "/",
+          '%' => "%",
+          '^' => "^",
+          '=' => "=",
+          '<' => "<",
+          '>' => ">",
+          '!' => "not",
+          _ => return Err(anyhow!("unexpected character `{c}` at {start}")),
+        };
+        tokens.push((start, Token::Op(op)));
+      }
+    }
+    i += 1;
+  }
+
+  Ok(tokens)
+}
+
+struct Parser<'a, S> {
+  tokens: Vec<(usize, Token)>,
+  pos: usize,
+  headers: &'a [S],
+}
+
+impl<'a, S: AsRef<str>> Parser<'a, S> {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.pos).map(|(_, t)| t)
+  }
+
+  fn next(&mut self) -> Option<(usize, Token)> {
+    let token = self.tokens.get(self.pos).cloned();
+    self.pos += 1;
+    token
+  }
+
+  fn eat_op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+    match self.peek() {
+      Some(Token::Op(op)) if ops.contains(op) => {
+        let op = *op;
+        self.pos += 1;
+        Some(op)
+      }
+      _ => None,
+    }
+  }
+
+  fn expect(&mut self, expected: Token, what: &str) -> Result<()> {
+    match self.next() {
+      Some((_, token)) if token == expected => Ok(()),
+      Some((at, _)) => Err(anyhow!("expected {what} at {at}")),
+      None => Err(anyhow!("expected {what} at the end")),
+    }
+  }
+
+  fn binary(op: &str) -> BinaryOp {
+    match op {
+      "+" => BinaryOp::Add,
+      "-" => BinaryOp::Sub,
+      "*" => BinaryOp::Mul,
+      "/" => BinaryOp::Div,
+      "%" => BinaryOp::Rem,
+      "^" => BinaryOp::Pow,
+      "=" => BinaryOp::Eq,
+      "!=" => BinaryOp::Ne,
+      "<" => BinaryOp::Lt,
+      "<=" => BinaryOp::Le,
+      ">" => BinaryOp::Gt,
+      ">=" => BinaryOp::Ge,
+      "and" => BinaryOp::And,
+      _ => BinaryOp::Or,
+    }
+  }
+
+  // or < and < not < comparison < + - < * / % < unary minus < ^
+  fn or(&mut self) -> Result<Ast> {
+    let mut left = self.and()?;
+    while let Some(op) = self.eat_op(&["or"]) {
+      left = Ast::Binary(Self::binary(op), Box::new(left), Box::new(self.and()?));
+    }
+    Ok(left)
+  }
+
+  fn and(&mut self) -> Result<Ast> {
+    let mut left = self.not()?;
+    while let Some(op) = self.eat_op(&["and"]) {
+      left = Ast::Binary(Self::binary(op), Box::new(left), Box::new(self.not()?));
+    }
+    Ok(left)
+  }
+
+  fn not(&mut self) -> Result<Ast> {
+    if self.eat_op(&["not"]).is_some() {
+      return Ok(Ast::Unary(UnaryOp::Not, Box::new(self.not()?)));
+    }
+    self.comparison()
+  }
+
+  fn comparison(&mut self) -> Result<Ast> {
+    let left = self.additive()?;
+    if let Some(op) = self.eat_op(&["=", "!=", "<", "<=", ">", ">="]) {
+      let right = self.additive()?;
+      return Ok(Ast::Binary(
+        Self::binary(op),
+        Box::new(left),
+        Box::new(right),
+      ));
+    }
+    Ok(left)
+  }
+
+  fn additive(&mut self) -> Result<Ast> {
+    let mut left = self.multiplicative()?;
+    while let Some(op) = self.eat_op(&["+", "-"]) {
+      left = Ast::Binary(
+        Self::binary(op),
+        Box::new(left),
+        Box::new(self.multiplicative()?),
+      );
+    }
+    Ok(left)
+  }
+
+  fn multiplicative(&mut self) -> Result<Ast> {
+    let mut left = self.unary()?;
+    while let Some(op) = self.eat_op(&["*", "/", "%"]) {
+      left = Ast::Binary(Self::binary(op), Box::new(left), Box::new(self.unary()?));
+    }
+    Ok(left)
+  }
+
+  fn unary(&mut self) -> Result<Ast> {
+    if self.eat_op(&["-"]).is_some() {
+      return Ok(Ast::Unary(UnaryOp::Neg, Box::new(self.unary()?)));
+    }
+    if self.eat_op(&["+"]).is_some() {
+      return self.unary();
+    }
+    self.power()
+  }
+
+  fn power(&mut self) -> Result<Ast> {
+    let base = self.primary()?;
+    if self.eat_op(&["^"]).is_some() {
+      // right associative, 2^3^2 is 2^9
+      let exponent = self.unary()?;
+      return Ok(Ast::Binary(
+        BinaryOp::Pow,
+        Box::new(base),
+        Box::new(exponent),
+      ));
+    }
+    Ok(base)
+  }
+
+  fn primary(&mut self) -> Result<Ast> {
+    let (at, token) = self
+      .next()
+      .ok_or_else(|| anyhow!("unexpected end of expression"))?;
+    match token {
+      Token::Num(n) => Ok(Ast::Literal(Value::Num(n))),
+      Token::Str(s) => Ok(Ast::Literal(Value::Str(s))),
+      Token::Column(name) => self
+        .headers
+        .iter()
+        .position(|h| h.as_ref() == name)
+        .map(Ast::Column)
+        .ok_or_else(|| anyhow!("unknown column `{name}` at {at}")),
+      Token::LParen => {
+        let inner = self.or()?;
+        self.expect(Token::RParen, "`)`")?;
+        Ok(inner)
+      }
+      Token::Ident(name) => match name.to_lowercase().as_str() {
+        "true" => Ok(Ast::Literal(Value::Bool(true))),
+        "false" => Ok(Ast::Literal(Value::Bool(false))),
+        "null" => Ok(Ast::Literal(Value::Null)),
+        _ => {
+          let function = Function::from_name(&name)
+            .ok_or_else(|| anyhow!("unknown function `{name}` at {at}"))?;
+          self.expect(Token::LParen, "`(`")?;
+          let mut args = Vec::new();
+          if self.peek() != Some(&Token::RParen) {
+            loop {
+              args.push(self.or()?);
+              if self.peek() == Some(&Token::Comma) {
+                self.pos += 1;
+              } else {
+                break;
+              }
+            }
+          }
+          self.expect(Token::RParen, "`)`")?;
+
+          let (min, max) = function.arity();
+          if args.len() < min || args.len() > max {
+            return Err(anyhow!(
+              "function `{name}` at {at} takes {} arguments, got {}",
+              if min == max {
+                min.to_string()
+              } else if max == usize::MAX {
+                format!("at least {min}")
+              } else {
+                format!("{min} to {max}")
+              },
+              args.len()
+            ));
+          }
+          Ok(Ast::Call(function, args))
+        }
+      },
+      Token::Op(op) => Err(anyhow!("unexpected `{op}` at {at}")),
+      Token::RParen => Err(anyhow!("unexpected `)` at {at}")),
+      Token::Comma => Err(anyhow!("unexpected `,` at {at}")),
+    }
+  }
+}
+
+/// Parse `src`, column references are resolved to their index in `headers`
+pub(crate) fn parse<S: AsRef<str>>(src: &str, headers: &[S]) -> Result<Ast> {
+  let tokens = tokenize(src)?;
+  if tokens.is_empty() {
+    return Err(anyhow!("expression is empty"));
+  }
+
+  let mut parser = Parser {
+    tokens,
+    pos: 0,
+    headers,
+  };
+  let ast = parser.or()?;
+  match parser.tokens.get(parser.pos) {
+    None => Ok(ast),
+    Some((at, _)) => Err(anyhow!("unexpected input at {at}")),
+  }
+}
@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use csv::StringRecord;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{flow::filter, regex_oncelock};
+
+/// One `condition → value` rule of a `case` operation
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CaseRule {
+  pub column: String,
+  pub mode: String,
+  #[serde(default)]
+  pub value: Option<String>,
+  pub then: CaseValue,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseValueKind {
+  #[default]
+  Literal,
+  Column,
+  Template,
+}
+
+/// A literal, the value of another column or a `{column}` template like `cat`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CaseValue {
+  #[serde(default)]
+  pub kind: CaseValueKind,
+  pub value: String,
+}
+
+enum Part {
+  Text(String),
+  Field(usize),
+}
+
+enum Value {
+  Literal(String),
+  Field(usize),
+  Template(Vec<Part>),
+}
+
+impl Value {
+  fn compile(value: &CaseValue, headers: &[String]) -> Result<Self> {
+    let position = |name: &str| {
+      headers
+        .iter()
+        .position(|h| h == name)
+        .ok_or_else(|| anyhow!("Column not found: {}", name))
+    };
+
+    match value.kind {
+      CaseValueKind::Literal => Ok(Value::Literal(value.value.clone())),
+      CaseValueKind::Column => Ok(Value::Field(position(&value.value)?)),
+      CaseValueKind::Template => {
+        let re: &'static Regex = regex_oncelock!(r"\{(?P<key>[^{}]+)\}");
+        let mut parts = Vec::new();
+        let mut last = 0;
+        for cap in re.captures_iter(&value.value) {
+          let m = cap.get(0).unwrap();
+          if m.start() > last {
+            parts.push(Part::Text(value.value[last..m.start()].to_string()));
+          }
+          parts.push(Part::Field(position(&cap["key"])?));
+          last = m.end();
+        }
+        if last < value.value.len() {
+          parts.push(Part::Text(value.value[last..].to_string()));
+        }
+        Ok(Value::Template(parts))
+      }
+    }
+  }
+
+  fn render(&self, record: &StringRecord) -> String {
+    match self {
+      Value::Literal(s) => s.clone(),
+      Value::Field(idx) => record.get(*idx).unwrap_or("").to_string(),
+      Value::Template(parts) => parts
+        .iter()
+        .map(|part| match part {
+          Part::Text(s) => s.as_str(),
+          Part::Field(idx) => record.get(*idx).unwrap_or(""),
+        })
+        .collect(),
+    }
+  }
+}
+
+/// Compiled `case` operation, rules are checked in order and the first match wins
+pub struct CaseWhen {
+  rules: Vec<(Box<dyn Fn(&StringRecord) -> bool + Send + Sync>, Value)>,
+  otherwise: Option<Value>,
+  /// Index of the column being overwritten, `None` when a new column is created
+  pub target: Option<usize>,
+}
+
+impl CaseWhen {
+  pub fn new(
+    column: &str,
+    rules: &[CaseRule],
+    otherwise: Option<&CaseValue>,
+    headers: Arc<Vec<String>>,
+  ) -> Result<Self> {
+    if rules.is_empty() {
+      return Err(anyhow!("case operation on '{}' has no rules", column));
+    }
+
+    let mut compiled = Vec::with_capacity(rules.len());
+    for (i, rule) in rules.iter().enumerate() {
+      let condition = filter::from_mode(
+        &rule.mode,
+        &rule.column,
+        rule.value.as_deref().unwrap_or(""),
+        headers.clone(),
+      )
+      .map_err(|e| anyhow!("case rule #{}: {}", i + 1, e))?;
+      let value =
+        Value::compile(&rule.then, &headers).map_err(|e| anyhow!("case rule #{}: {}", i + 1, e))?;
+      compiled.push((condition, value));
+    }
+
+    let otherwise = otherwise
+      .map(|v| Value::compile(v, &headers))
+      .transpose()
+      .map_err(|e| anyhow!("case otherwise: {}", e))?;
+
+    Ok(CaseWhen {
+      rules: compiled,
+      otherwise,
+      target: headers.iter().position(|h| h == column),
+    })
+  }
+
+  /// Value of the first matching rule or `otherwise`,
+  /// `None` when nothing applies (an overwritten column then keeps its value)
+  pub fn evaluate(&self, record: &StringRecord) -> Option<String> {
+    self
+      .rules
+      .iter()
+      .find(|(condition, _)| condition(record))
+      .map(|(_, value)| value)
+      .or(self.otherwise.as_ref())
+      .map(|value| value.render(record))
+  }
+}
@@ -3,6 +3,54 @@ use std::{collections::HashSet, sync::Arc};
 use anyhow::{Result, anyhow};
 use csv::StringRecord;
 
+/// Filter modes understood by `filter` operations
+pub const MODES: &[&str] = &[
+  "equal",
+  "not_equal",
+  "contains",
+  "not_contains",
+  "starts_with",
+  "not_starts_with",
+  "ends_with",
+  "not_ends_with",
+  "gt",
+  "ge",
+  "lt",
+  "le",
+  "between",
+  "is_null",
+  "is_not_null",
+];
+
+/// Build the filter for `mode`, `value` is ignored by is_null and is_not_null
+pub fn from_mode(
+  mode: &str,
+  column: &str,
+  value: &str,
+  headers: Arc<Vec<String>>,
+) -> Result<Box<dyn Fn(&StringRecord) -> bool + Send + Sync>> {
+  let column = Arc::from(column);
+  let value = Arc::from(value);
+  match mode {
+    "equal" => equal(column, value, headers),
+    "not_equal" => not_equal(column, value, headers),
+    "contains" => contains(column, value, headers),
+    "not_contains" => not_contains(column, value, headers),
+    "starts_with" => starts_with(column, value, headers),
+    "not_starts_with" => not_starts_with(column, value, headers),
+    "ends_with" => ends_with(column, value, headers),
+    "not_ends_with" => not_ends_with(column, value, headers),
+    "gt" => gt(column, value, headers),
+    "ge" => ge(column, value, headers),
+    "lt" => lt(column, value, headers),
+    "le" => le(column, value, headers),
+    "between" => between(column, value, headers),
+    "is_null" => is_null(column, headers),
+    "is_not_null" => is_not_null(column, headers),
+    _ => Err(anyhow!("Not supported filter mode: {}", mode)),
+  }
+}
+
 pub fn equal(
   column: Arc<str>,
   value: Arc<str>,
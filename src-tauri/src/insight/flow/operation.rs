@@ -1,13 +1,11 @@
-use std::{collections::HashMap, fs::File, io::BufWriter, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use anyhow::{Result, anyhow};
-use csv::{ReaderBuilder, StringRecord, WriterBuilder};
 
 use crate::flow::filter;
+use crate::flow::output::FlowWriter;
 use crate::flow::str::str_process;
-use crate::flow::utils::{Operation, ProcessContext};
-use crate::io::csv::options::CsvOptions;
-use crate::utils::WTR_BUFFER_SIZE;
+use crate::flow::utils::{FlowDialect, FlowOutput, Operation, ProcessContext};
 
 pub(crate) fn is_pure_rename(ops: &[Operation]) -> Option<HashMap<String, String>> {
   if ops.iter().all(|op| op.op == "rename") {
@@ -58,14 +56,10 @@ pub(crate) fn process_rename_only(
   input_path: String,
   rename_map: HashMap<String, String>,
   output_path: PathBuf,
-  quoting: bool,
-) -> Result<()> {
-  let opts = CsvOptions::new(input_path);
-  let (sep, reader) = opts.skiprows_and_delimiter()?;
-  let mut rdr = ReaderBuilder::new()
-    .delimiter(sep)
-    .quoting(quoting)
-    .from_reader(reader);
+  dialect: &FlowDialect,
+  output: &FlowOutput,
+) -> Result<usize> {
+  let (sep, mut rdr) = dialect.reader(&input_path)?;
 
   let original_headers: Vec<String> = rdr.headers()?.iter().map(|s| s.to_string()).collect();
 
@@ -74,30 +68,26 @@ pub(crate) fn process_rename_only(
     .map(|h| rename_map.get(h).cloned().unwrap_or_else(|| h.clone()))
     .collect();
 
-  let output_file = File::create(output_path)?;
-  let buf_wtr = BufWriter::with_capacity(WTR_BUFFER_SIZE, output_file);
-  let mut wtr = WriterBuilder::new().delimiter(sep).from_writer(buf_wtr);
-  wtr.write_record(&output_headers)?;
+  let mut wtr = FlowWriter::new(output, output_path, sep, &output_headers)?;
 
+  let mut count = 0;
   for result in rdr.records() {
     wtr.write_record(&result?)?;
+    count += 1;
   }
 
-  Ok(wtr.flush()?)
+  wtr.finish()?;
+  Ok(count)
 }
 
 pub(crate) fn process_select_only(
   input_path: String,
   select_cols: Vec<String>,
   output_path: PathBuf,
-  quoting: bool,
-) -> Result<()> {
-  let opts = CsvOptions::new(input_path);
-  let (sep, reader) = opts.skiprows_and_delimiter()?;
-  let mut rdr = ReaderBuilder::new()
-    .delimiter(sep)
-    .quoting(quoting)
-    .from_reader(reader);
+  dialect: &FlowDialect,
+  output: &FlowOutput,
+) -> Result<usize> {
+  let (sep, mut rdr) = dialect.reader(&input_path)?;
 
   let original_headers: Vec<String> = rdr.headers()?.iter().map(|s| s.to_string()).collect();
 
@@ -122,11 +112,9 @@ pub(crate) fn process_select_only(
     .map(|&i| &original_headers[i])
     .collect();
 
-  let output_file = File::create(output_path)?;
-  let buf_wtr = BufWriter::with_capacity(WTR_BUFFER_SIZE, output_file);
-  let mut wtr = WriterBuilder::new().delimiter(sep).from_writer(buf_wtr);
-  wtr.write_record(&output_headers)?;
+  let mut wtr = FlowWriter::new(output, output_path, sep, &output_headers)?;
 
+  let mut count = 0;
   for result in rdr.records() {
     let record = result?;
     let selected_fields: Vec<&str> = selected_indices
@@ -134,23 +122,21 @@ pub(crate) fn process_select_only(
       .map(|&i| record.get(i).unwrap_or(""))
       .collect();
     wtr.write_record(&selected_fields)?;
+    count += 1;
   }
 
-  Ok(wtr.flush()?)
+  wtr.finish()?;
+  Ok(count)
 }
 
 pub(crate) fn process_filter_only(
   input_path: String,
   filter_op: &Operation,
   output_path: PathBuf,
-  quoting: bool,
-) -> Result<()> {
-  let opts = CsvOptions::new(input_path);
-  let (sep, reader) = opts.skiprows_and_delimiter()?;
-  let mut rdr = ReaderBuilder::new()
-    .delimiter(sep)
-    .quoting(quoting)
-    .from_reader(reader);
+  dialect: &FlowDialect,
+  output: &FlowOutput,
+) -> Result<usize> {
+  let (sep, mut rdr) = dialect.reader(&input_path)?;
 
   let original_headers: Vec<String> = rdr.headers()?.iter().map(|s| s.to_string()).collect();
   let headers_arc = Arc::new(original_headers.clone());
@@ -170,52 +156,31 @@ pub(crate) fn process_filter_only(
       .ok_or_else(|| anyhow!("Missing value in filter"))?,
   );
 
-  let filter_fn: Box<dyn Fn(&StringRecord) -> bool + Send + Sync> = match mode {
-    "equal" => filter::equal(Arc::from(col), Arc::from(val), headers_arc)?,
-    "not_equal" => filter::not_equal(Arc::from(col), Arc::from(val), headers_arc)?,
-    "contains" => filter::contains(Arc::from(col), Arc::from(val), headers_arc)?,
-    "not_contains" => filter::not_contains(Arc::from(col), Arc::from(val), headers_arc)?,
-    "starts_with" => filter::starts_with(Arc::from(col), Arc::from(val), headers_arc)?,
-    "not_starts_with" => filter::not_starts_with(Arc::from(col), Arc::from(val), headers_arc)?,
-    "ends_with" => filter::ends_with(Arc::from(col), Arc::from(val), headers_arc)?,
-    "not_ends_with" => filter::not_ends_with(Arc::from(col), Arc::from(val), headers_arc)?,
-    "gt" => filter::gt(Arc::from(col), Arc::from(val), headers_arc)?,
-    "ge" => filter::ge(Arc::from(col), Arc::from(val), headers_arc)?,
-    "lt" => filter::lt(Arc::from(col), Arc::from(val), headers_arc)?,
-    "le" => filter::le(Arc::from(col), Arc::from(val), headers_arc)?,
-    "between" => filter::between(Arc::from(col), Arc::from(val), headers_arc)?,
-    "is_null" => filter::is_null(Arc::from(col), headers_arc)?,
-    "is_not_null" => filter::is_not_null(Arc::from(col), headers_arc)?,
-    _ => return Err(anyhow!("Unsupported filter mode: {}", mode)),
-  };
+  let filter_fn = filter::from_mode(mode, col, val, headers_arc)?;
 
-  let output_file = File::create(output_path)?;
-  let buf_wtr = BufWriter::with_capacity(WTR_BUFFER_SIZE, output_file);
-  let mut wtr = WriterBuilder::new().delimiter(sep).from_writer(buf_wtr);
-  wtr.write_record(&original_headers)?;
+  let mut wtr = FlowWriter::new(output, output_path, sep, &original_headers)?;
 
+  let mut count = 0;
   for result in rdr.records() {
     let record = result?;
     if filter_fn(&record) {
       wtr.write_record(&record)?;
+      count += 1;
     }
   }
 
-  Ok(wtr.flush()?)
+  wtr.finish()?;
+  Ok(count)
 }
 
 pub(crate) fn process_pure_str_fast(
   input_path: String,
   operations: &[Operation],
   output_path: PathBuf,
-  quoting: bool,
-) -> Result<()> {
-  let opts = CsvOptions::new(input_path);
-  let (sep, reader) = opts.skiprows_and_delimiter()?;
-  let mut rdr = ReaderBuilder::new()
-    .delimiter(sep)
-    .quoting(quoting)
-    .from_reader(reader);
+  dialect: &FlowDialect,
+  output: &FlowOutput,
+) -> Result<(usize, Vec<String>)> {
+  let (sep, mut rdr) = dialect.reader(&input_path)?;
 
   let original_headers: Vec<String> = rdr.headers()?.iter().map(|s| s.to_string()).collect();
 
@@ -228,34 +193,14 @@ pub(crate) fn process_pure_str_fast(
   };
 
   for op in operations {
-    if let (Some(col), Some(mode)) = (&op.column, &op.mode) {
-      context.add_str(
-        col,
-        mode,
-        op.comparand.as_deref(),
-        op.replacement.as_deref(),
-      );
-    } else if let Some(mode) = &op.mode {
-      if mode == "cat" || mode == "calcconv" {
-        context.add_str(
-          "",
-          mode,
-          op.comparand.as_deref(),
-          op.replacement.as_deref(),
-        );
-      }
-    }
+    context.add_str_op(op, &original_headers)?;
   }
 
   let dynamic_col_names: Vec<String> = context
     .str_ops
     .iter()
     .filter(|op| op.produces_new_column())
-    .map(|op| match op.mode.as_str() {
-      "cat" => format!("concatenated"),
-      "calcconv" => format!("calculated"),
-      mode => format!("{}_{}", op.column, mode),
-    })
+    .map(|op| op.output_name())
     .collect();
 
   let output_headers: Vec<String> = original_headers
@@ -264,34 +209,30 @@ pub(crate) fn process_pure_str_fast(
     .chain(dynamic_col_names.into_iter())
     .collect();
 
-  let output_file = File::create(output_path)?;
-  let buf_wtr = BufWriter::with_capacity(WTR_BUFFER_SIZE, output_file);
-  let mut wtr = WriterBuilder::new().delimiter(sep).from_writer(buf_wtr);
-  wtr.write_record(&output_headers)?;
+  let mut wtr = FlowWriter::new(output, output_path, sep, &output_headers)?;
 
+  let mut count = 0;
   for result in rdr.records() {
     let record = result?;
     let (row_fields, str_results) = str_process(&record, &context, &original_headers)?;
     let mut output_row: Vec<&str> = row_fields.iter().map(|s| s.as_str()).collect();
     output_row.extend(str_results.iter().map(|s| s.as_str()));
     wtr.write_record(&output_row)?;
+    count += 1;
   }
 
-  Ok(wtr.flush()?)
+  wtr.finish()?;
+  Ok((count, context.error_reports()))
 }
 
 pub(crate) fn process_select_filter(
   input_path: String,
   operations: &[Operation],
   output_path: PathBuf,
-  quoting: bool,
-) -> Result<()> {
-  let opts = CsvOptions::new(input_path);
-  let (sep, reader) = opts.skiprows_and_delimiter()?;
-  let mut rdr = ReaderBuilder::new()
-    .delimiter(sep)
-    .quoting(quoting)
-    .from_reader(reader);
+  dialect: &FlowDialect,
+  output: &FlowOutput,
+) -> Result<usize> {
+  let (sep, mut rdr) = dialect.reader(&input_path)?;
 
   let original_headers: Vec<String> = rdr.headers()?.iter().map(|s| s.to_string()).collect();
   let headers_arc = Arc::new(original_headers.clone());
@@ -340,33 +281,11 @@ pub(crate) fn process_select_filter(
     filter_op.logic.as_deref().unwrap_or("and"),
   );
 
-  let col_arc = Arc::from(col);
-  let val_arc = Arc::from(val);
-
-  let filter_fn: Box<dyn Fn(&StringRecord) -> bool + Send + Sync> = match mode {
-    "equal" => filter::equal(col_arc, val_arc, headers_arc)?,
-    "not_equal" => filter::not_equal(col_arc, val_arc, headers_arc)?,
-    "contains" => filter::contains(col_arc, val_arc, headers_arc)?,
-    "not_contains" => filter::not_contains(col_arc, val_arc, headers_arc)?,
-    "starts_with" => filter::starts_with(col_arc, val_arc, headers_arc)?,
-    "not_starts_with" => filter::not_starts_with(col_arc, val_arc, headers_arc)?,
-    "ends_with" => filter::ends_with(col_arc, val_arc, headers_arc)?,
-    "not_ends_with" => filter::not_ends_with(col_arc, val_arc, headers_arc)?,
-    "gt" => filter::gt(col_arc, val_arc, headers_arc)?,
-    "ge" => filter::ge(col_arc, val_arc, headers_arc)?,
-    "lt" => filter::lt(col_arc, val_arc, headers_arc)?,
-    "le" => filter::le(col_arc, val_arc, headers_arc)?,
-    "between" => filter::between(col_arc, val_arc, headers_arc)?,
-    "is_null" => filter::is_null(col_arc, headers_arc)?,
-    "is_not_null" => filter::is_not_null(col_arc, headers_arc)?,
-    _ => return Err(anyhow!("Unsupported filter mode: {}", mode)),
-  };
+  let filter_fn = filter::from_mode(mode, col, val, headers_arc)?;
 
-  let output_file = File::create(output_path)?;
-  let buf_wtr = BufWriter::with_capacity(WTR_BUFFER_SIZE, output_file);
-  let mut wtr = WriterBuilder::new().delimiter(sep).from_writer(buf_wtr);
-  wtr.write_record(&output_headers)?;
+  let mut wtr = FlowWriter::new(output, output_path, sep, &output_headers)?;
 
+  let mut count = 0;
   for result in rdr.records() {
     let record = result?;
     if filter_fn(&record) {
@@ -375,8 +294,10 @@ pub(crate) fn process_select_filter(
         .map(|&i| record.get(i).unwrap_or(""))
         .collect();
       wtr.write_record(&selected_fields)?;
+      count += 1;
     }
   }
 
-  Ok(wtr.flush()?)
+  wtr.finish()?;
+  Ok(count)
 }
@@ -0,0 +1,287 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs::{self, File},
+  io::{BufReader, BufWriter, Read, Write},
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use anyhow::{Result, anyhow};
+use csv::ReaderBuilder;
+use polars::prelude::{
+  DataFrame, JsonFormat, JsonWriter, LazyCsvReader, LazyFileListReader, ParquetWriter, PlRefPath,
+  SerWriter,
+};
+
+use crate::{
+  flow::utils::FlowOutput,
+  io::excel::xlsx_writer::XlsxWriter,
+  utils::{EXCEL_MAX_ROW, WTR_BUFFER_SIZE},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+  Csv,
+  Xlsx,
+  Parquet,
+  Jsonl,
+}
+
+impl OutputFormat {
+  pub fn parse(format: Option<&str>) -> Result<Self> {
+    match format.unwrap_or("csv") {
+      "" | "csv" => Ok(OutputFormat::Csv),
+      "xlsx" => Ok(OutputFormat::Xlsx),
+      "parquet" => Ok(OutputFormat::Parquet),
+      "jsonl" => Ok(OutputFormat::Jsonl),
+      other => Err(anyhow!("Unsupported output format: {other}")),
+    }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      OutputFormat::Csv => "csv",
+      OutputFormat::Xlsx => "xlsx",
+      OutputFormat::Parquet => "parquet",
+      OutputFormat::Jsonl => "jsonl",
+    }
+  }
+}
+
+/// Characters that are not allowed in file names
+fn sanitize_file_name(value: &str) -> String {
+  let name: String = value
+    .trim()
+    .chars()
+    .map(|c| match c {
+      '/' | '\\' | '|' | ':' | '*' | '?' | '"' | '<' | '>' => '-',
+      c if c.is_control() => '-',
+      _ => c,
+    })
+    .collect();
+  if name.is_empty() {
+    "empty".to_string()
+  } else {
+    name
+  }
+}
+
+/// The most partition files of a flow, every partition keeps its file open
+const MAX_PARTITIONS: usize = 500;
+
+struct PartWriter {
+  path: PathBuf,
+  /// csv written while the flow runs, equal to `path` for csv output
+  csv_path: PathBuf,
+  wtr: csv::Writer<BufWriter<File>>,
+}
+
+/// Writes the rows of a flow as csv, xlsx, parquet or jsonl.
+/// Other formats than csv are written to a temporary csv first and converted in `finish`.
+/// A writer dropped before `finish` succeeds removes the files it created.
+pub struct FlowWriter {
+  output: FlowOutput,
+  format: OutputFormat,
+  sep: u8,
+  output_path: PathBuf,
+  headers: Vec<String>,
+  partition: Option<usize>,
+  parts: HashMap<String, PartWriter>,
+  /// the sanitized file names in use, lowercased as file systems may ignore the case
+  names: HashSet<String>,
+  // keep the partitions in the order they were first seen
+  order: Vec<String>,
+  /// the files completed by `finish`
+  written: Vec<PathBuf>,
+  finished: bool,
+}
+
+impl FlowWriter {
+  pub fn new<T: AsRef<str>>(
+    output: &FlowOutput,
+    output_path: PathBuf,
+    sep: u8,
+    headers: &[T],
+  ) -> Result<Self> {
+    let headers: Vec<String> = headers.iter().map(|h| h.as_ref().to_string()).collect();
+    let partition = match output.partition_by.as_deref() {
+      Some(col) if !col.is_empty() => Some(
+        headers
+          .iter()
+          .position(|h| h == col)
+          .ok_or_else(|| anyhow!("Partition column '{}' not found in output", col))?,
+      ),
+      _ => None,
+    };
+
+    let mut writer = FlowWriter {
+      output: output.clone(),
+      format: OutputFormat::parse(output.format.as_deref())?,
+      sep,
+      output_path,
+      headers,
+      partition,
+      parts: HashMap::new(),
+      names: HashSet::new(),
+      order: Vec::new(),
+      written: Vec::new(),
+      finished: false,
+    };
+    if writer.partition.is_none() {
+      // without partitions the output exists even when no row is written
+      writer.part("")?;
+    }
+
+    Ok(writer)
+  }
+
+  fn part(&mut self, key: &str) -> Result<&mut PartWriter> {
+    if !self.parts.contains_key(key) {
+      let path = match self.partition {
+        None => self.output_path.clone(),
+        Some(idx) => {
+          if self.parts.len() >= MAX_PARTITIONS {
+            return Err(anyhow!(
+              "Partition column '{}' has more than {MAX_PARTITIONS} distinct values",
+              self.headers[idx]
+            ));
+          }
+          // `a/b` and `a:b` are both `a-b`, the second one is `a-b_2`, the same for `ABC` and `abc`
+          let base = sanitize_file_name(key);
+          let mut name = base.clone();
+          let mut n = 1;
+          while !self.names.insert(name.to_lowercase()) {
+            n += 1;
+            name = format!("{base}_{n}");
+          }
+          let parent = self.output_path.parent().unwrap_or(Path::new("."));
+          let stem = self.output_path.file_stem().unwrap_or_default();
+          parent.join(format!(
+            "{}_{}.{}",
+            stem.to_string_lossy(),
+            name,
+            self.format.extension()
+          ))
+        }
+      };
+      let (csv_path, mut wtr) = match self.format {
+        OutputFormat::Csv => (path.clone(), self.output.writer(&path, self.sep)?),
+        _ => {
+          let mut tmp = path.clone().into_os_string();
+          tmp.push(".tmp");
+          let tmp = PathBuf::from(tmp);
+          let wtr = csv::WriterBuilder::new().from_writer(BufWriter::with_capacity(
+            WTR_BUFFER_SIZE,
+            File::create(&tmp)?,
+          ));
+          (tmp, wtr)
+        }
+      };
+      wtr.write_record(&self.headers)?;
+
+      self.order.push(key.to_string());
+      self.parts.insert(
+        key.to_string(),
+        PartWriter {
+          path,
+          csv_path,
+          wtr,
+        },
+      );
+    }
+
+    Ok(self.parts.get_mut(key).unwrap())
+  }
+
+  pub fn write_record<I, T>(&mut self, record: I) -> Result<()>
+  where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+  {
+    match self.partition {
+      None => self.part("")?.wtr.write_record(record)?,
+      Some(idx) => {
+        let fields: Vec<T> = record.into_iter().collect();
+        let key = fields
+          .get(idx)
+          .map(|f| String::from_utf8_lossy(f.as_ref()).to_string())
+          .unwrap_or_default();
+        self.part(&key)?.wtr.write_record(fields)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Flush every output and convert it to the target format, return the files written
+  pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+    for key in std::mem::take(&mut self.order) {
+      let mut part = self.parts.remove(&key).unwrap();
+      // the files of `part` are removed on error too
+      self.written.push(part.path.clone());
+      let flushed = part.wtr.flush().map_err(anyhow::Error::from);
+      drop(part.wtr);
+
+      let converted = match self.format {
+        OutputFormat::Csv => flushed,
+        format => {
+          let converted = flushed.and_then(|_| convert(&part.csv_path, &part.path, format));
+          fs::remove_file(&part.csv_path)?;
+          converted
+        }
+      };
+      converted?;
+    }
+    self.finished = true;
+
+    Ok(std::mem::take(&mut self.written))
+  }
+}
+
+impl Drop for FlowWriter {
+  fn drop(&mut self) {
+    if self.finished {
+      return;
+    }
+    for (_, part) in self.parts.drain() {
+      // close the file before it is removed
+      drop(part.wtr);
+      let _ = fs::remove_file(&part.csv_path);
+      self.written.push(part.path);
+    }
+    for path in &self.written {
+      let _ = fs::remove_file(path);
+    }
+  }
+}
+
+fn convert(csv_path: &Path, path: &Path, format: OutputFormat) -> Result<()> {
+  if format == OutputFormat::Xlsx {
+    let reader: Box<dyn Read + Send> = Box::new(File::open(csv_path)?);
+    let rdr = ReaderBuilder::new().from_reader(BufReader::new(reader));
+    return XlsxWriter::new().write_xlsx(rdr, EXCEL_MAX_ROW, path);
+  }
+
+  // every column stays a string, the same as in the csv output
+  let p: Arc<Path> = Arc::from(csv_path.to_path_buf());
+  let mut df: DataFrame = LazyCsvReader::new(PlRefPath::try_from_path(&p)?)
+    .with_has_header(true)
+    .with_infer_schema_length(Some(0))
+    .finish()?
+    .collect()?;
+
+  let mut wtr = BufWriter::with_capacity(WTR_BUFFER_SIZE, File::create(path)?);
+  match format {
+    OutputFormat::Parquet => {
+      ParquetWriter::new(&mut wtr)
+        .with_row_group_size(Some(768 * 768))
+        .finish(&mut df)?;
+    }
+    _ => {
+      JsonWriter::new(&mut wtr)
+        .with_json_format(JsonFormat::JsonLines)
+        .finish(&mut df)?;
+    }
+  }
+
+  Ok(wtr.flush()?)
+}
@@ -1,48 +1,54 @@
-use std::{collections::HashMap, fs::File, io::BufWriter, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use anyhow::{Result, anyhow};
-use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use csv::StringRecord;
 
+use crate::flow::case::CaseWhen;
 use crate::flow::filter;
 use crate::flow::operation;
+use crate::flow::output::FlowWriter;
 use crate::flow::str::str_process;
-use crate::flow::utils::{ColumnSource, FilterLogic, Operation, ProcessContext};
-use crate::io::csv::options::CsvOptions;
-use crate::utils::WTR_BUFFER_SIZE;
+use crate::flow::utils::{
+  ColumnSource, FilterLogic, FlowDialect, FlowOutput, Operation, ProcessContext,
+};
+use crate::flow::window::{Lookahead, Windows};
 
+/// Run the operations on `input_path`, return the number of rows written
+/// and the summaries of the rows the `calcconv` expressions failed on
 pub async fn process_operations(
   input_path: String,
   operations: &[Operation],
   output_path: PathBuf,
-  quoting: bool,
-) -> Result<()> {
+  dialect: &FlowDialect,
+  output: &FlowOutput,
+) -> Result<(usize, Vec<String>)> {
+  let no_reports = |rows| (rows, Vec::new());
   if let Some(rename_map) = operation::is_pure_rename(operations) {
-    return operation::process_rename_only(input_path, rename_map, output_path, quoting);
+    return operation::process_rename_only(input_path, rename_map, output_path, dialect, output)
+      .map(no_reports);
   }
   if let Some(select_cols) = operation::is_pure_select(operations) {
-    return operation::process_select_only(input_path, select_cols, output_path, quoting);
+    return operation::process_select_only(input_path, select_cols, output_path, dialect, output)
+      .map(no_reports);
   }
   if let Some(filter_op) = operation::is_pure_filter(operations) {
-    return operation::process_filter_only(input_path, filter_op, output_path, quoting);
+    return operation::process_filter_only(input_path, filter_op, output_path, dialect, output)
+      .map(no_reports);
   }
   if operation::is_pure_str(operations) {
-    return operation::process_pure_str_fast(input_path, operations, output_path, quoting);
+    return operation::process_pure_str_fast(input_path, operations, output_path, dialect, output);
   }
   if operation::is_select_and_filter_only(operations) {
-    return operation::process_select_filter(input_path, operations, output_path, quoting);
+    return operation::process_select_filter(input_path, operations, output_path, dialect, output)
+      .map(no_reports);
   }
 
-  let opts = CsvOptions::new(input_path);
-  let (sep, reader) = opts.skiprows_and_delimiter()?;
-
-  let mut rdr = ReaderBuilder::new()
-    .delimiter(sep)
-    .quoting(quoting)
-    .from_reader(reader);
+  let (sep, mut rdr) = dialect.reader(&input_path)?;
 
   let original_headers: Vec<String> = rdr.headers()?.iter().map(|s| s.to_string()).collect();
   let headers_arc = Arc::new(original_headers.clone());
   let mut context = ProcessContext::new();
+  let mut window_ops = Vec::new();
 
   for op in operations {
     match op.op.as_str() {
@@ -66,48 +72,24 @@ pub async fn process_operations(
           .as_deref()
           .ok_or_else(|| anyhow!("Missing value in filter"))?;
         let logic = FilterLogic::from(op.logic.as_deref().unwrap_or("or"));
-        let col_arc = Arc::from(col);
-        let val_arc = Arc::from(val);
-
-        let filter_fn: Box<dyn Fn(&StringRecord) -> bool + Send + Sync> = match mode {
-          "equal" => filter::equal(col_arc, val_arc, headers_arc.clone())?,
-          "not_equal" => filter::not_equal(col_arc, val_arc, headers_arc.clone())?,
-          "contains" => filter::contains(col_arc, val_arc, headers_arc.clone())?,
-          "not_contains" => filter::not_contains(col_arc, val_arc, headers_arc.clone())?,
-          "starts_with" => filter::starts_with(col_arc, val_arc, headers_arc.clone())?,
-          "not_starts_with" => filter::not_starts_with(col_arc, val_arc, headers_arc.clone())?,
-          "ends_with" => filter::ends_with(col_arc, val_arc, headers_arc.clone())?,
-          "not_ends_with" => filter::not_ends_with(col_arc, val_arc, headers_arc.clone())?,
-          "gt" => filter::gt(col_arc, val_arc, headers_arc.clone())?,
-          "ge" => filter::ge(col_arc, val_arc, headers_arc.clone())?,
-          "lt" => filter::lt(col_arc, val_arc, headers_arc.clone())?,
-          "le" => filter::le(col_arc, val_arc, headers_arc.clone())?,
-          "between" => filter::between(col_arc, val_arc, headers_arc.clone())?,
-          "is_null" => filter::is_null(col_arc, headers_arc.clone())?,
-          "is_not_null" => filter::is_not_null(col_arc, headers_arc.clone())?,
-          _ => return Err(anyhow!("Not supported filter mode: {}", mode)),
-        };
+        let filter_fn = filter::from_mode(mode, col, val, headers_arc.clone())?;
 
         context.add_filter(filter_fn, logic);
       }
       "str" => {
-        if let (Some(col), Some(mode)) = (&op.column, &op.mode) {
-          context.add_str(
-            col,
-            mode,
-            op.comparand.as_deref(),
-            op.replacement.as_deref(),
-          );
-        } else if let Some(mode) = &op.mode {
-          if mode == "cat" || mode == "calcconv" {
-            context.add_str(
-              "",
-              mode,
-              op.comparand.as_deref(),
-              op.replacement.as_deref(),
-            );
-          }
-        }
+        context.add_str_op(op, &original_headers)?;
+      }
+      "case" => {
+        let col = op
+          .column
+          .as_deref()
+          .ok_or_else(|| anyhow!("Missing column in case"))?;
+        let rules = op
+          .rules
+          .as_deref()
+          .ok_or_else(|| anyhow!("Missing rules in case"))?;
+        let case = CaseWhen::new(col, rules, op.otherwise.as_ref(), headers_arc.clone())?;
+        context.add_case(col, case);
       }
       "rename" => {
         if let (Some(old_name), Some(new_name)) = (&op.column, &op.value) {
@@ -118,19 +100,20 @@ pub async fn process_operations(
           ));
         }
       }
+      "window" => window_ops.push(op),
       _ => return Err(anyhow!("Not supported operation: {}", op.op)),
     }
   }
 
+  let mut windows = Windows::new(&window_ops, &original_headers)?;
+
+  // window columns come after the ones built by str operations
   let dynamic_col_names: Vec<String> = context
     .str_ops
     .iter()
     .filter(|op| op.produces_new_column())
-    .map(|op| match op.mode.as_str() {
-      "cat" => format!("concatenated"),
-      "calcconv" => format!("calculated"),
-      mode => format!("{}_{}", op.column, mode),
-    })
+    .map(|op| op.output_name())
+    .chain(windows.names().iter().cloned())
     .collect();
 
   let logical_columns: Vec<String> = original_headers
@@ -197,14 +180,21 @@ pub async fn process_operations(
 
   context.output_column_sources = output_column_sources;
 
-  let output_file = File::create(output_path)?;
-  let buf_wtr = BufWriter::with_capacity(WTR_BUFFER_SIZE, output_file);
-  let mut wtr = WriterBuilder::new().delimiter(sep).from_writer(buf_wtr);
-  wtr.write_record(&output_headers)?;
+  let mut wtr = FlowWriter::new(output, output_path, sep, &output_headers)?;
+
+  let records: Box<dyn Iterator<Item = Result<StringRecord>> + '_> = if windows.needs_sort() {
+    let mut records = rdr.records().collect::<csv::Result<Vec<_>>>()?;
+    windows.sort(&mut records);
+    Box::new(records.into_iter().map(Ok))
+  } else {
+    Box::new(rdr.records().map(|r| r.map_err(anyhow::Error::from)))
+  };
+  let mut records = Lookahead::new(records, windows.lookahead());
 
-  for result in rdr.records() {
-    let record = result?;
-    let (row_fields, str_results) = str_process(&record, &context, &original_headers)?;
+  let mut count = 0;
+  while let Some(record) = records.next_record()? {
+    let (row_fields, mut str_results) = str_process(&record, &context, &original_headers)?;
+    str_results.extend(windows.evaluate(&record, records.ahead()));
 
     if context.is_valid(&record) {
       let output_row: Vec<&str> = context
@@ -219,8 +209,10 @@ pub async fn process_operations(
         .collect();
 
       wtr.write_record(&output_row)?;
+      count += 1;
     }
   }
 
-  Ok(wtr.flush()?)
+  wtr.finish()?;
+  Ok((count, context.error_reports()))
 }
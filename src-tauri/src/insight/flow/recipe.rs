@@ -0,0 +1,380 @@
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::flow::{
+  filter,
+  output::OutputFormat,
+  process::process_operations,
+  str,
+  utils::{FlowDialect, FlowOutput, Operation, parse_delimiter},
+  window,
+};
+
+/// Version of the recipe file format written by this build
+pub const RECIPE_VERSION: u32 = 1;
+
+/// File extensions picked up when a folder is given as input
+const INPUT_EXTENSIONS: &[&str] = &["csv", "tsv", "psv", "txt", "dat"];
+
+/// A flow saved as a file: the operations plus how to read and write the csv
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Recipe {
+  pub version: u32,
+  #[serde(default)]
+  pub name: Option<String>,
+  #[serde(default)]
+  pub description: Option<String>,
+  #[serde(default)]
+  pub dialect: FlowDialect,
+  #[serde(default)]
+  pub output: FlowOutput,
+  pub operations: Vec<Operation>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecipeFileResult {
+  pub input: String,
+  pub output: Option<String>,
+  pub rows: usize,
+  pub error: Option<String>,
+  /// the rows the `calcconv` expressions failed on
+  pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RecipeSummary {
+  pub files: usize,
+  pub succeeded: usize,
+  pub failed: usize,
+  pub rows: usize,
+  pub results: Vec<RecipeFileResult>,
+}
+
+impl Recipe {
+  pub fn new(operations: Vec<Operation>) -> Self {
+    Recipe {
+      version: RECIPE_VERSION,
+      name: None,
+      description: None,
+      dialect: FlowDialect::default(),
+      output: FlowOutput::default(),
+      operations,
+    }
+  }
+
+  /// Parse a recipe, a bare array of operations (the frontend flow config)
+  /// is accepted and wrapped into a recipe with default settings
+  pub fn from_json(json: &str) -> Result<Self> {
+    let value: Value = serde_json::from_str(json)?;
+    match value {
+      Value::Array(_) => Ok(Recipe::new(serde_json::from_value(value)?)),
+      Value::Object(ref map) => {
+        let version = map
+          .get("version")
+          .and_then(Value::as_u64)
+          .ok_or(anyhow!("recipe has no version"))?;
+        if version == 0 || version > RECIPE_VERSION as u64 {
+          return Err(anyhow!(
+            "recipe version {version} is not supported (supported: 1..={RECIPE_VERSION})"
+          ));
+        }
+        Ok(serde_json::from_value(value)?)
+      }
+      _ => Err(anyhow!("recipe must be a json object")),
+    }
+  }
+
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let json = fs::read_to_string(&path)
+      .map_err(|e| anyhow!("read recipe {:?} failed: {e}", path.as_ref()))?;
+    Self::from_json(&json)
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    self.validate()?;
+    fs::write(path, serde_json::to_string_pretty(self)?)?;
+    Ok(())
+  }
+
+  /// Every problem found in the recipe, empty when the recipe is valid
+  pub fn problems(&self) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if self.operations.is_empty() {
+      problems.push("recipe has no operations".to_string());
+    }
+    if let Some(d) = self.dialect.delimiter.as_deref().filter(|d| !d.is_empty()) {
+      if let Err(e) = parse_delimiter(d) {
+        problems.push(format!("dialect: {e}"));
+      }
+    }
+    if let Some(d) = self.output.delimiter.as_deref().filter(|d| !d.is_empty()) {
+      if let Err(e) = parse_delimiter(d) {
+        problems.push(format!("output: {e}"));
+      }
+    }
+    if let Some(style) = self.output.quote_style.as_deref() {
+      if !["necessary", "always", "never", "non_numeric"].contains(&style) {
+        problems.push(format!("output: unsupported quote style `{style}`"));
+      }
+    }
+    if let Err(e) = OutputFormat::parse(self.output.format.as_deref()) {
+      problems.push(format!("output: {e}"));
+    }
+
+    for (i, op) in self.operations.iter().enumerate() {
+      let at = format!("operation #{} ({})", i + 1, op.op);
+      let missing = |field: &str| format!("{at}: missing `{field}`");
+      let has = |field: &Option<String>| field.as_deref().is_some_and(|s| !s.is_empty());
+
+      match op.op.as_str() {
+        "select" => {
+          if !has(&op.column) {
+            problems.push(missing("column"));
+          }
+        }
+        "filter" => {
+          if !has(&op.column) {
+            problems.push(missing("column"));
+          }
+          match op.mode.as_deref() {
+            None => problems.push(missing("mode")),
+            Some(mode) if !filter::MODES.contains(&mode) => {
+              problems.push(format!("{at}: unsupported filter mode `{mode}`"))
+            }
+            _ => {}
+          }
+          if op.value.is_none() {
+            problems.push(missing("value"));
+          }
+        }
+        "str" => match op.mode.as_deref() {
+          None => problems.push(missing("mode")),
+          Some(mode) if !str::MODES.contains(&mode) => {
+            problems.push(format!("{at}: unsupported str mode `{mode}`"))
+          }
+          Some(mode) => {
+            if !has(&op.column) && mode != "cat" && mode != "calcconv" {
+              problems.push(missing("column"));
+            }
+            if str::LENGTH_MODES.contains(&mode) {
+              let length = op.replacement.as_deref().unwrap_or("");
+              if length.parse::<usize>().is_err() {
+                problems.push(format!(
+                  "{at}: `replacement` must be a number, got `{length}`"
+                ));
+              }
+            }
+          }
+        },
+        "case" => {
+          if !has(&op.column) {
+            problems.push(missing("column"));
+          }
+          let rules = op.rules.as_deref().unwrap_or_default();
+          if rules.is_empty() {
+            problems.push(missing("rules"));
+          }
+          for (j, rule) in rules.iter().enumerate() {
+            if rule.column.is_empty() {
+              problems.push(format!("{at}: rule #{} is missing `column`", j + 1));
+            }
+            if !filter::MODES.contains(&rule.mode.as_str()) {
+              problems.push(format!(
+                "{at}: rule #{} has unsupported mode `{}`",
+                j + 1,
+                rule.mode
+              ));
+            }
+            let needs_value = rule.mode != "is_null" && rule.mode != "is_not_null";
+            if needs_value && rule.value.is_none() {
+              problems.push(format!("{at}: rule #{} is missing `value`", j + 1));
+            }
+          }
+        }
+        "window" => match op.mode.as_deref() {
+          None => problems.push(missing("mode")),
+          Some(mode) if !window::MODES.contains(&mode) => {
+            problems.push(format!("{at}: unsupported window mode `{mode}`"))
+          }
+          Some(mode) => {
+            if !has(&op.column) && mode != "row_number" {
+              problems.push(missing("column"));
+            }
+            if op.offset == Some(0) {
+              problems.push(format!("{at}: `offset` must be at least 1"));
+            }
+          }
+        },
+        "rename" => {
+          if !has(&op.column) {
+            problems.push(missing("column"));
+          }
+          if !has(&op.value) {
+            problems.push(missing("value"));
+          }
+        }
+        other => problems.push(format!("{at}: unsupported operation `{other}`")),
+      }
+    }
+
+    problems
+  }
+
+  pub fn validate(&self) -> Result<()> {
+    let problems = self.problems();
+    if problems.is_empty() {
+      Ok(())
+    } else {
+      Err(anyhow!("invalid recipe:\n{}", problems.join("\n")))
+    }
+  }
+
+  /// Apply the recipe to every input, one output per input.
+  /// A failing input is recorded in the summary and does not stop the batch.
+  pub async fn run_batch(&self, inputs: &[String]) -> Result<RecipeSummary> {
+    self.validate()?;
+    if inputs.len() > 1 && self.output.path.as_deref().is_some_and(|p| !p.is_empty()) {
+      return Err(anyhow!(
+        "output path can only be used with a single input, use output dir instead"
+      ));
+    }
+
+    let mut summary = RecipeSummary::default();
+    for input in inputs {
+      let output_path = self.output.output_path(input);
+      let result = match output_path {
+        Ok(output_path) => process_operations(
+          input.clone(),
+          &self.operations,
+          output_path.clone(),
+          &self.dialect,
+          &self.output,
+        )
+        .await
+        .map(|(rows, warnings)| (output_path, rows, warnings)),
+        Err(e) => Err(e),
+      };
+
+      summary.files += 1;
+      match result {
+        Ok((output_path, rows, warnings)) => {
+          summary.succeeded += 1;
+          summary.rows += rows;
+          summary.results.push(RecipeFileResult {
+            input: input.clone(),
+            output: Some(output_path.to_string_lossy().to_string()),
+            rows,
+            error: None,
+            warnings,
+          });
+        }
+        Err(err) => {
+          log::warn!("recipe failed on {input}: {err}");
+          summary.failed += 1;
+          summary.results.push(RecipeFileResult {
+            input: input.clone(),
+            output: None,
+            rows: 0,
+            error: Some(err.to_string()),
+            warnings: Vec::new(),
+          });
+        }
+      }
+    }
+
+    Ok(summary)
+  }
+
+  /// Expand `|` separated files and folders into the list of input files.
+  /// Outputs of this recipe found in a folder are skipped.
+  pub fn collect_inputs(&self, inputs: &str) -> Result<Vec<String>> {
+    let suffix = self.output.suffix.as_deref().unwrap_or("flow");
+    // `<stem>.<suffix>.csv` and partitioned `<stem>.<suffix>_<value>.csv`
+    let output_marks = [format!(".{suffix}."), format!(".{suffix}_")];
+    let mut files = Vec::new();
+
+    for entry in inputs.split('|').filter(|s| !s.is_empty()) {
+      let path = PathBuf::from(entry);
+      if !path.is_dir() {
+        files.push(entry.to_string());
+        continue;
+      }
+
+      let mut dir_files: Vec<String> = fs::read_dir(&path)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .filter(|p| {
+          p.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .is_some_and(|ext| INPUT_EXTENSIONS.contains(&ext.as_str()))
+        })
+        .map(|p| p.to_string_lossy().to_string())
+        .filter(|p| {
+          let name = Path::new(p)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+          !output_marks.iter().any(|mark| name.contains(mark.as_str()))
+        })
+        .collect();
+      dir_files.sort();
+      files.extend(dir_files);
+    }
+
+    if files.is_empty() {
+      return Err(anyhow!("No input files found"));
+    }
+
+    Ok(files)
+  }
+}
+
+#[tauri::command]
+pub async fn save_recipe(path: String, recipe: String) -> Result<(), String> {
+  Recipe::from_json(&recipe)
+    .and_then(|r| r.save(&path))
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn load_recipe(path: String) -> Result<String, String> {
+  Recipe::load(&path)
+    .and_then(|r| Ok(serde_json::to_string(&r)?))
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn validate_recipe(recipe: String) -> Result<Vec<String>, String> {
+  Recipe::from_json(&recipe)
+    .map(|r| r.problems())
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn run_recipe(recipe_path: String, inputs: String) -> Result<(String, String), String> {
+  let start_time = Instant::now();
+
+  let run = async {
+    let recipe = Recipe::load(&recipe_path)?;
+    let files = recipe.collect_inputs(&inputs)?;
+    let summary = recipe.run_batch(&files).await?;
+    Ok::<_, anyhow::Error>(serde_json::to_string(&summary)?)
+  };
+
+  match run.await {
+    Ok(summary) => {
+      let end_time = Instant::now();
+      let elapsed_time = end_time.duration_since(start_time).as_secs_f64();
+      Ok((summary, format!("{elapsed_time:.2}")))
+    }
+    Err(err) => Err(format!("{err}")),
+  }
+}
@@ -7,6 +7,49 @@ use regex::Regex;
 
 use crate::{flow::utils::ProcessContext, regex_oncelock};
 
+/// Modes understood by `str` operations
+pub const MODES: &[&str] = &[
+  "fill",
+  "f_fill",
+  "lower",
+  "upper",
+  "trim",
+  "ltrim",
+  "rtrim",
+  "squeeze",
+  "strip",
+  "normalize",
+  "replace",
+  "regex_replace",
+  "round",
+  "reverse",
+  "abs",
+  "neg",
+  "pinyin",
+  "left",
+  "right",
+  "slice",
+  "split",
+  "pad_left",
+  "pad_right",
+  "pad_both",
+  "len",
+  "copy",
+  "cat",
+  "calcconv",
+];
+
+/// Modes whose `replacement` holds a length or position
+pub const LENGTH_MODES: &[&str] = &[
+  "left",
+  "right",
+  "slice",
+  "split",
+  "pad_left",
+  "pad_right",
+  "pad_both",
+];
+
 pub fn str_process(
   record: &StringRecord,
   context: &ProcessContext,
@@ -17,7 +60,17 @@ pub fn str_process(
   let mut row_fields: Vec<String> = record.iter().map(|s| s.to_string()).collect();
   let mut str_results = Vec::new();
   for (i, str_op) in context.str_ops.iter().enumerate() {
-    if str_op.mode == "cat" {
+    if let Some(case) = &str_op.case {
+      let value = case.evaluate(record);
+      match case.target {
+        Some(idx) => {
+          if let Some(value) = value {
+            row_fields[idx] = value;
+          }
+        }
+        None => str_results.push(value.unwrap_or_default()),
+      }
+    } else if str_op.mode == "cat" {
       // cat操作不依赖特定列，直接处理整个记录
       let template = str_op.comparand.as_deref().unwrap_or("");
       let mut dynfmt_template_wrk = template.to_string();
@@ -49,7 +102,10 @@ pub fn str_process(
       } else {
         str_results.push(String::new());
       }
+    } else if let Some(expr) = &str_op.expr {
+      str_results.push(expr.eval_cell(record));
     } else if str_op.mode == "calcconv" {
+      // `<UNIT>` and `<CPC>` templates are evaluated by the cpc calculator
       let template = str_op.comparand.as_deref().unwrap_or("");
       let mut dynfmt_template_wrk = template.to_string();
       let mut dynfmt_fields = Vec::new();
@@ -83,7 +139,7 @@ pub fn str_process(
       let (expr_str, append_unit) = if formatted.ends_with("<UNIT>") {
         (formatted.trim_end_matches("<UNIT>").trim(), true)
       } else {
-        (formatted.trim(), false)
+        (formatted.trim().trim_end_matches("<CPC>").trim_end(), false)
       };
 
       match cpc::eval(expr_str, true, Unit::Celsius, false) {
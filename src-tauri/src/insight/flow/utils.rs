@@ -1,7 +1,24 @@
-use csv::StringRecord;
-use serde::Deserialize;
+use std::{
+  fs::File,
+  io::{BufReader, BufWriter, Read},
+  path::{Path, PathBuf},
+  sync::Arc,
+};
 
-#[derive(Debug, Deserialize)]
+use anyhow::{Result, anyhow};
+use csv::{QuoteStyle, ReaderBuilder, StringRecord};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  expr::{self, Expression},
+  flow::{
+    case::{CaseRule, CaseValue, CaseWhen},
+    output::OutputFormat,
+  },
+  io::csv::{config::CsvConfigBuilder, options::CsvOptions},
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Operation {
   pub op: String,
   pub mode: Option<String>,
@@ -10,6 +27,137 @@ pub struct Operation {
   pub value: Option<String>,
   pub comparand: Option<String>,
   pub replacement: Option<String>,
+  /// Rules of a `case` operation
+  pub rules: Option<Vec<CaseRule>>,
+  /// Value of a `case` operation when no rule matches
+  pub otherwise: Option<CaseValue>,
+  /// `|` separated key columns of a `window` operation
+  pub partition_by: Option<String>,
+  /// `|` separated columns ordering the rows inside a partition when `sort` is set
+  pub order_by: Option<String>,
+  /// Sort the input by partition and order keys before a `window` operation
+  pub sort: Option<bool>,
+  /// Row distance of lag, lead and diff, defaults to 1
+  pub offset: Option<usize>,
+  /// Header of the column added by a `window` operation
+  pub alias: Option<String>,
+}
+
+/// How the input CSV of a flow is read
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FlowDialect {
+  /// Input delimiter, detected from the header line when empty
+  pub delimiter: Option<String>,
+  pub quoting: bool,
+  pub skiprows: usize,
+}
+
+impl Default for FlowDialect {
+  fn default() -> Self {
+    Self {
+      delimiter: None,
+      quoting: true,
+      skiprows: 0,
+    }
+  }
+}
+
+impl FlowDialect {
+  /// Open `input_path` with this dialect, return the delimiter and the reader
+  pub fn reader(
+    &self,
+    input_path: &str,
+  ) -> Result<(u8, csv::Reader<BufReader<Box<dyn Read + Send>>>)> {
+    let mut opts = CsvOptions::new(input_path);
+    opts.set_skiprows(self.skiprows);
+    let (detected, reader) = opts.skiprows_and_delimiter()?;
+    let sep = match self.delimiter.as_deref() {
+      Some(d) if !d.is_empty() => parse_delimiter(d)?,
+      _ => detected,
+    };
+    let rdr = ReaderBuilder::new()
+      .delimiter(sep)
+      .quoting(self.quoting)
+      .from_reader(reader);
+
+    Ok((sep, rdr))
+  }
+}
+
+/// Where and how the result of a flow is written
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FlowOutput {
+  /// Output file, overrides `dir` and `suffix`
+  pub path: Option<String>,
+  /// Output directory, defaults to the directory of the input file
+  pub dir: Option<String>,
+  /// Output file name is `<stem>.<suffix>.<format>`, defaults to `flow`
+  pub suffix: Option<String>,
+  /// csv, xlsx, parquet or jsonl, defaults to csv
+  pub format: Option<String>,
+  /// Write one file per distinct value of this output column
+  pub partition_by: Option<String>,
+  /// Output delimiter, defaults to the input delimiter
+  pub delimiter: Option<String>,
+  /// necessary, always, never or non_numeric
+  pub quote_style: Option<String>,
+}
+
+impl FlowOutput {
+  pub fn output_path(&self, input_path: &str) -> Result<PathBuf> {
+    if let Some(path) = self.path.as_deref().filter(|p| !p.is_empty()) {
+      return Ok(PathBuf::from(path));
+    }
+
+    let input = Path::new(input_path);
+    let parent = match self.dir.as_deref() {
+      Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+      _ => input
+        .parent()
+        .ok_or(anyhow!("get parent path failed"))?
+        .to_path_buf(),
+    };
+    let stem = input
+      .file_stem()
+      .ok_or(anyhow!("get file stem failed"))?
+      .to_string_lossy();
+    let suffix = self.suffix.as_deref().unwrap_or("flow");
+    let ext = OutputFormat::parse(self.format.as_deref())?.extension();
+
+    Ok(parent.join(format!("{stem}.{suffix}.{ext}")))
+  }
+
+  /// Create the csv writer, `sep` is the delimiter of the input file
+  pub fn writer(&self, output_path: &PathBuf, sep: u8) -> Result<csv::Writer<BufWriter<File>>> {
+    let write_delim = match self.delimiter.as_deref() {
+      Some(d) if !d.is_empty() => parse_delimiter(d)?,
+      _ => sep,
+    };
+    let quote_style = match self.quote_style.as_deref().unwrap_or("necessary") {
+      "necessary" => QuoteStyle::Necessary,
+      "always" => QuoteStyle::Always,
+      "never" => QuoteStyle::Never,
+      "non_numeric" => QuoteStyle::NonNumeric,
+      other => return Err(anyhow!("Unsupported quote style: {other}")),
+    };
+
+    CsvConfigBuilder::new()
+      .write_delimiter(write_delim)
+      .quote_style(quote_style)
+      .build()
+      .build_writer(output_path)
+  }
+}
+
+/// Parse a delimiter written as a single character or as `\t` / `tab`
+pub fn parse_delimiter(delimiter: &str) -> Result<u8> {
+  match delimiter {
+    "\\t" | "tab" => Ok(b'\t'),
+    d if d.len() == 1 => Ok(d.as_bytes()[0]),
+    d => Err(anyhow!("Delimiter must be a single ASCII character: {d}")),
+  }
 }
 
 #[derive(Clone)]
@@ -18,11 +166,16 @@ pub struct StrOperation {
   pub mode: String,
   pub comparand: Option<String>,
   pub replacement: Option<String>,
+  pub case: Option<Arc<CaseWhen>>,
+  /// Compiled `calcconv` expression, `None` for `<UNIT>` and `<CPC>` templates
+  pub expr: Option<Arc<Expression>>,
 }
 
 impl StrOperation {
   pub fn produces_new_column(&self) -> bool {
     match self.mode.as_str() {
+      // case creates the column only when it does not exist yet
+      "case" => self.case.as_ref().is_none_or(|c| c.target.is_none()),
       // In-place modifications — do NOT produce new column
       "fill" | "f_fill" | "lower" | "upper" | "trim" | "ltrim" | "rtrim" | "squeeze" | "strip"
       | "replace" | "regex_replace" | "round" | "reverse" | "abs" | "neg" | "normalize" => false,
@@ -30,6 +183,16 @@ impl StrOperation {
       _ => true,
     }
   }
+
+  /// Header of the column added by this operation
+  pub fn output_name(&self) -> String {
+    match self.mode.as_str() {
+      "cat" => "concatenated".to_string(),
+      "calcconv" => "calculated".to_string(),
+      "case" => self.column.clone(),
+      mode => format!("{}_{}", self.column, mode),
+    }
+  }
 }
 
 pub struct Filter {
@@ -104,6 +267,50 @@ impl ProcessContext {
       mode: mode.to_string(),
       comparand: comparand.map(|s| s.to_string()),
       replacement: replacement.map(|s| s.to_string()),
+      case: None,
+      expr: None,
+    });
+  }
+
+  /// Add a `str` operation, `calcconv` expressions are compiled against `headers`
+  pub fn add_str_op(&mut self, op: &Operation, headers: &[String]) -> Result<()> {
+    let Some(mode) = op.mode.as_deref() else {
+      return Ok(());
+    };
+    let column = match (op.column.as_deref(), mode) {
+      (Some(col), _) => col,
+      (None, "cat" | "calcconv") => "",
+      (None, _) => return Ok(()),
+    };
+    self.add_str(
+      column,
+      mode,
+      op.comparand.as_deref(),
+      op.replacement.as_deref(),
+    );
+
+    if mode == "calcconv" {
+      let template = op.comparand.as_deref().unwrap_or("");
+      // `<UNIT>` and `<CPC>` templates still go through the cpc calculator
+      if !expr::is_cpc_template(template) {
+        let expr = Expression::compile(template, headers)?;
+        if let Some(str_op) = self.str_ops.last_mut() {
+          str_op.expr = Some(Arc::new(expr));
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn add_case(&mut self, column: &str, case: CaseWhen) {
+    self.str_ops.push(StrOperation {
+      column: column.to_string(),
+      mode: "case".to_string(),
+      comparand: None,
+      replacement: None,
+      case: Some(Arc::new(case)),
+      expr: None,
     });
   }
 
@@ -113,6 +320,15 @@ impl ProcessContext {
       .push((column.to_string(), value.to_string()));
   }
 
+  /// One line per expression with the rows `calcconv` failed on
+  pub fn error_reports(&self) -> Vec<String> {
+    self
+      .str_ops
+      .iter()
+      .filter_map(|str_op| str_op.expr.as_ref().and_then(|e| e.report()))
+      .collect()
+  }
+
   pub fn is_valid(&self, record: &StringRecord) -> bool {
     if self.filters.is_empty() {
       return true;
@@ -0,0 +1,278 @@
+use std::{cmp::Ordering, collections::VecDeque, str::FromStr};
+
+use anyhow::{Result, anyhow};
+use csv::StringRecord;
+use rust_decimal::Decimal;
+
+use crate::flow::utils::Operation;
+
+/// Modes understood by `window` operations
+pub const MODES: &[&str] = &["cumsum", "lag", "lead", "diff", "row_number"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+  CumSum,
+  Lag,
+  Lead,
+  Diff,
+  RowNumber,
+}
+
+struct Window {
+  mode: Mode,
+  column: Option<usize>,
+  partition: Vec<usize>,
+  offset: usize,
+  // state of the current partition
+  key: Option<Vec<String>>,
+  sum: Decimal,
+  row_number: usize,
+  history: VecDeque<String>,
+}
+
+/// Window operations of a flow, evaluated row by row on input sorted by the partition keys
+pub struct Windows {
+  windows: Vec<Window>,
+  names: Vec<String>,
+  sort_keys: Option<Vec<usize>>,
+}
+
+fn position(headers: &[String], name: &str) -> Result<usize> {
+  headers
+    .iter()
+    .position(|h| h == name)
+    .ok_or_else(|| anyhow!("Column not found: {}", name))
+}
+
+fn positions(headers: &[String], names: Option<&str>) -> Result<Vec<usize>> {
+  names
+    .unwrap_or("")
+    .split('|')
+    .filter(|s| !s.is_empty())
+    .map(|name| position(headers, name))
+    .collect()
+}
+
+fn to_decimal(s: &str) -> Option<Decimal> {
+  let s = s.trim();
+  Decimal::from_str(s)
+    .or_else(|_| Decimal::from_scientific(s))
+    .ok()
+}
+
+/// Numbers compare as numbers and sort before everything else, which compares as text
+fn compare_values(a: &str, b: &str) -> Ordering {
+  match (to_decimal(a), to_decimal(b)) {
+    (Some(x), Some(y)) => x.cmp(&y),
+    (Some(_), None) => Ordering::Less,
+    (None, Some(_)) => Ordering::Greater,
+    (None, None) => a.cmp(b),
+  }
+}
+
+impl Window {
+  fn partition_key(&self, record: &StringRecord) -> Vec<String> {
+    self
+      .partition
+      .iter()
+      .map(|&i| record.get(i).unwrap_or("").to_string())
+      .collect()
+  }
+
+  fn value<'a>(&self, record: &'a StringRecord) -> &'a str {
+    self.column.and_then(|i| record.get(i)).unwrap_or("")
+  }
+
+  fn evaluate(&mut self, record: &StringRecord, ahead: &VecDeque<StringRecord>) -> String {
+    let key = self.partition_key(record);
+    if self.key.as_ref() != Some(&key) {
+      self.sum = Decimal::ZERO;
+      self.row_number = 0;
+      self.history.clear();
+      self.key = Some(key);
+    }
+
+    let value = self.value(record);
+    let previous = if self.history.len() >= self.offset {
+      self.history.get(self.history.len() - self.offset).cloned()
+    } else {
+      None
+    };
+
+    let result = match self.mode {
+      Mode::RowNumber => {
+        self.row_number += 1;
+        self.row_number.to_string()
+      }
+      Mode::CumSum => {
+        self.sum += to_decimal(value).unwrap_or(Decimal::ZERO);
+        self.sum.normalize().to_string()
+      }
+      Mode::Lag => previous.unwrap_or_default(),
+      Mode::Diff => previous
+        .and_then(|p| {
+          Some(
+            (to_decimal(value)? - to_decimal(&p)?)
+              .normalize()
+              .to_string(),
+          )
+        })
+        .unwrap_or_default(),
+      Mode::Lead => ahead
+        .get(self.offset - 1)
+        .filter(|next| self.key.as_ref() == Some(&self.partition_key(next)))
+        .map(|next| self.value(next).to_string())
+        .unwrap_or_default(),
+    };
+
+    if matches!(self.mode, Mode::Lag | Mode::Diff) {
+      self.history.push_back(value.to_string());
+      if self.history.len() > self.offset {
+        self.history.pop_front();
+      }
+    }
+
+    result
+  }
+}
+
+impl Windows {
+  pub fn new(operations: &[&Operation], headers: &[String]) -> Result<Self> {
+    let mut windows = Vec::with_capacity(operations.len());
+    let mut names = Vec::with_capacity(operations.len());
+    let mut sort_keys: Option<Vec<usize>> = None;
+
+    for op in operations {
+      let mode_name = op
+        .mode
+        .as_deref()
+        .ok_or_else(|| anyhow!("Missing mode in window"))?;
+      let mode = match mode_name {
+        "cumsum" => Mode::CumSum,
+        "lag" => Mode::Lag,
+        "lead" => Mode::Lead,
+        "diff" => Mode::Diff,
+        "row_number" => Mode::RowNumber,
+        _ => return Err(anyhow!("Not supported window mode: {}", mode_name)),
+      };
+      let column = match (mode, op.column.as_deref()) {
+        (Mode::RowNumber, _) => None,
+        (_, Some(col)) => Some(position(headers, col)?),
+        (_, None) => return Err(anyhow!("Missing column in window {}", mode_name)),
+      };
+      let offset = op.offset.unwrap_or(1);
+      if offset == 0 {
+        return Err(anyhow!("window offset must be at least 1"));
+      }
+      let partition = positions(headers, op.partition_by.as_deref())?;
+
+      if op.sort.unwrap_or(false) {
+        let mut keys = partition.clone();
+        keys.extend(positions(headers, op.order_by.as_deref())?);
+        match &sort_keys {
+          Some(existing) if existing != &keys => {
+            return Err(anyhow!(
+              "window operations ask to sort by different columns"
+            ));
+          }
+          _ => sort_keys = Some(keys),
+        }
+      }
+
+      names.push(match (&op.alias, op.column.as_deref()) {
+        (Some(alias), _) if !alias.is_empty() => alias.clone(),
+        (_, Some(col)) if mode != Mode::RowNumber => format!("{col}_{mode_name}"),
+        _ => mode_name.to_string(),
+      });
+      windows.push(Window {
+        mode,
+        column,
+        partition,
+        offset,
+        key: None,
+        sum: Decimal::ZERO,
+        row_number: 0,
+        history: VecDeque::new(),
+      });
+    }
+
+    Ok(Windows {
+      windows,
+      names,
+      sort_keys,
+    })
+  }
+
+  /// Headers of the columns added by the window operations
+  pub fn names(&self) -> &[String] {
+    &self.names
+  }
+
+  /// Number of rows `evaluate` needs to see ahead of the current row
+  pub fn lookahead(&self) -> usize {
+    self
+      .windows
+      .iter()
+      .filter(|w| w.mode == Mode::Lead)
+      .map(|w| w.offset)
+      .max()
+      .unwrap_or(0)
+  }
+
+  /// Sort the records by the partition and order keys when asked to sort first
+  pub fn sort(&self, records: &mut [StringRecord]) {
+    if let Some(keys) = &self.sort_keys {
+      records.sort_by(|a, b| {
+        keys
+          .iter()
+          .map(|&i| compare_values(a.get(i).unwrap_or(""), b.get(i).unwrap_or("")))
+          .find(|o| o.is_ne())
+          .unwrap_or(Ordering::Equal)
+      });
+    }
+  }
+
+  pub fn needs_sort(&self) -> bool {
+    self.sort_keys.is_some()
+  }
+
+  /// Window values of `record`, `ahead` holds the rows following it
+  pub fn evaluate(&mut self, record: &StringRecord, ahead: &VecDeque<StringRecord>) -> Vec<String> {
+    self
+      .windows
+      .iter_mut()
+      .map(|w| w.evaluate(record, ahead))
+      .collect()
+  }
+}
+
+/// Record iterator keeping the next `size` rows buffered for `lead`
+pub struct Lookahead<I> {
+  iter: I,
+  size: usize,
+  buffer: VecDeque<StringRecord>,
+}
+
+impl<I: Iterator<Item = Result<StringRecord>>> Lookahead<I> {
+  pub fn new(iter: I, size: usize) -> Self {
+    Lookahead {
+      iter,
+      size,
+      buffer: VecDeque::with_capacity(size + 1),
+    }
+  }
+
+  pub fn next_record(&mut self) -> Result<Option<StringRecord>> {
+    while self.buffer.len() <= self.size {
+      match self.iter.next() {
+        Some(record) => self.buffer.push_back(record?),
+        None => break,
+      }
+    }
+    Ok(self.buffer.pop_front())
+  }
+
+  pub fn ahead(&self) -> &VecDeque<StringRecord> {
+    &self.buffer
+  }
+}
@@ -29,6 +29,13 @@ impl Selection {
     Ok(Selection { indices })
   }
 
+  /// Every column of a row with `len` fields
+  pub fn all(len: usize) -> Self {
+    Selection {
+      indices: (0..len).collect(),
+    }
+  }
+
   pub fn get_row_key(&self, row: &ByteRecord) -> Vec<ByteString> {
     self
       .indices
@@ -1,9 +1,11 @@
 pub mod cmd;
+pub mod expr;
 pub mod flow;
 pub mod io;
 pub mod sql;
 
 pub mod command;
 pub mod index;
+pub mod normalize;
 pub mod tojson;
 pub mod utils;
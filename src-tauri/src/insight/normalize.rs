@@ -0,0 +1,101 @@
+//! Text normalisation shared by the commands comparing cell values.
+
+use std::borrow::Cow;
+
+use unicode_normalization::UnicodeNormalization;
+
+/// How values are normalised before they are compared.
+/// The steps run in the order nfkc, width, collapse/trim, case, zeros, numeric.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct TextNorm {
+  /// lowercase both sides
+  pub case: bool,
+  /// strip leading and trailing whitespace
+  pub trim: bool,
+  /// replace runs of whitespace with one space, implies `trim`
+  pub collapse: bool,
+  /// fold full-width ASCII and the ideographic space to half-width
+  pub width: bool,
+  /// Unicode NFKC
+  pub nfkc: bool,
+  /// strip leading zeros, `00123` -> `123`
+  pub zeros: bool,
+  /// write numbers the same way, `1.0` -> `1`, `+1e3` -> `1000`
+  pub numeric: bool,
+}
+
+/// `Ａ` -> `A`, ideographic space -> space
+fn fold_width(c: char) -> char {
+  match c {
+    '\u{3000}' => ' ',
+    '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+    _ => c,
+  }
+}
+
+/// `007` -> `7`, `000` -> `0`, `0.5` is kept
+fn strip_zeros(s: &str) -> &str {
+  let stripped = s.trim_start_matches('0');
+  if stripped.len() == s.len() {
+    return s;
+  }
+  match stripped.chars().next() {
+    None | Some('.') => &s[s.len() - stripped.len() - 1..],
+    _ => stripped,
+  }
+}
+
+/// The canonical form of a number, `None` when `s` is not a number
+fn canonical_number(s: &str) -> Option<String> {
+  let t = s.trim();
+  let digits = t.strip_prefix(['+', '-']).unwrap_or(t);
+  // integers are kept exact, f64 would round the long ones
+  if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+    let n = strip_zeros(digits);
+    return Some(match t.starts_with('-') && n != "0" {
+      true => format!("-{n}"),
+      false => n.to_string(),
+    });
+  }
+  let v = t.parse::<f64>().ok().filter(|v| v.is_finite())?;
+  Some(if v == 0.0 {
+    "0".to_string()
+  } else {
+    v.to_string()
+  })
+}
+
+impl TextNorm {
+  pub fn is_identity(&self) -> bool {
+    *self == TextNorm::default()
+  }
+
+  pub fn apply<'a>(&self, value: &'a str) -> Cow<'a, str> {
+    let mut s = Cow::Borrowed(value);
+    if self.nfkc {
+      s = Cow::Owned(s.nfkc().collect());
+    }
+    if self.width && s.chars().any(|c| c != fold_width(c)) {
+      s = Cow::Owned(s.chars().map(fold_width).collect());
+    }
+    if self.collapse {
+      s = Cow::Owned(s.split_whitespace().collect::<Vec<_>>().join(" "));
+    } else if self.trim {
+      s = match s {
+        Cow::Borrowed(b) => Cow::Borrowed(b.trim()),
+        Cow::Owned(o) => Cow::Owned(o.trim().to_string()),
+      };
+    }
+    if self.case {
+      s = Cow::Owned(s.to_lowercase());
+    }
+    if self.zeros && s.starts_with('0') {
+      s = Cow::Owned(strip_zeros(&s).to_string());
+    }
+    if let Some(n) = self.numeric.then(|| canonical_number(&s)).flatten() {
+      s = Cow::Owned(n);
+    }
+    s
+  }
+}
@@ -12,6 +12,8 @@ use insight::cmd::cat;
 use insight::cmd::convert;
 use insight::cmd::count;
 use insight::cmd::datefmt;
+use insight::cmd::dedup;
+use insight::cmd::diff;
 use insight::cmd::enumerate;
 use insight::cmd::extsort;
 use insight::cmd::fill;
@@ -71,6 +73,10 @@ fn main() {
       command::dupli_headers,
       command::to_json,
       flow::flow,
+      flow::recipe::save_recipe,
+      flow::recipe::load_recipe,
+      flow::recipe::validate_recipe,
+      flow::recipe::run_recipe,
       apply::apply,
       cat::concat,
       convert::excel_to_csv::map_excel_sheets,
@@ -85,6 +91,8 @@ fn main() {
       convert::perform::jsonl2csv,
       count::count,
       datefmt::datefmt,
+      dedup::dedup,
+      diff::diff,
       enumerate::enumer,
       extsort::extsort,
       fill::fill,
@@ -97,6 +105,7 @@ fn main() {
       reverse::reverse,
       search::perform::search,
       search::perform::search_chain,
+      search::many::search_many,
       select::select,
       separate::separate,
       skip::skip,
@@ -0,0 +1,61 @@
+#[tokio::test]
+async fn test_dedup() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path = temp_dir.path().join("input.csv");
+  std::fs::write(&path, "id,name\n1,Tom\n2,Jerry\n1,tom\n3,Pat\n2,Jerry\n")?;
+
+  let cases = vec![
+    (r#"{}"#, "id,name\n1,Tom\n2,Jerry\n1,tom\n3,Pat\n", 1),
+    (r#"{"keys": "id"}"#, "id,name\n1,Tom\n2,Jerry\n3,Pat\n", 2),
+    (
+      r#"{"keys": "id", "keep": "last"}"#,
+      "id,name\n1,tom\n3,Pat\n2,Jerry\n",
+      2,
+    ),
+    (r#"{"keys": "id", "keep": "none"}"#, "id,name\n3,Pat\n", 2),
+    (
+      r#"{"keys": "name", "norm": {"case": true}}"#,
+      "id,name\n1,Tom\n2,Jerry\n3,Pat\n",
+      2,
+    ),
+  ];
+  for external in [false, true] {
+    for (options, expected, duplicate_keys) in &cases {
+      let mut options: insight::cmd::dedup::DedupOptions = serde_json::from_str(options)?;
+      options.external = external;
+      let stats =
+        insight::cmd::dedup::run_dedup(path.to_string_lossy().to_string(), true, options).await?;
+
+      let output = std::fs::read_to_string(temp_dir.path().join("input_dedup.csv"))?;
+      assert_eq!(*expected, output, "{external}");
+      let kept = output.lines().count() - 1;
+      let expected_stats = insight::cmd::dedup::DedupStats {
+        rows: 5,
+        kept,
+        removed: 5 - kept,
+        duplicate_keys: *duplicate_keys,
+      };
+      assert_eq!(expected_stats, stats, "{external}");
+    }
+  }
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_dedup_removed() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path = temp_dir.path().join("input.csv");
+  std::fs::write(&path, "id,name\n1,Tom\n2,Jerry\n1,tom\n3,Pat\n2,Jerry\n")?;
+
+  insight::cmd::dedup::run_dedup(
+    path.to_string_lossy().to_string(),
+    true,
+    serde_json::from_str(r#"{"keys": "id", "removed": true}"#)?,
+  )
+  .await?;
+  let removed = std::fs::read_to_string(temp_dir.path().join("input_dedup_removed.csv"))?;
+  assert_eq!("id,name\n1,tom\n2,Jerry\n", removed);
+
+  Ok(temp_dir.close()?)
+}
@@ -0,0 +1,121 @@
+fn create_temp_csv() -> anyhow::Result<(tempfile::TempDir, String, String)> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("old.csv");
+  std::fs::write(
+    &path1,
+    "id,name,city,phone\n1,Acme,NY,111\n2,Beta,LA,222\n3,Gamma,SF,333\n",
+  )?;
+  let path2 = temp_dir.path().join("new.csv");
+  std::fs::write(
+    &path2,
+    "id,name,city,email\n1,Acme,Boston,a@x\n3,Gamma,SF,g@x\n4,Delta,TX,d@x\n",
+  )?;
+
+  let p1 = path1.to_string_lossy().to_string();
+  let p2 = path2.to_string_lossy().to_string();
+  Ok((temp_dir, p1, p2))
+}
+
+#[tokio::test]
+async fn test_diff() -> anyhow::Result<()> {
+  let (temp_dir, path1, path2) = create_temp_csv()?;
+
+  let cases = vec![
+    (
+      r#"{}"#,
+      "diff,id,name,city,phone,email\n\
+       changed,1,Acme,Boston,111,a@x\n\
+       added,4,Delta,TX,,d@x\n\
+       removed,2,Beta,LA,222,\n",
+    ),
+    (
+      r#"{"sort_merge": true}"#,
+      "diff,id,name,city,phone,email\n\
+       changed,1,Acme,Boston,111,a@x\n\
+       removed,2,Beta,LA,222,\n\
+       added,4,Delta,TX,,d@x\n",
+    ),
+  ];
+  for (options, expected) in cases {
+    let stats = insight::cmd::diff::run_diff(
+      path1.clone(),
+      path2.clone(),
+      "id".to_string(),
+      true,
+      serde_json::from_str(options)?,
+    )
+    .await?;
+    let expected_stats = insight::cmd::diff::DiffStats {
+      old_rows: 3,
+      new_rows: 3,
+      added: 1,
+      removed: 1,
+      changed: 1,
+      unchanged: 1,
+      changed_cells: 1,
+      added_columns: vec!["email".to_string()],
+      removed_columns: vec!["phone".to_string()],
+    };
+    assert_eq!(expected_stats, stats, "{options}");
+
+    let output = std::fs::read_to_string(temp_dir.path().join("old_diff.csv"))?;
+    assert_eq!(expected, output, "{options}");
+    let cells = std::fs::read_to_string(temp_dir.path().join("old_diff_cells.csv"))?;
+    assert_eq!("id,column,old_value,new_value\n1,city,NY,Boston\n", cells);
+  }
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_diff_ignore_columns() -> anyhow::Result<()> {
+  let (temp_dir, path1, path2) = create_temp_csv()?;
+
+  let stats = insight::cmd::diff::run_diff(
+    path1,
+    path2,
+    "id".to_string(),
+    true,
+    serde_json::from_str(r#"{"ignore_columns": "city"}"#)?,
+  )
+  .await?;
+  assert_eq!(
+    (0, 2, 0),
+    (stats.changed, stats.unchanged, stats.changed_cells)
+  );
+
+  let output = std::fs::read_to_string(temp_dir.path().join("old_diff.csv"))?;
+  assert_eq!(
+    "diff,id,name,city,phone,email\nadded,4,Delta,TX,,d@x\nremoved,2,Beta,LA,222,\n",
+    output
+  );
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_diff_duplicate_key() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("old.csv");
+  std::fs::write(&path1, "id,name\n1,Acme\n2,Beta\n")?;
+  let path2 = temp_dir.path().join("new.csv");
+  std::fs::write(&path2, "id,name\n1,Acme\n1,Acme Corp\n")?;
+
+  for options in [r#"{}"#, r#"{"sort_merge": true}"#] {
+    let err = insight::cmd::diff::run_diff(
+      path1.to_string_lossy().to_string(),
+      path2.to_string_lossy().to_string(),
+      "id".to_string(),
+      true,
+      serde_json::from_str(options)?,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!("Duplicate key '1' in the new file", err.to_string());
+    // nothing is written by a failed diff
+    let files = std::fs::read_dir(temp_dir.path())?.count();
+    assert_eq!(2, files, "{options}");
+  }
+
+  Ok(temp_dir.close()?)
+}
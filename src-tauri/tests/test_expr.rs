@@ -0,0 +1,94 @@
+use csv::StringRecord;
+use insight::expr::{Expression, Value};
+
+fn eval(source: &str, headers: &[&str], row: &[&str]) -> anyhow::Result<String> {
+  let expr = Expression::compile(source, headers)?;
+  Ok(expr.eval(&StringRecord::from(row.to_vec()))?.to_string())
+}
+
+#[test]
+fn test_expr_arithmetic() -> anyhow::Result<()> {
+  let headers = ["price", "qty", "discount"];
+  let row = ["0.1", "3", ""];
+
+  assert_eq!(eval("{price} * {qty}", &headers, &row)?, "0.3");
+  assert_eq!(eval("{price} + 0.2", &headers, &row)?, "0.3");
+  assert_eq!(eval("-2 ^ 2 + 10 % 4", &headers, &row)?, "-2");
+  assert_eq!(eval("round(10 / 3, 2)", &headers, &row)?, "3.33");
+  assert_eq!(eval("{qty} - {discount}", &headers, &row)?, "");
+  assert_eq!(eval("coalesce({discount}, 0) + 1", &headers, &row)?, "1");
+  assert_eq!(eval("abs(-{qty})", &headers, &row)?, "3");
+  assert_eq!(eval("max({qty}, 7, {discount})", &headers, &row)?, "7");
+
+  Ok(())
+}
+
+#[test]
+fn test_expr_logic_and_functions() -> anyhow::Result<()> {
+  let headers = ["name", "score", "start", "end"];
+  let row = ["Patrick Star", "72", "2024-01-01", "2024-03-01"];
+
+  assert_eq!(
+    eval(
+      "if({score} >= 60 and {name} != 'x', 'pass', 'fail')",
+      &headers,
+      &row
+    )?,
+    "pass"
+  );
+  assert_eq!(
+    eval("not ({score} > 100 or {score} < 0)", &headers, &row)?,
+    "true"
+  );
+  assert_eq!(eval("substr({name}, 9)", &headers, &row)?, "Star");
+  assert_eq!(eval("upper(substr({name}, 1, 3))", &headers, &row)?, "PAT");
+  assert_eq!(eval("datediff({end}, {start})", &headers, &row)?, "60");
+  assert_eq!(
+    eval("concat({name}, ': ', len({name}))", &headers, &row)?,
+    "Patrick Star: 12"
+  );
+
+  let expr = Expression::compile("{score} + 1", &headers)?;
+  assert_eq!(
+    expr.eval(&StringRecord::from(vec!["a", "12", "", ""]))?,
+    Value::Num(13.into())
+  );
+
+  Ok(())
+}
+
+#[test]
+fn test_expr_errors() -> anyhow::Result<()> {
+  let headers = ["a", "b"];
+
+  let err = Expression::compile("{a} + {c}", &headers).err().unwrap();
+  assert!(err.to_string().contains("unknown column `c`"));
+  assert!(Expression::compile("round({a}, 1, 2)", &headers).is_err());
+  assert!(Expression::compile("foo({a})", &headers).is_err());
+  assert!(Expression::compile("({a} + 1", &headers).is_err());
+  assert!(Expression::compile("{a} +", &headers).is_err());
+
+  // runtime errors leave the cell empty and are reported once
+  let expr = Expression::compile("{a} / {b}", &headers)?;
+  assert_eq!(expr.eval_cell(&StringRecord::from(vec!["1", "0"])), "");
+  assert_eq!(expr.eval_cell(&StringRecord::from(vec!["x", "2"])), "");
+  assert_eq!(expr.eval_cell(&StringRecord::from(vec!["1", "4"])), "0.25");
+  assert_eq!(expr.errors(), 2);
+  assert!(expr.report().unwrap().contains("failed on 2 rows"));
+
+  Ok(())
+}
+
+#[test]
+fn test_expr_power() -> anyhow::Result<()> {
+  let headers = ["a"];
+  let row = ["1"];
+
+  assert_eq!(eval("2 ^ 10", &headers, &row)?, "1024");
+  assert_eq!(eval("2 ^ -2", &headers, &row)?, "0.25");
+  assert_eq!(eval("{a} ^ 1000", &headers, &row)?, "1");
+  assert!(eval("{a} ^ 1000000000000000", &headers, &row).is_err());
+  assert!(eval("2 ^ 200", &headers, &row).is_err());
+
+  Ok(())
+}
@@ -0,0 +1,377 @@
+use insight::flow::recipe::Recipe;
+
+fn write_csv(path: &std::path::Path, lines: &[&str]) -> anyhow::Result<()> {
+  use std::io::Write;
+
+  let mut file = std::fs::File::create(path)?;
+  for line in lines {
+    writeln!(file, "{}", line)?;
+  }
+  Ok(())
+}
+
+#[tokio::test]
+async fn test_recipe_batch() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+
+  write_csv(
+    &temp_dir.path().join("a.csv"),
+    &["name,age", "Jerry,19", "Patrick,4", "Sandy,24"],
+  )?;
+  write_csv(
+    &temp_dir.path().join("b.csv"),
+    &["name,age", "Tom,31", "Spike,2"],
+  )?;
+
+  let recipe = Recipe::from_json(
+    r#"{
+      "version": 1,
+      "name": "adults",
+      "output": { "delimiter": ";" },
+      "operations": [
+        { "op": "filter", "column": "age", "mode": "ge", "value": "18" },
+        { "op": "select", "column": "name" }
+      ]
+    }"#,
+  )?;
+  let recipe_path = temp_dir.path().join("adults.json");
+  recipe.save(&recipe_path)?;
+
+  let recipe = Recipe::load(&recipe_path)?;
+  let inputs = recipe.collect_inputs(temp_dir.path().to_str().unwrap())?;
+  assert_eq!(inputs.len(), 2);
+
+  let summary = recipe.run_batch(&inputs).await?;
+  assert_eq!(summary.files, 2);
+  assert_eq!(summary.failed, 0);
+  assert_eq!(summary.rows, 3);
+
+  let context = std::fs::read_to_string(temp_dir.path().join("a.flow.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  assert_eq!(vec!["name", "Jerry", "Sandy"], result);
+
+  let context = std::fs::read_to_string(temp_dir.path().join("b.flow.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  assert_eq!(vec!["name", "Tom"], result);
+
+  // outputs of the recipe are not picked up again
+  let inputs = recipe.collect_inputs(temp_dir.path().to_str().unwrap())?;
+  assert_eq!(inputs.len(), 2);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_recipe_batch_failure() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+
+  let good = temp_dir.path().join("good.csv");
+  write_csv(&good, &["name,age", "Jerry,19"])?;
+  let bad = temp_dir.path().join("bad.csv");
+  write_csv(&bad, &["first_name,years", "Tom,31"])?;
+
+  let recipe = Recipe::from_json(r#"[{ "op": "select", "column": "name" }]"#)?;
+  let inputs = vec![
+    good.to_str().unwrap().to_string(),
+    bad.to_str().unwrap().to_string(),
+  ];
+  let summary = recipe.run_batch(&inputs).await?;
+  assert_eq!(summary.succeeded, 1);
+  assert_eq!(summary.failed, 1);
+  assert!(summary.results[1].error.is_some());
+
+  Ok(temp_dir.close()?)
+}
+
+#[test]
+fn test_recipe_validate() -> anyhow::Result<()> {
+  let recipe = Recipe::from_json(
+    r#"{
+      "version": 1,
+      "operations": [
+        { "op": "filter", "column": "age", "mode": "bigger", "value": "18" },
+        { "op": "str", "column": "name", "mode": "left", "replacement": "x" },
+        { "op": "drop" }
+      ]
+    }"#,
+  )?;
+  let problems = recipe.problems();
+  assert_eq!(problems.len(), 3);
+  assert!(problems[0].starts_with("operation #1 (filter)"));
+  assert!(recipe.validate().is_err());
+
+  assert!(Recipe::from_json(r#"{ "version": 99, "operations": [] }"#).is_err());
+  assert!(Recipe::from_json(r#"{ "operations": [] }"#).is_err());
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn test_flow_case() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+
+  let file_path = temp_dir.path().join("input.csv");
+  write_csv(
+    &file_path,
+    &["name,score", "Jerry,95", "Patrick,", "Sandy,72", "Tom,40"],
+  )?;
+
+  let config = r#"[
+    {
+      "op": "case",
+      "column": "grade",
+      "rules": [
+        { "column": "score", "mode": "is_null", "then": { "value": "absent" } },
+        { "column": "score", "mode": "ge", "value": "90", "then": { "value": "A" } },
+        { "column": "score", "mode": "ge", "value": "60", "then": { "kind": "template", "value": "{name}:pass" } }
+      ],
+      "otherwise": { "kind": "column", "value": "score" }
+    },
+    {
+      "op": "case",
+      "column": "name",
+      "rules": [
+        { "column": "name", "mode": "equal", "value": "Tom", "then": { "value": "Thomas" } }
+      ]
+    }
+  ]"#;
+  insight::flow::flow(
+    file_path.to_str().unwrap().to_string(),
+    config.to_string(),
+    true,
+    None,
+  )
+  .await
+  .map_err(|e| anyhow::anyhow!(e))?;
+
+  let context = std::fs::read_to_string(temp_dir.path().join("input.flow.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "name,score,grade",
+    "Jerry,95,A",
+    "Patrick,,absent",
+    "Sandy,72,Sandy:pass",
+    "Thomas,40,40",
+  ];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_flow_window() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+
+  let file_path = temp_dir.path().join("input.csv");
+  write_csv(
+    &file_path,
+    &[
+      "acct,day,amount",
+      "b,2,5",
+      "a,1,10.5",
+      "b,1,1",
+      "a,3,-2",
+      "a,2,4",
+    ],
+  )?;
+
+  let config = r#"[
+    { "op": "window", "mode": "row_number", "partition_by": "acct", "order_by": "day", "sort": true },
+    { "op": "window", "mode": "cumsum", "column": "amount", "partition_by": "acct", "alias": "balance" },
+    { "op": "window", "mode": "lag", "column": "amount", "partition_by": "acct" },
+    { "op": "window", "mode": "lead", "column": "amount", "partition_by": "acct" },
+    { "op": "window", "mode": "diff", "column": "amount", "partition_by": "acct" }
+  ]"#;
+  insight::flow::flow(
+    file_path.to_str().unwrap().to_string(),
+    config.to_string(),
+    true,
+    None,
+  )
+  .await
+  .map_err(|e| anyhow::anyhow!(e))?;
+
+  let context = std::fs::read_to_string(temp_dir.path().join("input.flow.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "acct,day,amount,row_number,balance,amount_lag,amount_lead,amount_diff",
+    "a,1,10.5,1,10.5,,4,",
+    "a,2,4,2,14.5,10.5,-2,-6.5",
+    "a,3,-2,3,12.5,4,,-6",
+    "b,1,1,1,1,,5,",
+    "b,2,5,2,6,1,,4",
+  ];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_flow_window_sort_mixed() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+
+  let file_path = temp_dir.path().join("input.csv");
+  write_csv(
+    &file_path,
+    &["acct,day", "a,1a", "a,10", "a,b", "a,2", "a,1.5", "a,10"],
+  )?;
+
+  // numbers first in numeric order, then the other values as text
+  let config = r#"[
+    { "op": "window", "mode": "row_number", "partition_by": "acct", "order_by": "day", "sort": true }
+  ]"#;
+  insight::flow::flow(
+    file_path.to_str().unwrap().to_string(),
+    config.to_string(),
+    true,
+    None,
+  )
+  .await
+  .map_err(|e| anyhow::anyhow!(e))?;
+
+  let context = std::fs::read_to_string(temp_dir.path().join("input.flow.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "acct,day,row_number",
+    "a,1.5,1",
+    "a,2,2",
+    "a,10,3",
+    "a,10,4",
+    "a,1a,5",
+    "a,b,6",
+  ];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_recipe_partitioned_output() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+
+  let file_path = temp_dir.path().join("input.csv");
+  write_csv(
+    &file_path,
+    &["name,city", "Jerry,Paris", "Tom,Oslo", "Sandy,Paris"],
+  )?;
+  let out_dir = temp_dir.path().join("out");
+  std::fs::create_dir(&out_dir)?;
+
+  let recipe = Recipe::from_json(&format!(
+    r#"{{
+      "version": 1,
+      "output": {{ "dir": {:?}, "partition_by": "city", "delimiter": "\\t" }},
+      "operations": [{{ "op": "str", "column": "name", "mode": "upper" }}]
+    }}"#,
+    out_dir.to_str().unwrap()
+  ))?;
+  let summary = recipe
+    .run_batch(&[file_path.to_str().unwrap().to_string()])
+    .await?;
+  assert_eq!(summary.rows, 3);
+
+  let context = std::fs::read_to_string(out_dir.join("input.flow_Paris.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  assert_eq!(vec!["name\tcity", "JERRY\tParis", "SANDY\tParis"], result);
+
+  let context = std::fs::read_to_string(out_dir.join("input.flow_Oslo.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  assert_eq!(vec!["name\tcity", "TOM\tOslo"], result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_partition_file_name_clash() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+
+  let output = insight::flow::utils::FlowOutput {
+    partition_by: Some("k".to_string()),
+    ..Default::default()
+  };
+  let output_path = temp_dir.path().join("input.flow.csv");
+  let mut wtr = insight::flow::output::FlowWriter::new(&output, output_path, b',', &["k", "v"])?;
+  for row in [
+    ["a/b", "1"],
+    ["a:b", "2"],
+    ["", "3"],
+    ["empty", "4"],
+    ["a/b", "5"],
+    ["A-B", "6"],
+  ] {
+    wtr.write_record(row)?;
+  }
+  let paths = wtr.finish()?;
+  let names: Vec<_> = paths
+    .iter()
+    .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+    .collect();
+  assert_eq!(
+    vec![
+      "input.flow_a-b.csv",
+      "input.flow_a-b_2.csv",
+      "input.flow_empty.csv",
+      "input.flow_empty_2.csv",
+      "input.flow_A-B_3.csv"
+    ],
+    names
+  );
+
+  let context = std::fs::read_to_string(&paths[0])?;
+  assert_eq!("k,v\na/b,1\na/b,5\n", context);
+  let context = std::fs::read_to_string(&paths[1])?;
+  assert_eq!("k,v\na:b,2\n", context);
+
+  // a writer dropped before `finish` removes its files
+  let output = insight::flow::utils::FlowOutput {
+    partition_by: Some("k".to_string()),
+    format: Some("jsonl".to_string()),
+    ..Default::default()
+  };
+  let output_path = temp_dir.path().join("failed.flow.jsonl");
+  let mut wtr = insight::flow::output::FlowWriter::new(&output, output_path, b',', &["k", "v"])?;
+  wtr.write_record(["x", "1"])?;
+  wtr.write_record(["y", "2"])?;
+  drop(wtr);
+  let failed = std::fs::read_dir(temp_dir.path())?
+    .filter_map(|e| e.ok())
+    .filter(|e| e.file_name().to_string_lossy().starts_with("failed"))
+    .count();
+  assert_eq!(0, failed);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_flow_calcconv() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+
+  let file_path = temp_dir.path().join("input.csv");
+  write_csv(&file_path, &["a", "9", "16"])?;
+  let flow = |comparand: &str| {
+    let config = format!(r#"[{{ "op": "str", "mode": "calcconv", "comparand": "{comparand}" }}]"#);
+    insight::flow::flow(file_path.to_str().unwrap().to_string(), config, true, None)
+  };
+
+  // `sqrt` is not an expression function, `<CPC>` sends the template to the cpc calculator
+  flow("sqrt({a}) <CPC>")
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?;
+  let context = std::fs::read_to_string(temp_dir.path().join("input.flow.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  assert_eq!("a,calculated", result[0]);
+  assert!(result[1].starts_with("9,3"));
+  assert!(result[2].starts_with("16,4"));
+
+  // an invalid expression fails before any row is written
+  let err = flow("{amout} * 2").await.unwrap_err();
+  assert!(err.contains("amout"), "{err}");
+
+  // the rows an expression fails on are reported with the result
+  let (reports, _) = flow("10 / ({a} - 9)")
+    .await
+    .map_err(|e| anyhow::anyhow!(e))?;
+  assert!(reports.contains("failed on 1 rows"), "{reports}");
+
+  Ok(temp_dir.close()?)
+}
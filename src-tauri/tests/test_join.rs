@@ -187,3 +187,526 @@ async fn test_right_anti_join() -> anyhow::Result<()> {
 
   Ok(temp_dir.close()?)
 }
+
+#[tokio::test]
+async fn test_composite_key_join() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("docs.csv");
+  std::fs::write(
+    &path1,
+    "bukrs,belnr,gjahr,amount\n1000,A1,2023,10\n1000,A1,2024,20\n2000,A1,2024,30\n",
+  )?;
+  let path2 = temp_dir.path().join("texts.csv");
+  std::fs::write(
+    &path2,
+    "company,doc_no,year,text\n1000,A1,2024,rent\n2000,A1,2024,salary\n2000,A2,2024,fee\n",
+  )?;
+
+  insight::cmd::join::run_join(
+    path1.to_string_lossy().to_string(),
+    path2.to_string_lossy().to_string(),
+    "bukrs|belnr|gjahr".to_string(),
+    "company|doc_no|year".to_string(),
+    "left",
+    false,
+    true,
+  )
+  .await?;
+  let context = std::fs::read_to_string(temp_dir.path().join("docs_join.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "bukrs,belnr,gjahr,amount,company,doc_no,year,text",
+    "1000,A1,2023,10,,,,",
+    "1000,A1,2024,20,1000,A1,2024,rent",
+    "2000,A1,2024,30,2000,A1,2024,salary",
+  ];
+  assert_eq!(expected, result);
+
+  let err = insight::cmd::join::run_join(
+    path1.to_string_lossy().to_string(),
+    path2.to_string_lossy().to_string(),
+    "bukrs|belnr".to_string(),
+    "company".to_string(),
+    "inner",
+    false,
+    true,
+  )
+  .await
+  .unwrap_err();
+  assert!(err.to_string().contains("number of key columns"));
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_join_key_normalisation() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("left.csv");
+  std::fs::write(&path1, "code,name\n 00123,a\nABC,b\n１２,c\n1.0,d\n")?;
+  let path2 = temp_dir.path().join("right.csv");
+  std::fs::write(&path2, "code,qty\n123,1\nabc,2\n12,3\n1,4\n")?;
+
+  let options: insight::cmd::join::JoinOptions = serde_json::from_str(
+    r#"{"norm": {"trim": true, "case": true, "width": true, "zeros": true, "numeric": true}}"#,
+  )?;
+  insight::cmd::join::run_join_with_options(
+    path1.to_string_lossy().to_string(),
+    path2.to_string_lossy().to_string(),
+    "code".to_string(),
+    "code".to_string(),
+    "inner",
+    false,
+    true,
+    options,
+  )
+  .await?;
+  let context = std::fs::read_to_string(temp_dir.path().join("left_join.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "code,name,code,qty",
+    " 00123,a,123,1",
+    "ABC,b,abc,2",
+    "１２,c,12,3",
+    "1.0,d,1,4",
+  ];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_text_norm_numbers() -> anyhow::Result<()> {
+  let norm = insight::normalize::TextNorm {
+    numeric: true,
+    ..Default::default()
+  };
+  assert_eq!(norm.apply("1.50"), "1.5");
+  assert_eq!(norm.apply("-007"), "-7");
+  assert_eq!(norm.apply("+1e3"), "1000");
+  assert_eq!(norm.apply("-0.0"), "0");
+  assert_eq!(norm.apply("12345678901234567890"), "12345678901234567890");
+  assert_eq!(norm.apply("A-1"), "A-1");
+
+  let zeros = insight::normalize::TextNorm {
+    zeros: true,
+    ..Default::default()
+  };
+  assert_eq!(zeros.apply("000"), "0");
+  assert_eq!(zeros.apply("0.5"), "0.5");
+  assert_eq!(zeros.apply("007X"), "7X");
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn test_join_excel_and_jsonl_inputs() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("ledger.csv");
+  std::fs::write(&path1, "code,amount\nA,10\nB,20\nC,30\n")?;
+
+  let jsonl = temp_dir.path().join("master.jsonl");
+  std::fs::write(
+    &jsonl,
+    "{\"code\":\"A\",\"name\":\"rent\"}\n{\"code\":\"C\",\"name\":\"tax\"}\n",
+  )?;
+  let xlsx = temp_dir.path().join("master.xlsx");
+  let mut workbook = rust_xlsxwriter::Workbook::new();
+  let sheet = workbook.add_worksheet();
+  sheet.write_row(0, 0, ["code", "name"])?;
+  sheet.write_row(1, 0, ["A", "rent"])?;
+  sheet.write_row(2, 0, ["C", "tax"])?;
+  workbook.save(&xlsx)?;
+
+  let expected = vec!["code,amount,code,name", "A,10,A,rent", "C,30,C,tax"];
+  for right in [&jsonl, &xlsx] {
+    insight::cmd::join::run_join(
+      path1.to_string_lossy().to_string(),
+      right.to_string_lossy().to_string(),
+      "code".to_string(),
+      "code".to_string(),
+      "inner",
+      false,
+      true,
+    )
+    .await?;
+    let context = std::fs::read_to_string(temp_dir.path().join("ledger_join.csv"))?;
+    let result = context.trim().split('\n').collect::<Vec<_>>();
+    assert_eq!(expected, result);
+  }
+
+  // the excel sheet on the left side
+  insight::cmd::join::run_join(
+    xlsx.to_string_lossy().to_string(),
+    path1.to_string_lossy().to_string(),
+    "code".to_string(),
+    "code".to_string(),
+    "left_semi",
+    false,
+    true,
+  )
+  .await?;
+  let context = std::fs::read_to_string(temp_dir.path().join("master_join.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  assert_eq!(vec!["code,name", "A,rent", "C,tax"], result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_sort_merge_join() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("left.csv");
+  std::fs::write(
+    &path1,
+    "id,name\n3,Patrick\n1,Tom\n,Nobody\n2,Jerry\n1,Tommy\n",
+  )?;
+  let path2 = temp_dir.path().join("right.csv");
+  std::fs::write(&path2, "id,age\n4,19\n1,18\n3,20\n,0\n1,81\n")?;
+
+  let options: insight::cmd::join::JoinOptions = serde_json::from_str(r#"{"sort_merge": true}"#)?;
+  let cases = vec![
+    (
+      "inner",
+      vec![
+        "id,name,id,age",
+        "1,Tom,1,18",
+        "1,Tom,1,81",
+        "1,Tommy,1,18",
+        "1,Tommy,1,81",
+        "3,Patrick,3,20",
+      ],
+    ),
+    (
+      "left",
+      vec![
+        "id,name,id,age",
+        ",Nobody,,",
+        "1,Tom,1,18",
+        "1,Tom,1,81",
+        "1,Tommy,1,18",
+        "1,Tommy,1,81",
+        "2,Jerry,,",
+        "3,Patrick,3,20",
+      ],
+    ),
+    (
+      "full",
+      vec![
+        "id,name,id,age",
+        ",Nobody,,",
+        ",,,0",
+        "1,Tom,1,18",
+        "1,Tom,1,81",
+        "1,Tommy,1,18",
+        "1,Tommy,1,81",
+        "2,Jerry,,",
+        "3,Patrick,3,20",
+        ",,4,19",
+      ],
+    ),
+    ("left_anti", vec!["id,name", ",Nobody", "2,Jerry"]),
+    ("right_semi", vec!["id,age", "1,18", "1,81", "3,20"]),
+  ];
+  for (join_type, expected) in cases {
+    insight::cmd::join::run_join_with_options(
+      path1.to_string_lossy().to_string(),
+      path2.to_string_lossy().to_string(),
+      "id".to_string(),
+      "id".to_string(),
+      join_type,
+      false,
+      true,
+      options.clone(),
+    )
+    .await?;
+    let context = std::fs::read_to_string(temp_dir.path().join("left_join.csv"))?;
+    let result = context.trim().split('\n').collect::<Vec<_>>();
+    assert_eq!(expected, result, "{join_type}");
+  }
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_fuzzy_join() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("left.csv");
+  std::fs::write(&path1, "name,city\nJon Smith,NY\nJane Doe,LA\nBob,NY\n")?;
+  let path2 = temp_dir.path().join("right.csv");
+  std::fs::write(
+    &path2,
+    "customer,city,id\nJohn Smith,NY,1\nJon Smyth,LA,2\nJane Do,LA,3\nRobert,NY,4\n",
+  )?;
+
+  let cases = vec![
+    (
+      r#"{"fuzzy": {"top": 2}}"#,
+      vec![
+        "name,city,customer,city,id,fuzzy_score,fuzzy_key",
+        "Jon Smith,NY,John Smith,NY,1,0.9000,John Smith",
+        "Jon Smith,NY,Jon Smyth,LA,2,0.8889,Jon Smyth",
+        "Jane Doe,LA,Jane Do,LA,3,0.8750,Jane Do",
+      ],
+    ),
+    (
+      r#"{"fuzzy": {"top": 2, "block_left": "city", "block_right": "city"}}"#,
+      vec![
+        "name,city,customer,city,id,fuzzy_score,fuzzy_key",
+        "Jon Smith,NY,John Smith,NY,1,0.9000,John Smith",
+        "Jane Doe,LA,Jane Do,LA,3,0.8750,Jane Do",
+      ],
+    ),
+    (
+      r#"{"fuzzy": {"top": 5, "max_distance": 1, "block_prefix": 3}}"#,
+      vec![
+        "name,city,customer,city,id,fuzzy_score,fuzzy_key",
+        "Jon Smith,NY,Jon Smyth,LA,2,0.8889,Jon Smyth",
+        "Jane Doe,LA,Jane Do,LA,3,0.8750,Jane Do",
+      ],
+    ),
+  ];
+  for (options, expected) in cases {
+    insight::cmd::join::run_join_with_options(
+      path1.to_string_lossy().to_string(),
+      path2.to_string_lossy().to_string(),
+      "name".to_string(),
+      "customer".to_string(),
+      "fuzzy",
+      false,
+      true,
+      serde_json::from_str(options)?,
+    )
+    .await?;
+    let context = std::fs::read_to_string(temp_dir.path().join("left_join.csv"))?;
+    let result = context.trim().split('\n').collect::<Vec<_>>();
+    assert_eq!(expected, result, "{options}");
+  }
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_asof_join() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("ledger.csv");
+  std::fs::write(
+    &path1,
+    "date,ccy,amount\n2024-01-06,USD,10\n2024-01-08,USD,20\n2024-01-01,EUR,30\n2024-01-09,JPY,40\n",
+  )?;
+  let path2 = temp_dir.path().join("rates.csv");
+  std::fs::write(
+    &path2,
+    "ccy,rate_date,rate\nUSD,2024-01-05,7.1\nUSD,2024-01-08,7.2\nEUR,2024-01-02,7.8\n",
+  )?;
+
+  let cases = vec![
+    (
+      r#"{"left_on": "date", "right_on": "rate_date"}"#,
+      vec![
+        "2024-01-06,USD,10,USD,2024-01-05,7.1",
+        "2024-01-08,USD,20,USD,2024-01-08,7.2",
+        "2024-01-01,EUR,30,,,",
+        "2024-01-09,JPY,40,,,",
+      ],
+    ),
+    (
+      r#"{"left_on": "date", "right_on": "rate_date", "direction": "forward"}"#,
+      vec![
+        "2024-01-06,USD,10,USD,2024-01-08,7.2",
+        "2024-01-08,USD,20,USD,2024-01-08,7.2",
+        "2024-01-01,EUR,30,EUR,2024-01-02,7.8",
+        "2024-01-09,JPY,40,,,",
+      ],
+    ),
+    (
+      r#"{"left_on": "date", "right_on": "rate_date", "direction": "nearest"}"#,
+      vec![
+        "2024-01-06,USD,10,USD,2024-01-05,7.1",
+        "2024-01-08,USD,20,USD,2024-01-08,7.2",
+        "2024-01-01,EUR,30,EUR,2024-01-02,7.8",
+        "2024-01-09,JPY,40,,,",
+      ],
+    ),
+    (
+      r#"{"left_on": "date", "right_on": "rate_date", "direction": "nearest", "tolerance": 0.5}"#,
+      vec![
+        "2024-01-06,USD,10,,,",
+        "2024-01-08,USD,20,USD,2024-01-08,7.2",
+        "2024-01-01,EUR,30,,,",
+        "2024-01-09,JPY,40,,,",
+      ],
+    ),
+  ];
+  for (asof, expected) in cases {
+    let options: insight::cmd::join::JoinOptions =
+      serde_json::from_str(&format!(r#"{{"asof": {asof}}}"#))?;
+    insight::cmd::join::run_join_with_options(
+      path1.to_string_lossy().to_string(),
+      path2.to_string_lossy().to_string(),
+      "ccy".to_string(),
+      "ccy".to_string(),
+      "asof",
+      false,
+      true,
+      options,
+    )
+    .await?;
+    let context = std::fs::read_to_string(temp_dir.path().join("ledger_join.csv"))?;
+    let result = context.trim().split('\n').collect::<Vec<_>>();
+    assert_eq!("date,ccy,amount,ccy,rate_date,rate", result[0]);
+    assert_eq!(expected, result[1..], "{asof}");
+  }
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_asof_join_values() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("ledger.csv");
+  std::fs::write(&path1, "ccy,date\nUSD,20240106\n")?;
+  let path2 = temp_dir.path().join("rates.csv");
+  std::fs::write(
+    &path2,
+    "ccy,rate_date,rate\nUSD,2024-01-05,7.1\nUSD,2024-01-08,7.2\nEUR,2024-01-02,7.8\nEUR,2024-01-02,7.9\n",
+  )?;
+  let join = |asof: &str| {
+    let options: insight::cmd::join::JoinOptions =
+      serde_json::from_str(&format!(r#"{{"asof": {asof}}}"#)).unwrap();
+    insight::cmd::join::run_join_with_options(
+      path1.to_string_lossy().to_string(),
+      path2.to_string_lossy().to_string(),
+      "ccy".to_string(),
+      "ccy".to_string(),
+      "asof",
+      false,
+      true,
+      options,
+    )
+  };
+
+  // a number on the left and dates on the right
+  let err = join(r#"{"left_on": "date", "right_on": "rate_date"}"#)
+    .await
+    .unwrap_err();
+  assert!(
+    err
+      .to_string()
+      .starts_with("The asof value '20240106' is a number")
+  );
+
+  std::fs::write(&path1, "ccy,date\nUSD,2024-01-06\n")?;
+  let stats = join(r#"{"left_on": "date", "right_on": "rate_date"}"#).await?;
+  // EUR has the same date twice, USD has two dates
+  assert_eq!(stats.right_duplicate_keys, 1);
+  let context = std::fs::read_to_string(temp_dir.path().join("ledger_join.csv"))?;
+  assert_eq!(
+    "ccy,date,ccy,rate_date,rate\nUSD,2024-01-06,USD,2024-01-05,7.1\n",
+    context
+  );
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_join_stats() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("left.csv");
+  std::fs::write(&path1, "id,name\n1,Tom\n1,Tommy\n2,Jerry\n3,Patrick\n")?;
+  let path2 = temp_dir.path().join("right.csv");
+  std::fs::write(&path2, "id,age\n1,18\n1,81\n3,20\n4,19\n")?;
+
+  let cases = vec![
+    ("inner", r#"{"unmatched": true}"#, 5),
+    ("right", r#"{"unmatched": true}"#, 6),
+    ("right", r#"{"unmatched": true, "sort_merge": true}"#, 6),
+  ];
+  for (join_type, options, output_rows) in cases {
+    let stats = insight::cmd::join::run_join_with_options(
+      path1.to_string_lossy().to_string(),
+      path2.to_string_lossy().to_string(),
+      "id".to_string(),
+      "id".to_string(),
+      join_type,
+      false,
+      true,
+      serde_json::from_str(options)?,
+    )
+    .await?;
+    let unmatched_left = temp_dir.path().join("left_join_unmatched_left.csv");
+    let unmatched_right = temp_dir.path().join("left_join_unmatched_right.csv");
+    let expected = insight::cmd::join::JoinStats {
+      left_rows: 4,
+      right_rows: 4,
+      output_rows,
+      left_matched: 3,
+      left_unmatched: 1,
+      right_matched: 3,
+      right_unmatched: 1,
+      left_duplicate_keys: 1,
+      right_duplicate_keys: 1,
+      max_fanout: 2,
+      max_fanout_key: "1".to_string(),
+      unmatched_left_path: Some(unmatched_left.to_string_lossy().to_string()),
+      unmatched_right_path: Some(unmatched_right.to_string_lossy().to_string()),
+    };
+    assert_eq!(expected, stats, "{join_type} {options}");
+    assert_eq!(
+      "id,name\n2,Jerry\n",
+      std::fs::read_to_string(&unmatched_left)?
+    );
+    assert_eq!("id,age\n4,19\n", std::fs::read_to_string(&unmatched_right)?);
+  }
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_join_output_columns() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path1 = temp_dir.path().join("left.csv");
+  std::fs::write(&path1, "id,name,amount\n1,Tom,10\n2,Jerry,20\n")?;
+  let path2 = temp_dir.path().join("right.csv");
+  std::fs::write(&path2, "id,amount,note\n1,15,a\n3,35,c\n")?;
+
+  let cases = vec![
+    (
+      "inner",
+      r#"{"left_columns": "name", "right_columns": "note"}"#,
+      "name,note\nTom,a\n",
+    ),
+    (
+      "inner",
+      r#"{"suffixes": true, "right_suffix": "_r"}"#,
+      "id_left,name,amount_left,id_r,amount_r,note\n1,Tom,10,1,15,a\n",
+    ),
+    (
+      "full",
+      r#"{"coalesce_keys": true, "suffixes": true, "left_columns": "id|amount", "right_columns": "amount"}"#,
+      "id,amount_left,amount_right\n1,10,15\n2,20,\n3,,35\n",
+    ),
+    (
+      "full",
+      r#"{"coalesce_keys": true, "left_columns": "name", "sort_merge": true}"#,
+      "id,name,amount,note\n1,Tom,15,a\n2,Jerry,,\n3,,35,c\n",
+    ),
+    ("right_semi", r#"{"right_columns": "note"}"#, "note\na\n"),
+  ];
+  for (join_type, options, expected) in cases {
+    insight::cmd::join::run_join_with_options(
+      path1.to_string_lossy().to_string(),
+      path2.to_string_lossy().to_string(),
+      "id".to_string(),
+      "id".to_string(),
+      join_type,
+      false,
+      true,
+      serde_json::from_str(options)?,
+    )
+    .await?;
+    let output = std::fs::read_to_string(temp_dir.path().join("left_join.csv"))?;
+    assert_eq!(expected, output, "{join_type} {options}");
+  }
+
+  Ok(temp_dir.close()?)
+}
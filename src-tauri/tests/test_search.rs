@@ -3,9 +3,18 @@ use std::io::Write;
 use insight::{
   cmd::{
     idx::create_index,
-    search::{filters, filters_multi},
+    search::{
+      columns::{self, ColumnSelector},
+      dates::DateMatcher,
+      extract::{self, ExtractOptions, Extractor},
+      filters, filters_multi,
+      fuzzy::{self, Fuzzy, Metric},
+      lookup,
+      matcher::Matcher,
+    },
   },
   io::csv::options::CsvOptions,
+  normalize::TextNorm,
 };
 
 async fn create_temp_csv() -> anyhow::Result<(
@@ -1273,3 +1282,844 @@ async fn test_ends_with_multi() -> anyhow::Result<()> {
 
   Ok(temp_dir.close()?)
 }
+
+#[tokio::test]
+async fn test_lookup_file() -> anyhow::Result<()> {
+  let (temp_dir, rdr, wtr, output_path, path) = create_temp_csv().await?;
+
+  let lookup_path = temp_dir.path().join("lookup.txt");
+  std::fs::write(&lookup_path, "om\n\nerr\nom\n")?;
+  let values = lookup::load_values(&lookup_path, None)?;
+  assert_eq!(values, vec!["om", "err"]);
+
+  let opts = CsvOptions::new(path);
+  let match_rows = lookup::lookup_search(
+    rdr,
+    wtr,
+    opts,
+    None,
+    "name".to_string(),
+    lookup::Lookup::new(values, "contains")?,
+    Some("matched".to_string()),
+    false,
+    Some(1),
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 2);
+
+  let context = std::fs::read_to_string(output_path)?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "name,age,gender,matched",
+    "Tom,18,male,om",
+    "Jerry,19,male,err",
+  ];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_lookup_csv_column() -> anyhow::Result<()> {
+  let (temp_dir, _, _, _, _) = create_temp_csv().await?;
+
+  let lookup_path = temp_dir.path().join("vendors.csv");
+  std::fs::write(&lookup_path, "id,vendor\n1,Pat\n2, Sandy \n3,\n")?;
+  let values = lookup::load_values(&lookup_path, Some("vendor"))?;
+  assert_eq!(values, vec!["Pat", "Sandy"]);
+
+  let lookup = lookup::Lookup::new(values, "starts_with")?;
+  assert_eq!(lookup.find("Patrick"), Some("Pat"));
+  assert_eq!(lookup.find("Sandy"), Some("Sandy"));
+  assert_eq!(lookup.find("xPat"), None);
+  assert!(lookup::Lookup::new(vec!["a".to_string()], "regex").is_err());
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_normalized_matcher() -> anyhow::Result<()> {
+  let norm = TextNorm {
+    case: true,
+    collapse: true,
+    width: true,
+    ..Default::default()
+  };
+  assert_eq!(norm.apply(" ＡＢＣ　 公司 "), "abc 公司");
+
+  let matcher = Matcher::new("equal", "abc公司", norm)?;
+  assert!(matcher.is_match("ABC公司 "));
+  assert!(matcher.is_match("ａｂｃ公司"));
+  assert!(!matcher.is_match("abc 公司"));
+
+  let matcher = Matcher::new(
+    "gt",
+    "１０",
+    TextNorm {
+      nfkc: true,
+      ..Default::default()
+    },
+  )?;
+  assert!(matcher.is_match("１１"));
+  assert!(!matcher.is_match("9"));
+
+  let matcher = Matcher::new("regex", "^TO", norm)?;
+  assert!(matcher.is_match("Tom"));
+
+  let lookup = lookup::Lookup::with_norm(vec!["ABC公司".to_string()], "starts_with", norm)?;
+  assert_eq!(lookup.find("ａｂｃ公司 上海"), Some("ABC公司"));
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn test_normalized_search() -> anyhow::Result<()> {
+  let (temp_dir, rdr, wtr, output_path, path) = create_temp_csv().await?;
+
+  let norm = TextNorm {
+    case: true,
+    trim: true,
+    ..Default::default()
+  };
+  let match_rows = insight::cmd::search::matcher::matcher_search(
+    rdr,
+    wtr,
+    CsvOptions::new(path),
+    None,
+    "name".to_string(),
+    Matcher::new("equal", " tom|SANDY", norm)?,
+    false,
+    Some(1),
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 2);
+
+  let context = std::fs::read_to_string(output_path)?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec!["name,age,gender", "Tom,18,male", "Sandy,24,female"];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_fuzzy_metrics() -> anyhow::Result<()> {
+  assert_eq!(Metric::Levenshtein.score("acme", "acme"), 1.0);
+  assert!(Metric::Levenshtein.score("acme ltd", "acme ltd.") > 0.8);
+  assert!(Metric::JaroWinkler.score("martha", "marhta") > 0.9);
+  assert_eq!(Metric::TokenSet.score("ltd acme acme", "acme ltd"), 1.0);
+  assert!(Metric::parse(Some("soundex")).is_err());
+
+  let fuzzy = Fuzzy::new(
+    Metric::Levenshtein,
+    Some(80.0),
+    &["Patrick".to_string()],
+    TextNorm::default(),
+  )?;
+  assert!(fuzzy.best("Patrik").is_some());
+  assert!(fuzzy.best("Sandy").is_none());
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn test_fuzzy_search() -> anyhow::Result<()> {
+  let (temp_dir, rdr, wtr, output_path, path) = create_temp_csv().await?;
+
+  let fuzzy = Fuzzy::new(
+    Metric::Levenshtein,
+    Some(0.7),
+    &["Jery".to_string(), "Sandi".to_string()],
+    TextNorm::default(),
+  )?;
+  let match_rows = fuzzy::fuzzy_search(
+    rdr,
+    wtr,
+    CsvOptions::new(path),
+    None,
+    "name".to_string(),
+    fuzzy,
+    None,
+    false,
+    Some(1),
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 2);
+
+  let context = std::fs::read_to_string(output_path)?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "name,age,gender,fuzzy_score",
+    "Jerry,19,male,0.8000",
+    "Sandy,24,female,0.8000",
+  ];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_fuzzy_search_chain() -> anyhow::Result<()> {
+  let (temp_dir, _, _, output_path, path) = create_temp_csv().await?;
+
+  let configs: Vec<insight::cmd::search::perform::ColumnConfig> = serde_json::from_str(
+    r#"[
+      {"column": "name", "mode": "fuzzy", "condition": "Patrik", "metric": "jaro_winkler", "threshold": 0.9},
+      {"column": "gender", "mode": "equal", "condition": "male"}
+    ]"#,
+  )?;
+  let match_rows = insight::cmd::search::filters_chain::search_with_chain(
+    path,
+    configs,
+    vec!["and".to_string()],
+    1,
+    true,
+    false,
+    false,
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 1);
+
+  let mut rdr = csv::Reader::from_path(output_path)?;
+  assert_eq!(
+    rdr.headers()?.iter().collect::<Vec<_>>(),
+    vec!["name", "age", "gender", "name_score"]
+  );
+  let record = rdr.records().next().unwrap()?;
+  assert_eq!(&record[0], "Patrick");
+  assert!(record[3].parse::<f64>()? >= 0.9);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_fuzzy_search_chain_selector() -> anyhow::Result<()> {
+  let (temp_dir, _, _, output_path, path) = create_temp_csv().await?;
+
+  let configs: Vec<insight::cmd::search::perform::ColumnConfig> = serde_json::from_str(
+    r#"[
+      {"column": "gender", "mode": "equal", "condition": "male"},
+      {"column": "name|gender", "mode": "fuzzy", "condition": "Patrik", "metric": "jaro_winkler", "threshold": 0.9}
+    ]"#,
+  )?;
+  let match_rows = insight::cmd::search::filters_chain::search_with_chain(
+    path,
+    configs,
+    vec!["and".to_string()],
+    1,
+    true,
+    false,
+    false,
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 1);
+
+  // the score of a selector is named after the position of its condition
+  let mut rdr = csv::Reader::from_path(output_path)?;
+  assert_eq!(
+    rdr.headers()?.iter().collect::<Vec<_>>(),
+    vec!["name", "age", "gender", "fuzzy_score_2", "matched_columns"]
+  );
+  let record = rdr.records().next().unwrap()?;
+  assert_eq!(&record[0], "Patrick");
+  assert!(record[3].parse::<f64>()? >= 0.9);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_search_columns() -> anyhow::Result<()> {
+  let (temp_dir, rdr, wtr, output_path, _) = create_temp_csv().await?;
+
+  let matcher = Matcher::new("contains", "e", TextNorm::default())?;
+  let match_rows = columns::columns_search(
+    rdr,
+    wtr,
+    &ColumnSelector::parse("re:^(name|gender)$")?,
+    move |v: &str| matcher.is_match(v),
+    false,
+    None,
+    false,
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 4);
+
+  let context = std::fs::read_to_string(output_path)?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "name,age,gender,matched_columns",
+    "Tom,18,male,gender",
+    "Jerry,19,male,name|gender",
+    "Patrick,4,male,gender",
+    "Sandy,24,female,gender",
+  ];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_search_all_columns_negated() -> anyhow::Result<()> {
+  let (temp_dir, rdr, wtr, output_path, _) = create_temp_csv().await?;
+
+  let matcher = Matcher::new("not_contains", "1", TextNorm::default())?;
+  assert!(matcher.is_negated());
+  let match_rows = columns::columns_search(
+    rdr,
+    wtr,
+    &ColumnSelector::parse("*")?,
+    move |v: &str| matcher.is_match(v),
+    true,
+    Some("columns".to_string()),
+    false,
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 2);
+
+  let context = std::fs::read_to_string(output_path)?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "name,age,gender,columns",
+    "Patrick,4,male,name|age|gender",
+    "Sandy,24,female,name|age|gender",
+  ];
+  assert_eq!(expected, result);
+
+  assert!(
+    ColumnSelector::parse("name|missing")?
+      .resolve(&csv::StringRecord::from(vec!["name"]))
+      .is_err()
+  );
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_search_many() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  std::fs::write(temp_dir.path().join("a.csv"), "id,name\n1,Tom\n2,Jerry\n")?;
+  std::fs::write(
+    temp_dir.path().join("b.csv"),
+    "name,city,id\nTommy,Paris,3\nSandy,Rome,4\n",
+  )?;
+  std::fs::write(temp_dir.path().join("c.csv"), "code\nTom\n")?;
+  std::fs::write(
+    temp_dir.path().join("d.csv"),
+    "name,id,id\nTomas,5,6\nJerry,7,8\n",
+  )?;
+
+  let inputs = insight::cmd::search::many::collect_inputs(temp_dir.path().to_str().unwrap())?;
+  assert_eq!(inputs.len(), 4);
+
+  let output_path = temp_dir.path().join("search_many.csv");
+  let summary = insight::cmd::search::many::search_files(
+    &inputs,
+    "name",
+    Box::new(|v: &str| v.starts_with("Tom")),
+    0,
+    true,
+    Some(2),
+    &output_path,
+  )?;
+  assert_eq!(summary.matches, 3);
+  assert_eq!(summary.files.len(), 4);
+  assert!(summary.files[2].error.is_some());
+
+  let a = inputs[0].to_string_lossy().to_string();
+  let b = inputs[1].to_string_lossy().to_string();
+  let d = inputs[3].to_string_lossy().to_string();
+  let context = std::fs::read_to_string(&output_path)?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "source_file,sheet,line,id,name,city,id_2".to_string(),
+    format!("{a},,2,1,Tom,,"),
+    format!("{b},,2,3,Tommy,Paris,"),
+    format!("{d},,2,5,Tomas,,6"),
+  ];
+  assert_eq!(expected, result);
+
+  // the output of a previous run is not searched again
+  assert_eq!(
+    insight::cmd::search::many::collect_inputs(temp_dir.path().to_str().unwrap())?.len(),
+    4
+  );
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_date_matcher() -> anyhow::Result<()> {
+  let before = DateMatcher::new("date_before", "2024-03-15", None)?;
+  assert!(before.is_match("2024/3/5"));
+  assert!(!before.is_match("2024-03-15"));
+  assert!(!before.is_match("not a date"));
+
+  let between = DateMatcher::new("date_between", "2024-03-01|2024-03-15", None)?;
+  assert!(between.is_match("2024-03-15 18:30:00"));
+  assert!(!between.is_match("2024-03-16"));
+
+  let month = DateMatcher::new("same_month", "2024-03", None)?;
+  assert!(month.is_match("20240331"));
+  assert!(!month.is_match("2023-03-31"));
+  let quarter = DateMatcher::new("same_quarter", "2024Q2", None)?;
+  assert!(quarter.is_match("2024-06-30"));
+  assert!(!quarter.is_match("2024-07-01"));
+  assert!(DateMatcher::new("same_year", "2024", None)?.is_match("2024/12/31"));
+
+  assert!(DateMatcher::new("weekend", "", None)?.is_match("2024-03-16"));
+  assert!(DateMatcher::new("weekday", "", None)?.is_match("2024-03-15"));
+
+  let explicit = DateMatcher::new("date_after", "01/03/2024", Some("%d/%m/%Y"))?;
+  assert!(explicit.is_match("05/03/2024"));
+  assert!(!explicit.is_match("03/02/2024"));
+  assert!(DateMatcher::new("date_before", "yesterday", None).is_err());
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn test_date_search_chain() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path = temp_dir.path().join("orders.csv");
+  std::fs::write(
+    &path,
+    "id,created\n1,2024/3/5\n2,2024-03-15\n3,2024-03-16\n4,\n",
+  )?;
+
+  let configs: Vec<insight::cmd::search::perform::ColumnConfig> = serde_json::from_str(
+    r#"[
+      {"column": "created", "mode": "date_between", "condition": "2024-03-01|2024-03-31"},
+      {"column": "created", "mode": "weekday", "condition": ""}
+    ]"#,
+  )?;
+  let match_rows = insight::cmd::search::filters_chain::search_with_chain(
+    path.to_str().unwrap(),
+    configs,
+    vec!["and".to_string()],
+    0,
+    true,
+    false,
+    false,
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 2);
+
+  let context = std::fs::read_to_string(temp_dir.path().join("orders_search.csv"))?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  assert_eq!(vec!["id,created", "1,2024/3/5", "2,2024-03-15"], result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_count_search() -> anyhow::Result<()> {
+  let (temp_dir, _, _, output_path, path) = create_temp_csv().await?;
+
+  // the index exists, counted in parallel
+  let json = insight::cmd::search::stats::count_search(
+    &path,
+    "name",
+    "contains_multi",
+    "o|a",
+    1,
+    true,
+    false,
+    Some(2),
+    None,
+    TextNorm::default(),
+    None,
+    None,
+    serde_json::from_str(r#"{"preview": 2}"#)?,
+    insight::utils::MockEmitter::default(),
+  )
+  .await?;
+  let stats: serde_json::Value = serde_json::from_str(&json)?;
+  assert_eq!(stats["rows"], 4);
+  assert_eq!(stats["matches"], 3);
+  assert_eq!(stats["counts"]["o"], 1);
+  assert_eq!(stats["counts"]["a"], 2);
+  assert_eq!(stats["preview"].as_array().unwrap().len(), 2);
+  assert_eq!(stats["preview"][0]["name"], "Tom");
+  assert_eq!(stats["preview"][1]["name"], "Patrick");
+  assert!(!std::path::Path::new(&output_path).exists());
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_count_search_chain() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path = temp_dir.path().join("input.csv");
+  std::fs::write(
+    &path,
+    "name,age,gender\nTom,18,male\nJerry,19,male\nPatrick,4,male\nSandy,24,female\n",
+  )?;
+
+  let configs: Vec<insight::cmd::search::perform::ColumnConfig> = serde_json::from_str(
+    r#"[
+      {"column": "gender", "mode": "equal", "condition": "male"},
+      {"column": "age", "mode": "gt", "condition": "10"}
+    ]"#,
+  )?;
+  let json = insight::cmd::search::filters_chain::count_with_chain(
+    path.to_str().unwrap(),
+    configs,
+    vec!["and".to_string()],
+    0,
+    true,
+    false,
+    Default::default(),
+    insight::utils::MockEmitter::default(),
+  )
+  .await?;
+  let stats: serde_json::Value = serde_json::from_str(&json)?;
+  assert_eq!(stats["rows"], 4);
+  assert_eq!(stats["matches"], 2);
+  assert_eq!(stats["counts"]["gender equal male"], 3);
+  assert_eq!(stats["counts"]["age gt 10"], 3);
+  assert_eq!(stats["preview"][1]["name"], "Jerry");
+  assert!(!temp_dir.path().join("input_search.csv").exists());
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_highlighter() -> anyhow::Result<()> {
+  use insight::cmd::search::highlight::Highlighter;
+
+  let contains = Highlighter::new("contains", "a|ri", TextNorm::default(), None)?.unwrap();
+  let h = contains.highlight("name", "Patrick").unwrap();
+  assert_eq!(
+    h.spans.iter().map(|s| (s.start, s.end)).collect::<Vec<_>>(),
+    vec![(1, 2), (3, 5)]
+  );
+  assert!(contains.highlight("name", "Tom").is_none());
+
+  let regex = Highlighter::new("regex", r"(?<city>北京)(\d+)", TextNorm::default(), None)?.unwrap();
+  let h = regex.highlight("addr", "在北京12号").unwrap();
+  assert_eq!((h.spans[0].start, h.spans[0].end), (3, 11));
+  assert_eq!((h.spans[0].char_start, h.spans[0].char_end), (1, 5));
+  assert_eq!(h.captures[0].name, "city");
+  assert_eq!(h.captures[1].name, "2");
+  assert_eq!(h.captures[1].text, "12");
+
+  let fuzzy = Highlighter::new("fuzzy", "Patrik", TextNorm::default(), None)?.unwrap();
+  assert!(fuzzy.highlight("name", "Patrick").unwrap().score.unwrap() > 0.8);
+  assert!(Highlighter::new("gt", "10", TextNorm::default(), None)?.is_none());
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn test_count_search_highlight() -> anyhow::Result<()> {
+  let (temp_dir, _, _, _, path) = create_temp_csv().await?;
+
+  let json = insight::cmd::search::stats::count_search(
+    &path,
+    "name|gender",
+    "contains",
+    "e",
+    1,
+    true,
+    false,
+    Some(1),
+    None,
+    TextNorm {
+      case: true,
+      ..Default::default()
+    },
+    None,
+    None,
+    serde_json::from_str(r#"{"preview": 2, "highlight": true}"#)?,
+    insight::utils::MockEmitter::default(),
+  )
+  .await?;
+  let stats: serde_json::Value = serde_json::from_str(&json)?;
+  assert_eq!(stats["matches"], 4);
+  let highlights = stats["highlights"].as_array().unwrap();
+  assert_eq!(highlights.len(), 2);
+  // Tom: only gender matches
+  assert_eq!(highlights[0].as_array().unwrap().len(), 1);
+  assert_eq!(highlights[0][0]["column"], "gender");
+  assert_eq!(highlights[0][0]["spans"][0]["start"], 3);
+  // Jerry: both columns
+  assert_eq!(highlights[1][0]["column"], "name");
+  assert_eq!(highlights[1][0]["spans"][0]["char_start"], 1);
+  assert_eq!(highlights[1][1]["column"], "gender");
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_count_search_highlight_trim() -> anyhow::Result<()> {
+  let (temp_dir, _, _, _, path) = create_temp_csv().await?;
+
+  let json = insight::cmd::search::stats::count_search(
+    &path,
+    "name|gender",
+    "contains",
+    "e",
+    1,
+    true,
+    false,
+    Some(1),
+    None,
+    TextNorm {
+      case: true,
+      trim: true,
+      ..Default::default()
+    },
+    None,
+    None,
+    serde_json::from_str(r#"{"preview": 2, "highlight": true}"#)?,
+    insight::utils::MockEmitter::default(),
+  )
+  .await?;
+  let stats: serde_json::Value = serde_json::from_str(&json)?;
+  let highlights = stats["highlights"].as_array().unwrap();
+  assert_eq!(highlights.len(), 2);
+  // trimmed cells are highlighted whole, but only the columns that match
+  assert_eq!(highlights[0].as_array().unwrap().len(), 1);
+  assert_eq!(highlights[0][0]["column"], "gender");
+  assert_eq!(highlights[0][0]["spans"][0]["start"], 0);
+  assert_eq!(highlights[0][0]["spans"][0]["end"], 4);
+  assert_eq!(highlights[1].as_array().unwrap().len(), 2);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_extractor() -> anyhow::Result<()> {
+  let first = Extractor::new(
+    r"(?<prefix>INV|PO)-(?<number>\d+)",
+    &ExtractOptions::default(),
+    false,
+  )?;
+  assert_eq!(first.headers(), ["prefix", "number"]);
+  assert_eq!(
+    first.extract("paid INV-001 and PO-17"),
+    Some(vec!["INV".to_string(), "001".to_string()])
+  );
+  assert_eq!(first.extract("nothing here"), None);
+
+  let all = Extractor::new(
+    r"(?<prefix>INV|PO)-(?<number>\d+)",
+    &ExtractOptions {
+      all: true,
+      separator: Some(";".to_string()),
+    },
+    false,
+  )?;
+  assert_eq!(
+    all.extract("paid INV-001 and PO-17"),
+    Some(vec!["INV;PO".to_string(), "001;17".to_string()])
+  );
+
+  // groups of the other alternative are empty, every match keeps its place
+  let alternatives = Extractor::new(
+    r"(?P<a>x)|(?P<b>y)",
+    &ExtractOptions {
+      all: true,
+      separator: Some(";".to_string()),
+    },
+    false,
+  )?;
+  assert_eq!(
+    alternatives.extract("x y x"),
+    Some(vec!["x;;x".to_string(), ";y;".to_string()])
+  );
+
+  let mut clash = Extractor::new(
+    r"(?<name>\w+)-(?<age>\d+)-(?<id>\d+)",
+    &ExtractOptions::default(),
+    false,
+  )?;
+  clash.rename_clashes(&["name", "age"]);
+  assert_eq!(clash.headers(), ["name_2", "age_2", "id"]);
+
+  let unnamed = Extractor::new(r"(\d+)-(\d+)", &ExtractOptions::default(), false)?;
+  assert_eq!(unnamed.headers(), ["group_1", "group_2"]);
+  let whole = Extractor::new(r"inv-\d+", &ExtractOptions::default(), true)?;
+  assert_eq!(whole.headers(), ["match"]);
+  assert_eq!(whole.extract("INV-9"), Some(vec!["INV-9".to_string()]));
+
+  Ok(())
+}
+
+#[tokio::test]
+async fn test_extract_search() -> anyhow::Result<()> {
+  let (temp_dir, rdr, wtr, output_path, path) = create_temp_csv().await?;
+
+  let match_rows = extract::extract_search(
+    rdr,
+    wtr,
+    CsvOptions::new(path),
+    None,
+    "name".to_string(),
+    Extractor::new(r"(?<vowel>[aeiou])[rt]", &ExtractOptions::default(), false)?,
+    false,
+    Some(1),
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 2);
+
+  let context = std::fs::read_to_string(output_path)?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec![
+    "name,age,gender,vowel",
+    "Jerry,19,male,e",
+    "Patrick,4,male,a",
+  ];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_irregular_extract() -> anyhow::Result<()> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let path = temp_dir.path().join("log.txt");
+  std::fs::write(&path, "start\nerror code=42, retry\nok\nerror code=7\n")?;
+  let output_path = temp_dir.path().join("log_search.csv");
+
+  let reader: Box<dyn std::io::Read + Send> = Box::new(std::fs::File::open(&path)?);
+  let match_rows = extract::irregular_extract(
+    std::io::BufReader::new(reader),
+    output_path.clone(),
+    Extractor::new(r"code=(?<code>\d+)", &ExtractOptions::default(), false)?,
+  )
+  .await?;
+  assert_eq!(match_rows, "2");
+
+  let context = std::fs::read_to_string(output_path)?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec!["line,code", "\"error code=42, retry\",42", "error code=7,7"];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_search_tree() -> anyhow::Result<()> {
+  let (temp_dir, _, _, output_path, path) = create_temp_csv().await?;
+
+  // male and not (age between 10 and 18 or name starts with P), or name is Sandy
+  let tree: insight::cmd::search::tree::ConditionNode = serde_json::from_str(
+    r#"{"or": [
+      {"and": [
+        {"column": "gender", "mode": "equal", "condition": "male"},
+        {"not": {"or": [
+          {"column": "age", "mode": "between", "condition": "10|18"},
+          {"column": "name", "mode": "starts_with_multi", "condition": "P|Q"}
+        ]}}
+      ]},
+      {"column": "name|gender", "mode": "regex", "condition": "^San"}
+    ]}"#,
+  )?;
+  let match_rows = insight::cmd::search::tree::search_with_tree(
+    &path,
+    tree,
+    1,
+    true,
+    false,
+    false,
+    insight::utils::MockEmitter::default(),
+  )
+  .await?
+  .parse::<usize>()?;
+  assert_eq!(match_rows, 2);
+
+  let context = std::fs::read_to_string(output_path)?;
+  let result = context.trim().split('\n').collect::<Vec<_>>();
+  let expected = vec!["name,age,gender", "Jerry,19,male", "Sandy,24,female"];
+  assert_eq!(expected, result);
+
+  Ok(temp_dir.close()?)
+}
+
+#[tokio::test]
+async fn test_search_tree_invalid_node() -> anyhow::Result<()> {
+  let (temp_dir, _, _, _, path) = create_temp_csv().await?;
+
+  let search = |json: &'static str| {
+    let path = path.clone();
+    async move {
+      insight::cmd::search::tree::search_with_tree(
+        path,
+        serde_json::from_str(json)?,
+        1,
+        true,
+        false,
+        false,
+        insight::utils::MockEmitter::default(),
+      )
+      .await
+    }
+  };
+
+  let err = search(
+    r#"{"and": [
+      {"column": "name", "mode": "equal", "condition": "Tom"},
+      {"not": {"column": "age", "mode": "gt", "condition": "ten"}}
+    ]}"#,
+  )
+  .await
+  .unwrap_err();
+  assert!(err.to_string().starts_with("root.and[1].not (age gt)"));
+
+  let err = search(r#"{"or": [{"column": "city", "mode": "equal", "condition": "x"}]}"#)
+    .await
+    .unwrap_err();
+  assert!(err.to_string().starts_with("root.or[0] (city equal)"));
+
+  let err = search(r#"{"and": []}"#).await.unwrap_err();
+  assert_eq!(err.to_string(), "root: empty 'and' group");
+
+  // structural errors name the node too
+  let err = search(r#"{"or": [{"not": {"column": "age", "mode": "gt"}}]}"#)
+    .await
+    .unwrap_err();
+  assert!(
+    err
+      .to_string()
+      .starts_with("root.or[0].not: missing field `condition`")
+  );
+
+  let err = search(r#"{"and": [{"nott": {"column": "age", "mode": "gt", "condition": "1"}}]}"#)
+    .await
+    .unwrap_err();
+  assert!(
+    err
+      .to_string()
+      .starts_with("root.and[0]: unknown key 'nott'")
+  );
+
+  let err = search(r#"{"or": {"column": "age", "mode": "gt", "condition": "1"}}"#)
+    .await
+    .unwrap_err();
+  assert!(
+    err
+      .to_string()
+      .starts_with("root: 'or' expects a list of nodes")
+  );
+
+  Ok(temp_dir.close()?)
+}
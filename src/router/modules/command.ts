@@ -177,6 +177,24 @@ export default {
         showLink: false
       }
     },
+    {
+      path: "/command/components/diff",
+      name: "diff",
+      component: () => import("@/views/command/components/diff.vue"),
+      meta: {
+        title: "diff",
+        showLink: false
+      }
+    },
+    {
+      path: "/command/components/dedup",
+      name: "dedup",
+      component: () => import("@/views/command/components/dedup.vue"),
+      meta: {
+        title: "dedup",
+        showLink: false
+      }
+    },
     {
       path: "/command/components/sort",
       name: "sort",
@@ -113,6 +113,18 @@ export const useCommandStore = defineStore("command", {
         description: "Joins two sets of CSV data on the specified columns",
         route: "/command/components/join"
       },
+      {
+        title: "Diff",
+        icon: "ri:git-pull-request-line",
+        description: "Compare two files by key columns",
+        route: "/command/components/diff"
+      },
+      {
+        title: "Dedup",
+        icon: "ri:delete-row",
+        description: "Remove duplicate rows by key columns",
+        route: "/command/components/dedup"
+      },
       {
         title: "Sort",
         icon: "ri:sort-alphabet-asc",
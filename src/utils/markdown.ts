@@ -983,6 +983,100 @@ sample file
 `;
 }
 
+export function mdDiff() {
+  return `
+\`\`\`
+old file
+┌────┬───────┬──────┐
+│ id │ name  │ city │
+├────┼───────┼──────┤
+│ 1  │ Acme  │ NY   │
+│ 2  │ Beta  │ LA   │
+│ 3  │ Gamma │ SF   │
+└────┴───────┴──────┘
+\`\`\`
+
+\`\`\`
+new file
+┌────┬───────┬────────┐
+│ id │ name  │ city   │
+├────┼───────┼────────┤
+│ 1  │ Acme  │ Boston │
+│ 3  │ Gamma │ SF     │
+│ 4  │ Delta │ TX     │
+└────┴───────┴────────┘
+\`\`\`
+
+\`\`\`
+diff result (keys='id'), written to <old>_diff.csv
+┌─────────┬────┬───────┬────────┐
+│ diff    │ id │ name  │ city   │
+├─────────┼────┼───────┼────────┤
+│ changed │ 1  │ Acme  │ Boston │
+│ added   │ 4  │ Delta │ TX     │
+│ removed │ 2  │ Beta  │ LA     │
+└─────────┴────┴───────┴────────┘
+\`\`\`
+
+\`\`\`
+changed values, written to <old>_diff_cells.csv
+┌────┬────────┬───────────┬───────────┐
+│ id │ column │ old_value │ new_value │
+├────┼────────┼───────────┼───────────┤
+│ 1  │ city   │ NY        │ Boston    │
+└────┴────────┴───────────┴───────────┘
+\`\`\`
+`;
+}
+
+export function mdDedup() {
+  return `
+\`\`\`
+sample file
+┌────┬───────┐
+│ id │ name  │
+├────┼───────┤
+│ 1  │ Tom   │
+│ 2  │ Jerry │
+│ 1  │ tom   │
+│ 3  │ Pat   │
+│ 2  │ Jerry │
+└────┴───────┘
+\`\`\`
+
+\`\`\`
+dedup result (keys='id', keep='first')
+┌────┬───────┐
+│ id │ name  │
+├────┼───────┤
+│ 1  │ Tom   │
+│ 2  │ Jerry │
+│ 3  │ Pat   │
+└────┴───────┘
+\`\`\`
+
+\`\`\`
+dedup result (keys='id', keep='last')
+┌────┬───────┐
+│ id │ name  │
+├────┼───────┤
+│ 1  │ tom   │
+│ 3  │ Pat   │
+│ 2  │ Jerry │
+└────┴───────┘
+\`\`\`
+
+\`\`\`
+dedup result (keys='id', keep='none')
+┌────┬──────┐
+│ id │ name │
+├────┼──────┤
+│ 3  │ Pat  │
+└────┴──────┘
+\`\`\`
+`;
+}
+
 export function mdReverse() {
   return `
 \`\`\`